@@ -1,47 +0,0 @@
-; Keywords
-[
-  "async"
-  "await"
-  "break"
-  "case"
-  "catch"
-  "class"
-  "const"
-  "continue"
-  "debugger"
-  "default"
-  "delete"
-  "do"
-  "else"
-  "export"
-  "extends"
-  "finally"
-  "for"
-  "function"
-  "if"
-  "import"
-  "in"
-  "instanceof"
-  "let"
-  "new"
-  "of"
-  "return"
-  "static"
-  "super"
-  "switch"
-  "this"
-  "throw"
-  "try"
-  "typeof"
-  "var"
-  "void"
-  "while"
-  "with"
-  "yield"
-] @keyword
-
-(function_declaration name: (identifier) @function)
-(call_expression function: (identifier) @function.call)
-(string) @string
-(number) @number
-(comment) @comment
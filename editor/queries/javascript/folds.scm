@@ -0,0 +1,14 @@
+; Code folding. Each @fold node can be collapsed to its first line.
+
+[
+  (class_body)
+  (statement_block)
+  (switch_body)
+  (object)
+  (array)
+  (arguments)
+  (template_string)
+  (named_imports)
+  (jsx_element)
+  (comment)
+] @fold
@@ -0,0 +1,152 @@
+; See rust.scm for how overlapping captures are resolved.
+
+; Identifier conventions
+([
+  (identifier)
+  (shorthand_property_identifier)
+  (shorthand_property_identifier_pattern)
+ ] @constant
+ (#match? @constant "^[A-Z_][A-Z\\d_]+$"))
+
+((identifier) @constructor
+ (#match? @constructor "^[A-Z]"))
+
+((identifier) @variable.builtin
+ (#any-of? @variable.builtin "arguments" "module" "console" "window" "document"))
+
+((identifier) @function.builtin
+ (#eq? @function.builtin "require"))
+
+(this) @variable.builtin
+(super) @variable.builtin
+
+; Function and method definitions
+(function_declaration name: (identifier) @function)
+(function_expression name: (identifier) @function)
+(generator_function_declaration name: (identifier) @function)
+(method_definition name: (property_identifier) @function.method)
+(variable_declarator
+  name: (identifier) @function
+  value: [(function_expression) (arrow_function)])
+(pair
+  key: (property_identifier) @function.method
+  value: [(function_expression) (arrow_function)])
+
+; Function and method calls
+(call_expression function: (identifier) @function.call)
+(call_expression
+  function: (member_expression
+    property: (property_identifier) @function.method.call))
+(new_expression constructor: (identifier) @constructor)
+
+; Parameters
+(formal_parameters (identifier) @parameter)
+(arrow_function parameter: (identifier) @parameter)
+
+; Properties
+(property_identifier) @property
+(shorthand_property_identifier) @property
+
+; Literals
+[
+  (true)
+  (false)
+  (null)
+  (undefined)
+] @constant.builtin
+
+(string) @string
+(template_string) @string
+(escape_sequence) @string.escape
+(regex) @string.regex
+(number) @number
+
+(comment) @comment
+
+; Embedded expressions inside template strings
+(template_substitution
+  "${" @punctuation.special
+  "}" @punctuation.special)
+
+; Keywords
+[
+  "as"
+  "async"
+  "await"
+  "class"
+  "const"
+  "debugger"
+  "default"
+  "delete"
+  "export"
+  "extends"
+  "from"
+  "get"
+  "import"
+  "in"
+  "instanceof"
+  "let"
+  "new"
+  "of"
+  "set"
+  "static"
+  "target"
+  "typeof"
+  "var"
+  "void"
+  "with"
+  "yield"
+] @keyword
+
+"function" @keyword.function
+
+[
+  "break"
+  "case"
+  "catch"
+  "continue"
+  "do"
+  "else"
+  "finally"
+  "for"
+  "if"
+  "return"
+  "switch"
+  "throw"
+  "try"
+  "while"
+] @keyword.control
+
+; Operators
+[
+  "-"
+  "--"
+  "-="
+  "+"
+  "++"
+  "+="
+  "*"
+  "*="
+  "**"
+  "/"
+  "/="
+  "%"
+  "<"
+  "<="
+  "="
+  "=="
+  "==="
+  "!"
+  "!="
+  "!=="
+  "=>"
+  ">"
+  ">="
+  "&&"
+  "||"
+  "??"
+] @operator
+
+; Punctuation
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+[";" "." ","] @punctuation.delimiter
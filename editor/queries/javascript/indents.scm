@@ -0,0 +1,24 @@
+; Indentation. Lines inside an @indent node are indented one level deeper
+; than the line it starts on; a line starting with an @outdent token is
+; indented one level less.
+
+[
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (object)
+  (object_pattern)
+  (array)
+  (array_pattern)
+  (arguments)
+  (formal_parameters)
+  (named_imports)
+  (export_clause)
+  (template_substitution)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+] @outdent
@@ -0,0 +1,25 @@
+; Code folding. Each @fold node can be collapsed to its first line.
+
+[
+  (function_definition)
+  (class_definition)
+  (decorated_definition)
+  (if_statement)
+  (elif_clause)
+  (else_clause)
+  (for_statement)
+  (while_statement)
+  (with_statement)
+  (try_statement)
+  (except_clause)
+  (finally_clause)
+  (match_statement)
+  (case_clause)
+  (dictionary)
+  (list)
+  (set)
+  (tuple)
+  (argument_list)
+  (parameters)
+  (string)
+] @fold
@@ -0,0 +1,144 @@
+; See rust.scm for how overlapping captures are resolved.
+
+; Identifier conventions
+((identifier) @constant
+ (#match? @constant "^[A-Z][A-Z_\\d]*$"))
+
+((identifier) @constructor
+ (#match? @constructor "^[A-Z]"))
+
+((identifier) @variable.builtin
+ (#any-of? @variable.builtin "self" "cls"))
+
+; Builtin functions
+((call
+  function: (identifier) @function.builtin)
+ (#match?
+   @function.builtin
+   "^(abs|all|any|ascii|bin|bool|breakpoint|bytearray|bytes|callable|chr|classmethod|compile|complex|delattr|dict|dir|divmod|enumerate|eval|exec|filter|float|format|frozenset|getattr|globals|hasattr|hash|help|hex|id|input|int|isinstance|issubclass|iter|len|list|locals|map|max|memoryview|min|next|object|oct|open|ord|pow|print|property|range|repr|reversed|round|set|setattr|slice|sorted|staticmethod|str|sum|super|tuple|type|vars|zip|__import__)$"))
+
+; Decorators
+(decorator) @attribute
+(decorator (identifier) @attribute)
+
+; Function definitions
+(function_definition name: (identifier) @function)
+(class_definition name: (identifier) @type)
+
+; Function calls
+(call function: (identifier) @function.call)
+(call
+  function: (attribute
+    attribute: (identifier) @function.method.call))
+
+; Parameters
+(parameters (identifier) @parameter)
+(default_parameter name: (identifier) @parameter)
+(typed_parameter (identifier) @parameter)
+(typed_default_parameter name: (identifier) @parameter)
+(lambda_parameters (identifier) @parameter)
+
+; Types
+(type (identifier) @type)
+
+; Properties
+(attribute attribute: (identifier) @property)
+
+; Literals
+[
+  (none)
+  (true)
+  (false)
+] @constant.builtin
+
+(integer) @number
+(float) @number.float
+
+(string) @string
+(escape_sequence) @string.escape
+(interpolation
+  "{" @punctuation.special
+  "}" @punctuation.special)
+
+; Docstrings: a string that is the first statement of a module, class or function
+(module . (expression_statement (string) @string.documentation))
+(function_definition
+  body: (block . (expression_statement (string) @string.documentation)))
+(class_definition
+  body: (block . (expression_statement (string) @string.documentation)))
+
+(comment) @comment
+
+; Keywords
+[
+  "as"
+  "assert"
+  "async"
+  "await"
+  "class"
+  "del"
+  "exec"
+  "from"
+  "global"
+  "import"
+  "lambda"
+  "nonlocal"
+  "pass"
+  "print"
+  "with"
+] @keyword
+
+"def" @keyword.function
+
+[
+  "break"
+  "continue"
+  "elif"
+  "else"
+  "except"
+  "finally"
+  "for"
+  "if"
+  "raise"
+  "return"
+  "try"
+  "while"
+  "yield"
+] @keyword.control
+
+[
+  "and"
+  "in"
+  "is"
+  "not"
+  "or"
+] @keyword.operator
+
+; Operators
+[
+  "-"
+  "-="
+  "!="
+  "*"
+  "**"
+  "*="
+  "/"
+  "//"
+  "/="
+  "%"
+  "+"
+  "+="
+  "->"
+  "<"
+  "<="
+  "="
+  ":="
+  "=="
+  ">"
+  ">="
+  "@"
+] @operator
+
+; Punctuation
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+["," "." ":" ";"] @punctuation.delimiter
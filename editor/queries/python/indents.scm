@@ -0,0 +1,34 @@
+; Indentation. Lines inside an @indent node are indented one level deeper
+; than the line it starts on. An @indent token indents the node after it:
+; the block after a `:`, or the next line while that block is empty. A line
+; starting with an @outdent token is indented one level less.
+
+(function_definition ":" @indent)
+(class_definition ":" @indent)
+(if_statement ":" @indent)
+(elif_clause ":" @indent)
+(else_clause ":" @indent)
+(for_statement ":" @indent)
+(while_statement ":" @indent)
+(with_statement ":" @indent)
+(try_statement ":" @indent)
+(except_clause ":" @indent)
+(finally_clause ":" @indent)
+(match_statement ":" @indent)
+(case_clause ":" @indent)
+
+[
+  (argument_list)
+  (parameters)
+  (list)
+  (tuple)
+  (set)
+  (dictionary)
+  (parenthesized_expression)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+] @outdent
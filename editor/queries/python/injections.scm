@@ -0,0 +1,31 @@
+; Injections: regions of a Python file written in another language.
+; `@injection.content` is the embedded code; its language comes from
+; `(#set! injection.language "...")` or an `@injection.language` capture.
+
+; Docstrings are Markdown
+(module . (expression_statement (string (string_content) @injection.content))
+ (#set! injection.language "markdown"))
+(function_definition
+  body: (block . (expression_statement (string (string_content) @injection.content)))
+  (#set! injection.language "markdown"))
+(class_definition
+  body: (block . (expression_statement (string (string_content) @injection.content)))
+  (#set! injection.language "markdown"))
+
+; Patterns passed to the `re` module
+(call
+  function: (attribute
+    object: (identifier) @_module
+    attribute: (identifier) @_function)
+  arguments: (argument_list . (string (string_content) @injection.content))
+  (#eq? @_module "re")
+  (#match? @_function "^(compile|search|match|fullmatch|split|findall|finditer|sub|subn)$")
+  (#set! injection.language "regex"))
+
+; SQL passed to DB-API cursors: cursor.execute("SELECT ...")
+(call
+  function: (attribute
+    attribute: (identifier) @_function)
+  arguments: (argument_list . (string (string_content) @injection.content))
+  (#match? @_function "^(execute|executemany)$")
+  (#set! injection.language "sql"))
@@ -0,0 +1,14 @@
+; Symbols for the outline and go-to-symbol. `@name` is the symbol's name,
+; `@definition.<kind>` the whole definition.
+
+(class_definition
+  name: (identifier) @name) @definition.class
+
+(function_definition
+  name: (identifier) @name) @definition.function
+
+(module
+  (expression_statement
+    (assignment
+      left: (identifier) @name) @definition.constant)
+  (#match? @name "^[A-Z][A-Z0-9_]*$"))
@@ -0,0 +1,21 @@
+; Text objects: `.outer` is the whole construct, `.inner` its body or content.
+
+(function_definition
+  body: (_) @function.inner) @function.outer
+
+(lambda
+  body: (_) @function.inner) @function.outer
+
+(class_definition
+  body: (_) @class.inner) @class.outer
+
+(parameters
+  (_) @parameter.inner)
+
+(lambda_parameters
+  (_) @parameter.inner)
+
+(argument_list
+  (_) @parameter.inner)
+
+(comment) @comment.outer
@@ -0,0 +1,22 @@
+; Code folding. Each @fold node can be collapsed to its first line.
+
+[
+  (mod_item)
+  (function_item)
+  (impl_item)
+  (trait_item)
+  (struct_item)
+  (enum_item)
+  (union_item)
+  (macro_definition)
+  (use_declaration)
+  (block)
+  (match_block)
+  (field_declaration_list)
+  (enum_variant_list)
+  (declaration_list)
+  (struct_expression)
+  (array_expression)
+  (arguments)
+  (block_comment)
+] @fold
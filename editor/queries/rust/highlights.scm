@@ -0,0 +1,173 @@
+; Captures are hierarchical (`function.macro` falls back to `function` in the
+; theme). When captures overlap, the innermost node wins; for the same node
+; the pattern listed first wins unless another has a higher
+; `(#set! priority N)` (default 100).
+
+; Identifier conventions
+((identifier) @constant
+ (#match? @constant "^[A-Z][A-Z\\d_]+$"))
+
+; Uppercase names in paths are types
+((scoped_identifier
+  path: (identifier) @type)
+ (#match? @type "^[A-Z]"))
+((scoped_type_identifier
+  path: (identifier) @type)
+ (#match? @type "^[A-Z]"))
+
+; Other uppercase names are enum variants / constructors
+((identifier) @constructor
+ (#match? @constructor "^[A-Z]"))
+
+; Builtin functions and variables
+((identifier) @function.builtin
+ (#any-of? @function.builtin "drop" "panic" "todo" "unimplemented" "unreachable"))
+(self) @variable.builtin
+
+; Macros
+(macro_invocation
+  macro: (identifier) @function.macro
+  "!" @function.macro)
+(macro_invocation
+  macro: (scoped_identifier
+    name: (identifier) @function.macro))
+(macro_definition
+  name: (identifier) @function.macro)
+
+; Functions
+(function_item name: (identifier) @function)
+(function_signature_item name: (identifier) @function)
+(call_expression function: (identifier) @function.call)
+(call_expression
+  function: (scoped_identifier
+    name: (identifier) @function.call))
+(call_expression
+  function: (field_expression
+    field: (field_identifier) @function.method.call))
+(generic_function
+  function: (identifier) @function.call)
+(generic_function
+  function: (field_expression
+    field: (field_identifier) @function.method.call))
+
+; Types
+(type_identifier) @type
+(primitive_type) @type.builtin
+
+; Modules
+(mod_item name: (identifier) @namespace)
+(scoped_identifier path: (identifier) @namespace)
+(scoped_type_identifier path: (identifier) @namespace)
+(crate) @keyword
+(super) @keyword
+
+; Attributes
+(attribute_item) @attribute
+(inner_attribute_item) @attribute
+
+; Lifetimes and labels
+(lifetime "'" @label)
+(lifetime (identifier) @label)
+
+; Parameters
+(parameter pattern: (identifier) @parameter)
+(closure_parameters (identifier) @parameter)
+
+; Properties
+(field_identifier) @property
+(shorthand_field_identifier) @property
+
+; Strings
+(string_literal) @string
+(raw_string_literal) @string
+(char_literal) @string.special
+(escape_sequence) @string.escape
+
+; Numbers
+(integer_literal) @number
+(float_literal) @number.float
+
+; Constants
+(boolean_literal) @constant.builtin
+
+; Comments
+(line_comment) @comment
+(block_comment) @comment
+
+; Keywords
+(mutable_specifier) @keyword
+(use_list (self) @keyword)
+(scoped_use_list (self) @keyword)
+(scoped_identifier (self) @keyword)
+
+[
+  "as"
+  "async"
+  "await"
+  "const"
+  "default"
+  "dyn"
+  "enum"
+  "extern"
+  "impl"
+  "let"
+  "macro_rules!"
+  "mod"
+  "move"
+  "pub"
+  "ref"
+  "static"
+  "struct"
+  "trait"
+  "type"
+  "union"
+  "unsafe"
+  "use"
+  "where"
+] @keyword
+
+"fn" @keyword.function
+
+[
+  "break"
+  "continue"
+  "else"
+  "for"
+  "if"
+  "in"
+  "loop"
+  "match"
+  "return"
+  "while"
+] @keyword.control
+
+; Operators
+[
+  "*"
+  "&"
+  "="
+  "=="
+  "!="
+  "<"
+  ">"
+  "<="
+  ">="
+  "+"
+  "-"
+  "/"
+  "%"
+  "+="
+  "-="
+  "*="
+  "/="
+  "&&"
+  "||"
+  "!"
+  "->"
+  "=>"
+  ".."
+] @operator
+
+; Punctuation
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+["::" ":" "." "," ";"] @punctuation.delimiter
@@ -0,0 +1,25 @@
+; Indentation. Lines inside an @indent node are indented one level deeper
+; than the line it starts on; a line starting with an @outdent token is
+; indented one level less.
+
+[
+  (block)
+  (match_block)
+  (declaration_list)
+  (field_declaration_list)
+  (enum_variant_list)
+  (field_initializer_list)
+  (use_list)
+  (arguments)
+  (parameters)
+  (type_arguments)
+  (array_expression)
+  (tuple_expression)
+  (token_tree)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+] @outdent
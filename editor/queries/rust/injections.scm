@@ -0,0 +1,23 @@
+; Injections: regions of a Rust file written in another language.
+; `@injection.content` is the embedded code; its language comes from
+; `(#set! injection.language "...")` or an `@injection.language` capture.
+
+; SQL in sqlx's checked query macros: sqlx::query!("SELECT ...")
+((macro_invocation
+  macro: (_) @_macro
+  (token_tree (string_literal [(string_content) @injection.content (escape_sequence)]+)))
+ (#match? @_macro "(^|::)query(_as|_scalar)?(_unchecked)?$")
+ (#set! injection.language "sql"))
+
+; Other macro arguments are Rust code
+((macro_invocation
+  macro: (_) @_macro
+  (token_tree) @injection.content)
+ (#not-match? @_macro "(^|::)query(_as|_scalar)?(_unchecked)?$")
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
+
+((macro_rule
+  (token_tree) @injection.content)
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
@@ -0,0 +1,38 @@
+; Symbols for the outline and go-to-symbol. `@name` is the symbol's name,
+; `@definition.<kind>` the whole definition.
+
+(mod_item
+  name: (identifier) @name) @definition.module
+
+(struct_item
+  name: (type_identifier) @name) @definition.struct
+
+(union_item
+  name: (type_identifier) @name) @definition.struct
+
+(enum_item
+  name: (type_identifier) @name) @definition.enum
+
+(trait_item
+  name: (type_identifier) @name) @definition.trait
+
+(impl_item
+  type: (_) @name) @definition.impl
+
+(function_item
+  name: (identifier) @name) @definition.function
+
+(function_signature_item
+  name: (identifier) @name) @definition.function
+
+(macro_definition
+  name: (identifier) @name) @definition.macro
+
+(const_item
+  name: (identifier) @name) @definition.constant
+
+(static_item
+  name: (identifier) @name) @definition.constant
+
+(type_item
+  name: (type_identifier) @name) @definition.type
@@ -0,0 +1,39 @@
+; Text objects: `.outer` is the whole construct, `.inner` its body or content.
+
+(function_item
+  body: (_) @function.inner) @function.outer
+
+(closure_expression
+  body: (_) @function.inner) @function.outer
+
+(struct_item
+  body: (_) @class.inner) @class.outer
+
+(enum_item
+  body: (_) @class.inner) @class.outer
+
+(union_item
+  body: (_) @class.inner) @class.outer
+
+(trait_item
+  body: (_) @class.inner) @class.outer
+
+(impl_item
+  body: (_) @class.inner) @class.outer
+
+(parameters
+  (_) @parameter.inner)
+
+(closure_parameters
+  (_) @parameter.inner)
+
+(arguments
+  (_) @parameter.inner)
+
+(type_arguments
+  (_) @parameter.inner)
+
+[
+  (line_comment)
+  (block_comment)
+] @comment.outer
@@ -1,61 +0,0 @@
-; Keywords
-[
-  "abstract"
-  "as"
-  "async"
-  "await"
-  "break"
-  "case"
-  "catch"
-  "class"
-  "const"
-  "continue"
-  "debugger"
-  "declare"
-  "default"
-  "delete"
-  "do"
-  "else"
-  "enum"
-  "export"
-  "extends"
-  "finally"
-  "for"
-  "from"
-  "function"
-  "if"
-  "implements"
-  "import"
-  "in"
-  "instanceof"
-  "interface"
-  "let"
-  "namespace"
-  "new"
-  "of"
-  "private"
-  "protected"
-  "public"
-  "readonly"
-  "return"
-  "static"
-  "super"
-  "switch"
-  "this"
-  "throw"
-  "try"
-  "type"
-  "typeof"
-  "var"
-  "void"
-  "while"
-  "with"
-  "yield"
-] @keyword
-
-(function_declaration name: (identifier) @function)
-(call_expression function: (identifier) @function.call)
-(type_identifier) @type
-(string) @string
-(number) @number
-(comment) @comment
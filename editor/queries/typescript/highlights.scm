@@ -0,0 +1,190 @@
+; See rust.scm for how overlapping captures are resolved.
+; The TypeScript grammar extends JavaScript, so this file is the JavaScript
+; query with TypeScript patterns first.
+
+; Types
+(type_identifier) @type
+(predefined_type) @type.builtin
+
+((identifier) @type
+ (#match? @type "^[A-Z]"))
+
+(type_arguments
+  "<" @punctuation.bracket
+  ">" @punctuation.bracket)
+(type_parameters
+  "<" @punctuation.bracket
+  ">" @punctuation.bracket)
+
+; Decorators
+(decorator) @attribute
+
+; Parameters
+(required_parameter (identifier) @parameter)
+(optional_parameter (identifier) @parameter)
+
+; Keywords
+[
+  "abstract"
+  "declare"
+  "enum"
+  "implements"
+  "interface"
+  "keyof"
+  "namespace"
+  "private"
+  "protected"
+  "public"
+  "readonly"
+  "type"
+] @keyword
+
+; Identifier conventions
+([
+  (identifier)
+  (shorthand_property_identifier)
+  (shorthand_property_identifier_pattern)
+ ] @constant
+ (#match? @constant "^[A-Z_][A-Z\\d_]+$"))
+
+((identifier) @constructor
+ (#match? @constructor "^[A-Z]"))
+
+((identifier) @variable.builtin
+ (#any-of? @variable.builtin "arguments" "module" "console" "window" "document"))
+
+((identifier) @function.builtin
+ (#eq? @function.builtin "require"))
+
+(this) @variable.builtin
+(super) @variable.builtin
+
+; Function and method definitions
+(function_declaration name: (identifier) @function)
+(function_expression name: (identifier) @function)
+(generator_function_declaration name: (identifier) @function)
+(method_definition name: (property_identifier) @function.method)
+(variable_declarator
+  name: (identifier) @function
+  value: [(function_expression) (arrow_function)])
+(pair
+  key: (property_identifier) @function.method
+  value: [(function_expression) (arrow_function)])
+
+; Function and method calls
+(call_expression function: (identifier) @function.call)
+(call_expression
+  function: (member_expression
+    property: (property_identifier) @function.method.call))
+(new_expression constructor: (identifier) @constructor)
+
+; Parameters
+(arrow_function parameter: (identifier) @parameter)
+
+; Properties
+(property_identifier) @property
+(shorthand_property_identifier) @property
+
+; Literals
+[
+  (true)
+  (false)
+  (null)
+  (undefined)
+] @constant.builtin
+
+(string) @string
+(template_string) @string
+(escape_sequence) @string.escape
+(regex) @string.regex
+(number) @number
+
+(comment) @comment
+
+; Embedded expressions inside template strings
+(template_substitution
+  "${" @punctuation.special
+  "}" @punctuation.special)
+
+; Keywords
+[
+  "as"
+  "async"
+  "await"
+  "class"
+  "const"
+  "debugger"
+  "default"
+  "delete"
+  "export"
+  "extends"
+  "from"
+  "get"
+  "import"
+  "in"
+  "instanceof"
+  "let"
+  "new"
+  "of"
+  "set"
+  "static"
+  "target"
+  "typeof"
+  "var"
+  "void"
+  "with"
+  "yield"
+] @keyword
+
+"function" @keyword.function
+
+[
+  "break"
+  "case"
+  "catch"
+  "continue"
+  "do"
+  "else"
+  "finally"
+  "for"
+  "if"
+  "return"
+  "switch"
+  "throw"
+  "try"
+  "while"
+] @keyword.control
+
+; Operators
+[
+  "-"
+  "--"
+  "-="
+  "+"
+  "++"
+  "+="
+  "*"
+  "*="
+  "**"
+  "/"
+  "/="
+  "%"
+  "<"
+  "<="
+  "="
+  "=="
+  "==="
+  "!"
+  "!="
+  "!=="
+  "=>"
+  ">"
+  ">="
+  "&&"
+  "||"
+  "??"
+] @operator
+
+; Punctuation
+["(" ")" "[" "]" "{" "}"] @punctuation.bracket
+[";" "." ","] @punctuation.delimiter
@@ -0,0 +1,31 @@
+; Injections: regions of a TypeScript file written in another language.
+; `@injection.content` is the embedded code; its language comes from
+; `(#set! injection.language "...")` or an `@injection.language` capture.
+
+; Tagged templates name their language: html`...`, css`...`, sql`...`
+(call_expression
+  function: [
+    (identifier) @injection.language
+    (member_expression
+      property: (property_identifier) @injection.language)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#set! injection.combined))
+
+; styled-components: styled.div`...`, styled(Button)`...`
+(call_expression
+  function: [
+    (member_expression object: (identifier) @_styled)
+    (call_expression function: (identifier) @_styled)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#eq? @_styled "styled")
+  (#set! injection.language "css")
+  (#set! injection.combined))
+
+((regex_pattern) @injection.content
+ (#set! injection.language "regex"))
+
+((comment) @injection.content
+ (#match? @injection.content "^/\\*\\*")
+ (#set! injection.language "jsdoc"))
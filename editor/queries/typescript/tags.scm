@@ -0,0 +1,45 @@
+; Symbols for the outline and go-to-symbol. `@name` is the symbol's name,
+; `@definition.<kind>` the whole definition.
+
+(module
+  name: (_) @name) @definition.module
+
+(internal_module
+  name: (_) @name) @definition.module
+
+(class_declaration
+  name: (_) @name) @definition.class
+
+(abstract_class_declaration
+  name: (_) @name) @definition.class
+
+(interface_declaration
+  name: (_) @name) @definition.interface
+
+(enum_declaration
+  name: (_) @name) @definition.enum
+
+(type_alias_declaration
+  name: (_) @name) @definition.type
+
+(method_definition
+  name: (_) @name) @definition.method
+
+(method_signature
+  name: (_) @name) @definition.method
+
+(abstract_method_signature
+  name: (_) @name) @definition.method
+
+(function_declaration
+  name: (identifier) @name) @definition.function
+
+(function_signature
+  name: (identifier) @name) @definition.function
+
+(generator_function_declaration
+  name: (identifier) @name) @definition.function
+
+(variable_declarator
+  name: (identifier) @name
+  value: [(arrow_function) (function_expression)]) @definition.function
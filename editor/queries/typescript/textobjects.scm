@@ -0,0 +1,42 @@
+; Text objects: `.outer` is the whole construct, `.inner` its body or content.
+
+(function_declaration
+  body: (_) @function.inner) @function.outer
+
+(function_expression
+  body: (_) @function.inner) @function.outer
+
+(generator_function_declaration
+  body: (_) @function.inner) @function.outer
+
+(arrow_function
+  body: (_) @function.inner) @function.outer
+
+(method_definition
+  body: (_) @function.inner) @function.outer
+
+(class_declaration
+  body: (_) @class.inner) @class.outer
+
+(abstract_class_declaration
+  body: (_) @class.inner) @class.outer
+
+(class
+  body: (_) @class.inner) @class.outer
+
+(interface_declaration
+  body: (_) @class.inner) @class.outer
+
+(formal_parameters
+  (_) @parameter.inner)
+
+(type_parameters
+  (_) @parameter.inner)
+
+(arguments
+  (_) @parameter.inner)
+
+(type_arguments
+  (_) @parameter.inner)
+
+(comment) @comment.outer
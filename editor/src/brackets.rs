@@ -0,0 +1,156 @@
+use tree_sitter::Tree;
+
+/// Pairs closed automatically when a language doesn't list its own
+pub const DEFAULT_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// Brackets that are matched and colored by depth
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// A bracket in the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bracket {
+    pub offset: usize,
+    /// Nesting depth; a pair shares its depth
+    pub depth: usize,
+    /// Offset of the matching bracket, if it has one
+    pub partner: Option<usize>,
+}
+
+/// Brackets of a document in offset order. With a syntax tree only bracket
+/// tokens count, so brackets in strings and comments are skipped.
+pub fn find_brackets(tree: Option<&Tree>, source: &str) -> Vec<Bracket> {
+    let tokens: Vec<(usize, char)> = match tree {
+        Some(tree) => {
+            let mut tokens = Vec::new();
+            let mut cursor = tree.walk();
+            'walk: loop {
+                let node = cursor.node();
+                if node.child_count() == 0 && !node.is_named() {
+                    let mut chars = node.kind().chars();
+                    if let (Some(c), None) = (chars.next(), chars.next()) {
+                        // Missing tokens inserted by error recovery have no text
+                        if is_bracket(c) && node.start_byte() < node.end_byte() {
+                            tokens.push((node.start_byte(), c));
+                        }
+                    }
+                }
+                if cursor.goto_first_child() {
+                    continue;
+                }
+                while !cursor.goto_next_sibling() {
+                    if !cursor.goto_parent() {
+                        break 'walk;
+                    }
+                }
+            }
+            tokens
+        }
+        None => source.char_indices().filter(|(_, c)| is_bracket(*c)).collect(),
+    };
+
+    let mut brackets: Vec<Bracket> = Vec::with_capacity(tokens.len());
+    // Index and expected closer of each open bracket not closed yet
+    let mut open: Vec<(usize, char)> = Vec::new();
+    for (offset, c) in tokens {
+        if let Some(&(_, close)) = BRACKETS.iter().find(|(o, _)| *o == c) {
+            open.push((brackets.len(), close));
+            brackets.push(Bracket { offset, depth: open.len() - 1, partner: None });
+        } else if open.last().is_some_and(|&(_, close)| close == c) {
+            let (index, _) = open.pop().unwrap_or_default();
+            brackets[index].partner = Some(offset);
+            brackets.push(Bracket { offset, depth: open.len(), partner: Some(brackets[index].offset) });
+        } else {
+            brackets.push(Bracket { offset, depth: open.len(), partner: None });
+        }
+    }
+    brackets
+}
+
+/// The bracket at `offset`, or just before it
+pub fn bracket_near(brackets: &[Bracket], offset: usize) -> Option<&Bracket> {
+    let at = |offset: usize| {
+        brackets
+            .binary_search_by_key(&offset, |b| b.offset)
+            .ok()
+            .map(|i| &brackets[i])
+    };
+    at(offset).or_else(|| offset.checked_sub(1).and_then(at))
+}
+
+/// True when `offset` is inside a string or comment, where pairs aren't closed
+pub fn in_string_or_comment(tree: &Tree, source: &str, offset: usize) -> bool {
+    let root = tree.root_node();
+    let candidates = [
+        root.descendant_for_byte_range(offset, offset),
+        offset.checked_sub(1).and_then(|before| root.descendant_for_byte_range(before, offset)),
+    ];
+
+    candidates.into_iter().flatten().any(|mut node| loop {
+        let kind = node.kind();
+        if kind.contains("substitution") || kind.contains("interpolation") {
+            // `${...}` in a template string is code again
+            return false;
+        }
+        let is_comment = kind.contains("comment");
+        if is_comment || kind.contains("string") || kind == "char_literal" {
+            // A line comment runs to the end of the line, past its last byte
+            let open_ended = is_comment && !source[..node.end_byte()].ends_with("*/");
+            if node.start_byte() < offset && (offset < node.end_byte() || open_ended) {
+                return true;
+            }
+        }
+        match node.parent() {
+            Some(parent) => node = parent,
+            None => return false,
+        }
+    })
+}
+
+fn is_bracket(c: char) -> bool {
+    BRACKETS.iter().any(|(open, close)| *open == c || *close == c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+
+    fn parse(language: &str, source: &str) -> Tree {
+        let registry = LanguageRegistry::builtin();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&registry.get(language).unwrap().grammar).unwrap();
+        parser.parse(source, None).unwrap()
+    }
+
+    #[test]
+    fn test_find_brackets() {
+        let source = "fn f(a: [u8; 2]) { g(\"(\"); } // )\n";
+        let tree = parse("rust", source);
+        let brackets = find_brackets(Some(&tree), source);
+
+        let found: String = brackets.iter().map(|b| &source[b.offset..b.offset + 1]).collect();
+        assert_eq!(found, "([]){()}");
+        let depths: Vec<usize> = brackets.iter().map(|b| b.depth).collect();
+        assert_eq!(depths, [0, 1, 1, 0, 0, 1, 1, 0]);
+        assert_eq!(bracket_near(&brackets, 4).unwrap().partner, Some(15));
+        assert_eq!(bracket_near(&brackets, 16).unwrap().partner, Some(4));
+
+        // Without a tree every bracket counts, unmatched ones have no partner
+        let brackets = find_brackets(None, "(]");
+        assert_eq!(brackets.iter().map(|b| b.partner).collect::<Vec<_>>(), [None, None]);
+    }
+
+    #[test]
+    fn test_in_string_or_comment() {
+        let source = "let s = \"ab\"; // note\nlet t = `${x}`;\n";
+        let tree = parse("javascript", source);
+        let at = |text: &str, delta: usize| in_string_or_comment(&tree, source, source.find(text).unwrap() + delta);
+
+        assert!(at("ab", 1));
+        assert!(!at("\"ab\"", 0));
+        assert!(!at("; //", 0));
+        assert!(at("note", 4));
+        assert!(!at("x}", 0));
+        assert!(at("`$", 1));
+    }
+}
@@ -1,13 +1,24 @@
 use ropey::Rope;
+use std::collections::VecDeque;
 use std::ops::Range;
 
+/// How many edits `edits_since` can replay
+const MAX_EDIT_HISTORY: usize = 256;
+
 /// Text buffer using rope data structure for efficient editing
 #[derive(Debug)]
 pub struct Buffer {
     rope: Rope,
     version: usize,
-    undo_stack: Vec<Change>,
-    redo_stack: Vec<Change>,
+    /// Undo steps, each the changes of one edit or transaction in order
+    undo_stack: Vec<Vec<Change>>,
+    redo_stack: Vec<Vec<Change>>,
+    /// Nesting depth of open transactions
+    transaction_depth: usize,
+    /// Changes made since the outermost open transaction began
+    pending: Vec<Change>,
+    /// Recent edits, tagged with the version they produced
+    edits: VecDeque<(usize, Edit)>,
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +28,21 @@ pub struct Change {
     pub text: String,
 }
 
+/// Where the text changed, so line-based state (folds, ...) can follow edits.
+/// Positions are the ones `insert_text` takes; `old_*` describe the text
+/// before the edit, `new_*` after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+    pub start_line: usize,
+    /// Position of `start` within `start_line`
+    pub start_column: usize,
+    pub old_end_line: usize,
+    pub new_end_line: usize,
+}
+
 impl Buffer {
     /// Create a new empty buffer
     pub fn new() -> Self {
@@ -25,6 +51,9 @@ impl Buffer {
             version: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            transaction_depth: 0,
+            pending: Vec::new(),
+            edits: VecDeque::new(),
         }
     }
 
@@ -35,6 +64,9 @@ impl Buffer {
             version: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            transaction_depth: 0,
+            pending: Vec::new(),
+            edits: VecDeque::new(),
         }
     }
 
@@ -58,6 +90,11 @@ impl Buffer {
         self.rope.to_string()
     }
 
+    /// The underlying rope; cloning it is cheap and gives a snapshot
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
     /// Get text in range
     pub fn text_range(&self, range: Range<usize>) -> String {
         self.rope.slice(range.start..range.end).to_string()
@@ -100,12 +137,10 @@ impl Buffer {
             end: pos,
             text: text.to_string(),
         };
-        self.undo_stack.push(change);
-        self.redo_stack.clear();
+        self.push_change(change);
 
         // Insert text
-        self.rope.insert(pos, text);
-        self.version += 1;
+        self.insert_raw(pos, text);
 
         log::debug!("Inserted {} bytes at position {}", text.len(), pos);
     }
@@ -124,28 +159,50 @@ impl Buffer {
             end: range.end,
             text: deleted_text,
         };
-        self.undo_stack.push(change);
-        self.redo_stack.clear();
+        self.push_change(change);
 
         // Delete range
         log::debug!("Deleted range {:?}", range);
-        self.rope.remove(range);
-        self.version += 1;
+        self.remove_raw(range);
+    }
+
+    /// Start grouping edits into one undo step. Transactions nest; the
+    /// step is recorded when the outermost one ends.
+    pub fn begin_transaction(&mut self) {
+        self.transaction_depth += 1;
+    }
+
+    /// End the transaction started by the matching `begin_transaction`
+    pub fn end_transaction(&mut self) {
+        self.transaction_depth = self.transaction_depth.saturating_sub(1);
+        if self.transaction_depth == 0 && !self.pending.is_empty() {
+            let changes = std::mem::take(&mut self.pending);
+            self.undo_stack.push(changes);
+        }
+    }
+
+    /// Run `edit` as a single undo step
+    pub fn transaction<R>(&mut self, edit: impl FnOnce(&mut Self) -> R) -> R {
+        self.begin_transaction();
+        let result = edit(self);
+        self.end_transaction();
+        result
     }
 
     /// Undo last change
     pub fn undo(&mut self) -> bool {
-        if let Some(change) = self.undo_stack.pop() {
-            // Reverse the change
-            if change.start == change.end {
-                // Was an insert, so delete
-                self.rope.remove(change.start..change.start + change.text.len());
-            } else {
-                // Was a delete, so insert
-                self.rope.insert(change.start, &change.text);
+        if let Some(changes) = self.undo_stack.pop() {
+            // Reverse the changes, last first
+            for change in changes.iter().rev() {
+                if change.start == change.end {
+                    // Was an insert, so delete
+                    self.remove_raw(change.start..change.start + change.text.len());
+                } else {
+                    // Was a delete, so insert
+                    self.insert_raw(change.start, &change.text);
+                }
             }
-            self.redo_stack.push(change);
-            self.version += 1;
+            self.redo_stack.push(changes);
             true
         } else {
             false
@@ -154,16 +211,17 @@ impl Buffer {
 
     /// Redo last undone change
     pub fn redo(&mut self) -> bool {
-        if let Some(change) = self.redo_stack.pop() {
-            if change.start == change.end {
-                // Redo insert
-                self.rope.insert(change.start, &change.text);
-            } else {
-                // Redo delete
-                self.rope.remove(change.start..change.end);
+        if let Some(changes) = self.redo_stack.pop() {
+            for change in &changes {
+                if change.start == change.end {
+                    // Redo insert
+                    self.insert_raw(change.start, &change.text);
+                } else {
+                    // Redo delete
+                    self.remove_raw(change.start..change.end);
+                }
             }
-            self.undo_stack.push(change);
-            self.version += 1;
+            self.undo_stack.push(changes);
             true
         } else {
             false
@@ -175,6 +233,74 @@ impl Buffer {
         self.version
     }
 
+    /// Edits made after `version`, oldest first. `None` if they are no
+    /// longer all remembered and dependent state has to be rebuilt.
+    pub fn edits_since(&self, version: usize) -> Option<Vec<Edit>> {
+        if version == self.version {
+            return Some(Vec::new());
+        }
+        match self.edits.front() {
+            Some(&(oldest, _)) if oldest <= version + 1 => Some(
+                self.edits
+                    .iter()
+                    .filter(|(v, _)| *v > version)
+                    .map(|(_, edit)| *edit)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn push_change(&mut self, change: Change) {
+        self.redo_stack.clear();
+        if self.transaction_depth > 0 {
+            self.pending.push(change);
+        } else {
+            self.undo_stack.push(vec![change]);
+        }
+    }
+
+    /// Insert without touching the undo history
+    fn insert_raw(&mut self, pos: usize, text: &str) {
+        let start_line = self.rope.char_to_line(pos);
+        let start_column = pos - self.rope.line_to_char(start_line);
+        self.rope.insert(pos, text);
+        self.record_edit(Edit {
+            start: pos,
+            old_end: pos,
+            new_end: pos + text.chars().count(),
+            start_line,
+            start_column,
+            old_end_line: start_line,
+            new_end_line: start_line + text.matches('\n').count(),
+        });
+    }
+
+    /// Remove without touching the undo history
+    fn remove_raw(&mut self, range: Range<usize>) {
+        let start_line = self.rope.char_to_line(range.start);
+        let start_column = range.start - self.rope.line_to_char(start_line);
+        let old_end_line = self.rope.char_to_line(range.end);
+        self.rope.remove(range.clone());
+        self.record_edit(Edit {
+            start: range.start,
+            old_end: range.end,
+            new_end: range.start,
+            start_line,
+            start_column,
+            old_end_line,
+            new_end_line: start_line,
+        });
+    }
+
+    fn record_edit(&mut self, edit: Edit) {
+        self.version += 1;
+        self.edits.push_back((self.version, edit));
+        if self.edits.len() > MAX_EDIT_HISTORY {
+            self.edits.pop_front();
+        }
+    }
+
     /// Convert byte offset to line and column
     pub fn offset_to_position(&self, offset: usize) -> (usize, usize) {
         let line = self.rope.byte_to_line(offset);
@@ -241,6 +367,23 @@ mod tests {
         assert_eq!(buffer.text(), "Hello World");
     }
 
+    #[test]
+    fn test_transaction() {
+        let mut buffer = Buffer::from_text("a\nb");
+        buffer.transaction(|buffer| {
+            buffer.delete_range(0..2);
+            buffer.transaction(|buffer| buffer.insert_text(1, "\na"));
+        });
+        buffer.insert_text(0, "c");
+        assert_eq!(buffer.text(), "cb\na");
+
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.text(), "a\nb");
+        buffer.redo();
+        assert_eq!(buffer.text(), "b\na");
+    }
+
     #[test]
     fn test_line_operations() {
         let buffer = Buffer::from_text("Line 1\nLine 2\nLine 3");
@@ -249,4 +392,26 @@ mod tests {
         assert_eq!(buffer.line(1), "Line 2\n");
         assert_eq!(buffer.line(2), "Line 3");
     }
+
+    #[test]
+    fn test_edits_since() {
+        let mut buffer = Buffer::from_text("a\nb\nc");
+        let version = buffer.version();
+
+        buffer.insert_text(2, "x\ny\n");
+        buffer.delete_range(0..2);
+        let edits = buffer.edits_since(version).unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!((edits[0].start_line, edits[0].old_end_line, edits[0].new_end_line), (1, 1, 3));
+        assert_eq!((edits[1].start_line, edits[1].old_end_line, edits[1].new_end_line), (0, 1, 0));
+
+        buffer.undo();
+        assert_eq!(buffer.edits_since(buffer.version() - 1).unwrap()[0].new_end_line, 1);
+        assert_eq!(buffer.edits_since(buffer.version()), Some(Vec::new()));
+
+        for _ in 0..MAX_EDIT_HISTORY + 1 {
+            buffer.insert_text(0, "z");
+        }
+        assert_eq!(buffer.edits_since(version), None);
+    }
 }
@@ -0,0 +1,188 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::workspace::is_workspace_file;
+
+pub const USAGE: &str = "\
+Usage: nexus [options] [paths...]
+
+Opens files as tabs and a folder or workspace file as the workspace.
+A file can be followed by a line and column: src/main.rs:42:7
+
+Options:
+  -n, --new-window     Open a new window instead of the running one
+  -w, --wait           Wait until the opened tabs are closed
+  -d, --diff <a> <b>   Show the differences between two files
+  -                    Read stdin into an untitled tab
+  -h, --help           Show this help
+  -V, --version        Show the version";
+
+/// A file to open, with the 1-based line and column to put the cursor on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileArg {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl FileArg {
+    /// Split `file.rs:42:7` into the path, line and column. A path that
+    /// exists is taken as it is, colons and all.
+    pub fn parse(arg: &str) -> Self {
+        let mut path = arg;
+        let mut numbers = Vec::new();
+        if !Path::new(arg).exists() {
+            // Compiler and grep output can end in a colon
+            path = path.strip_suffix(':').unwrap_or(path);
+            while numbers.len() < 2 {
+                match path.rsplit_once(':') {
+                    Some((rest, number)) if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => {
+                        numbers.insert(0, number.parse().unwrap_or(usize::MAX));
+                        path = rest;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        let mut numbers = numbers.into_iter();
+        Self { path: PathBuf::from(path), line: numbers.next(), column: numbers.next() }
+    }
+}
+
+/// The parsed command line
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    /// Files and folders, in the order given
+    pub paths: Vec<FileArg>,
+    pub new_window: bool,
+    pub wait: bool,
+    /// Read stdin into an untitled tab
+    pub stdin: bool,
+    pub diff: Option<(PathBuf, PathBuf)>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    /// Parse the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        let mut options_done = false;
+        while let Some(arg) = args.next() {
+            if options_done {
+                parsed.paths.push(FileArg::parse(&arg));
+                continue;
+            }
+            match arg.as_str() {
+                "--" => options_done = true,
+                "-" => parsed.stdin = true,
+                "-n" | "--new-window" => parsed.new_window = true,
+                "-w" | "--wait" => parsed.wait = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "-d" | "--diff" => match (args.next(), args.next()) {
+                    (Some(a), Some(b)) => parsed.diff = Some((PathBuf::from(a), PathBuf::from(b))),
+                    _ => bail!("--diff needs two files"),
+                },
+                option if option.starts_with('-') => bail!("Unknown option {}", option),
+                _ => parsed.paths.push(FileArg::parse(&arg)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Resolve the paths against `cwd` and read stdin if asked to. Returns
+    /// the folders and workspace files to open as the workspace, and what
+    /// to open in tabs.
+    pub fn into_request(self, cwd: &Path) -> Result<(Vec<PathBuf>, OpenRequest)> {
+        let absolute = |path: &Path| {
+            let path = cwd.join(path);
+            path.canonicalize().unwrap_or(path)
+        };
+
+        let mut folders = Vec::new();
+        let mut files = Vec::new();
+        for file in self.paths {
+            let path = absolute(&file.path);
+            if path.is_dir() || (is_workspace_file(&path) && path.is_file()) {
+                folders.push(path);
+            } else {
+                files.push(FileArg { path, ..file });
+            }
+        }
+
+        let stdin = if self.stdin {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).context("Failed to read stdin")?;
+            Some(text)
+        } else {
+            None
+        };
+
+        let diff = self.diff.map(|(a, b)| (absolute(&a), absolute(&b)));
+        Ok((folders, OpenRequest { files, stdin, diff, wait: self.wait }))
+    }
+}
+
+/// What the command line asks to open in tabs, by this process or sent to
+/// the running one
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenRequest {
+    /// Absolute paths; files that don't exist open as new files
+    pub files: Vec<FileArg>,
+    /// Text read from stdin
+    pub stdin: Option<String>,
+    pub diff: Option<(PathBuf, PathBuf)>,
+    /// Keep the caller waiting until the tabs are closed
+    pub wait: bool,
+}
+
+impl OpenRequest {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.stdin.is_none() && self.diff.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = |args: &[&str]| Args::parse(args.iter().map(|arg| arg.to_string()));
+
+        let parsed = args(&["src/missing.rs:42:7", "-w", "notes.md:3:", "--", "-n"]).unwrap();
+        assert!(parsed.wait && !parsed.new_window);
+        assert_eq!(
+            parsed.paths,
+            [
+                FileArg { path: PathBuf::from("src/missing.rs"), line: Some(42), column: Some(7) },
+                FileArg { path: PathBuf::from("notes.md"), line: Some(3), column: None },
+                FileArg { path: PathBuf::from("-n"), line: None, column: None },
+            ]
+        );
+        assert_eq!(args(&["--diff", "a", "b"]).unwrap().diff, Some((PathBuf::from("a"), PathBuf::from("b"))));
+        assert!(args(&["--diff", "a"]).is_err());
+        assert!(args(&["--frobnicate"]).is_err());
+
+        // Folders and workspace files open the workspace, the rest open in tabs
+        let dir = std::env::temp_dir().join("nexus_test_cli");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        std::fs::write(dir.join("a:b.txt"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let (folders, request) = Args::parse(["app".to_string(), "a:b.txt".to_string(), "new.rs:2".to_string()])
+            .unwrap()
+            .into_request(&dir)
+            .unwrap();
+        assert_eq!(folders, [dir.join("app")]);
+        let paths: Vec<(&Path, Option<usize>)> = request.files.iter().map(|file| (file.path.as_path(), file.line)).collect();
+        assert_eq!(paths, [(dir.join("a:b.txt").as_path(), None), (dir.join("new.rs").as_path(), Some(2))]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
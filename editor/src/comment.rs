@@ -0,0 +1,172 @@
+use crate::indent::leading_whitespace;
+use crate::language::Language;
+
+/// Comment tokens of a language
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommentTokens<'a> {
+    pub line: Option<&'a str>,
+    pub block: Option<(&'a str, &'a str)>,
+}
+
+impl<'a> CommentTokens<'a> {
+    pub fn of(language: &'a Language) -> Self {
+        Self {
+            line: language.config.line_comment.as_deref(),
+            block: language.config.block_comment.as_ref().map(|(open, close)| (open.as_str(), close.as_str())),
+        }
+    }
+}
+
+/// Replace `delete` bytes at `offset` with `insert`. Offsets are relative
+/// to the text the edits were computed for, and edits come in offset order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub delete: usize,
+    pub insert: String,
+}
+
+impl Edit {
+    fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self { offset, delete: 0, insert: text.into() }
+    }
+
+    fn delete(offset: usize, delete: usize) -> Self {
+        Self { offset, delete, insert: String::new() }
+    }
+}
+
+/// Comment or uncomment whole lines. Lines are uncommented if every
+/// non-blank one is commented, otherwise all of them are commented with
+/// the tokens aligned at the smallest indentation. Languages without line
+/// comments wrap each line in a block comment instead.
+pub fn toggle_lines(text: &str, tokens: CommentTokens) -> Vec<Edit> {
+    let mut offset = 0;
+    let lines: Vec<(usize, &str)> = text
+        .split('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len() + 1;
+            (start, line)
+        })
+        .filter(|(_, line)| !line.trim().is_empty())
+        .collect();
+    let min_indent = lines.iter().map(|(_, line)| leading_whitespace(line).len()).min().unwrap_or(0);
+
+    let mut edits = Vec::new();
+    match tokens {
+        CommentTokens { line: Some(token), .. } => {
+            let commented = |line: &str| line.trim_start().starts_with(token);
+            if lines.iter().all(|(_, line)| commented(line)) {
+                for (start, line) in lines {
+                    let indent = leading_whitespace(line).len();
+                    let rest = &line[indent + token.len()..];
+                    edits.push(Edit::delete(start + indent, token.len() + rest.starts_with(' ') as usize));
+                }
+            } else {
+                for (start, _) in lines {
+                    edits.push(Edit::insert(start + min_indent, format!("{} ", token)));
+                }
+            }
+        }
+        CommentTokens { block: Some((open, close)), .. } => {
+            let commented = |line: &str| {
+                let content = line.trim();
+                content.len() >= open.len() + close.len() && content.starts_with(open) && content.ends_with(close)
+            };
+            if lines.iter().all(|(_, line)| commented(line)) {
+                for (start, line) in lines {
+                    let indent = leading_whitespace(line).len();
+                    edits.extend(unwrap_block(start + indent, line.trim(), open, close));
+                }
+            } else {
+                for (start, line) in lines {
+                    edits.push(Edit::insert(start + min_indent, format!("{} ", open)));
+                    edits.push(Edit::insert(start + line.trim_end().len(), format!(" {}", close)));
+                }
+            }
+        }
+        _ => {}
+    }
+    edits
+}
+
+/// Wrap text in a block comment, or unwrap it if it already is one.
+/// Surrounding whitespace stays outside the comment.
+pub fn toggle_block(text: &str, open: &str, close: &str) -> Vec<Edit> {
+    let start = leading_whitespace(text).len();
+    let content = text.trim();
+    if content.len() >= open.len() + close.len() && content.starts_with(open) && content.ends_with(close) {
+        return unwrap_block(start, content, open, close);
+    }
+    let end = start + content.len();
+    vec![Edit::insert(start, format!("{} ", open)), Edit::insert(end, format!(" {}", close))]
+}
+
+/// Remove the tokens of a block comment at `start`, and a space inside each
+fn unwrap_block(start: usize, comment: &str, open: &str, close: &str) -> Vec<Edit> {
+    let inner = &comment[open.len()..comment.len() - close.len()];
+    let open_len = open.len() + inner.starts_with(' ') as usize;
+    let close_len = close.len() + (inner.len() > 1 && inner.ends_with(' ')) as usize;
+    vec![Edit::delete(start, open_len), Edit::delete(start + comment.len() - close_len, close_len)]
+}
+
+/// Where `offset` ends up after `edits`. A position at an insertion point
+/// moves past the inserted text, or stays before it with `stay_before`.
+pub fn map_offset(edits: &[Edit], offset: usize, stay_before: bool) -> usize {
+    let mut delta = 0isize;
+    for edit in edits {
+        if offset < edit.offset || offset == edit.offset && (edit.delete > 0 || stay_before) {
+            break;
+        }
+        if offset < edit.offset + edit.delete {
+            return (edit.offset as isize + delta) as usize;
+        }
+        delta += edit.insert.len() as isize - edit.delete as isize;
+    }
+    (offset as isize + delta) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, edits: &[Edit]) -> String {
+        let mut out = text.to_string();
+        for edit in edits.iter().rev() {
+            out.replace_range(edit.offset..edit.offset + edit.delete, &edit.insert);
+        }
+        out
+    }
+
+    #[test]
+    fn test_toggle_lines() {
+        let tokens = CommentTokens { line: Some("//"), block: Some(("/*", "*/")) };
+        let toggle = |text: &str| apply(text, &toggle_lines(text, tokens));
+
+        // Tokens line up at the smallest indentation; blank lines are left alone
+        assert_eq!(toggle("    a\n\n  b"), "  //   a\n\n  // b");
+        assert_eq!(toggle("  //   a\n\n  // b"), "    a\n\n  b");
+
+        // A mix of commented and plain lines gets commented as a whole
+        assert_eq!(toggle("// a\nb"), "// // a\n// b");
+
+        // Without line comments each line becomes a block comment
+        let html = CommentTokens { line: None, block: Some(("<!--", "-->")) };
+        assert_eq!(apply("  <p>\n", &toggle_lines("  <p>\n", html)), "  <!-- <p> -->\n");
+        assert_eq!(apply("  <!-- <p> -->", &toggle_lines("  <!-- <p> -->", html)), "  <p>");
+    }
+
+    #[test]
+    fn test_toggle_block() {
+        let edits = toggle_block(" a + b ", "/*", "*/");
+        assert_eq!(apply(" a + b ", &edits), " /* a + b */ ");
+        assert_eq!(map_offset(&edits, 1, true), 1);
+        assert_eq!(map_offset(&edits, 1, false), 4);
+        assert_eq!(map_offset(&edits, 6, false), 12);
+
+        let text = "/* a + b */";
+        assert_eq!(apply(text, &toggle_block(text, "/*", "*/")), "a + b");
+        assert_eq!(apply("/**/", &toggle_block("/**/", "/*", "*/")), "");
+    }
+}
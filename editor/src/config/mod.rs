@@ -19,6 +19,19 @@ pub struct EditorConfig {
     pub word_wrap: bool,
     pub auto_save: bool,
     pub cursor_blink: bool,
+    /// Color brackets by nesting depth
+    #[serde(default)]
+    pub rainbow_brackets: bool,
+    /// Punctuation that word motion and deletion treat as words of their own
+    #[serde(default = "default_word_separators")]
+    pub word_separators: String,
+    /// Stop word motion at camelCase humps and snake_case underscores
+    #[serde(default)]
+    pub subword_navigation: bool,
+}
+
+fn default_word_separators() -> String {
+    crate::motion::DEFAULT_WORD_SEPARATORS.to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +68,9 @@ impl Default for Config {
                 word_wrap: false,
                 auto_save: true,
                 cursor_blink: true,
+                rainbow_brackets: false,
+                word_separators: default_word_separators(),
+                subword_navigation: false,
             },
             ui: UiConfig {
                 theme: "dark".to_string(),
@@ -106,9 +122,14 @@ impl Config {
     }
 
     fn config_path() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("config.toml"))
+    }
+
+    /// Directory holding Nexus configuration (config.toml, themes, ...)
+    pub fn config_dir() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
             .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
-        Ok(config_dir.join("nexus").join("config.toml"))
+        Ok(config_dir.join("nexus"))
     }
 }
 
@@ -0,0 +1,407 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A change to make to the workspace's files
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileAction {
+    /// Create an empty file or a directory, and any missing parents
+    Create { path: PathBuf, is_dir: bool },
+    /// Rename or move a file or directory
+    Move { from: PathBuf, to: PathBuf },
+    /// Copy a file or directory
+    Copy { from: PathBuf, to: PathBuf },
+    /// Move a file or directory to the trash
+    Trash(PathBuf),
+    /// Bring a file or directory back from the trash
+    Restore { trashed: PathBuf, path: PathBuf },
+}
+
+/// A change made to the workspace's files, with what it takes to undo it
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileOp {
+    Create { path: PathBuf, is_dir: bool },
+    Move { from: PathBuf, to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+    /// `path` is in the trash at `trashed`
+    Trash { path: PathBuf, trashed: PathBuf },
+    Restore { trashed: PathBuf, path: PathBuf },
+}
+
+impl FileOp {
+    /// The action that undoes this one. Created and copied files go to the
+    /// trash rather than being deleted for good.
+    fn inverse(&self) -> FileAction {
+        match self {
+            FileOp::Create { path, .. } | FileOp::Copy { to: path, .. } | FileOp::Restore { path, .. } => {
+                FileAction::Trash(path.clone())
+            }
+            FileOp::Move { from, to } => FileAction::Move { from: to.clone(), to: from.clone() },
+            FileOp::Trash { path, trashed } => FileAction::Restore { trashed: trashed.clone(), path: path.clone() },
+        }
+    }
+
+    /// Paths that appeared or disappeared
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            FileOp::Create { path, .. } | FileOp::Trash { path, .. } | FileOp::Restore { path, .. } => vec![path],
+            FileOp::Move { from, to } => vec![from, to],
+            FileOp::Copy { to, .. } => vec![to],
+        }
+    }
+
+    /// The path the operation left in the workspace, if any
+    pub fn result(&self) -> Option<&Path> {
+        match self {
+            FileOp::Create { path, .. } | FileOp::Restore { path, .. } => Some(path),
+            FileOp::Move { to, .. } | FileOp::Copy { to, .. } => Some(to),
+            FileOp::Trash { .. } => None,
+        }
+    }
+}
+
+/// Runs file actions and keeps them for undo and redo during the session
+pub struct FileOps {
+    /// Trash directory with `files` and `info` subdirectories; `None` if
+    /// there is nowhere to put deleted files
+    trash: Option<PathBuf>,
+    undo_stack: Vec<FileOp>,
+    redo_stack: Vec<FileOp>,
+}
+
+impl FileOps {
+    /// Deleted files go to the freedesktop.org trash on Linux, where file
+    /// managers can restore them too, and elsewhere to a trash in the Nexus
+    /// data directory
+    pub fn new() -> Self {
+        let trash = dirs::data_dir().map(|dir| {
+            if cfg!(target_os = "linux") {
+                dir.join("Trash")
+            } else {
+                dir.join("nexus").join("trash")
+            }
+        });
+        Self::with_trash(trash)
+    }
+
+    pub fn with_trash(trash: Option<PathBuf>) -> Self {
+        Self { trash, undo_stack: Vec::new(), redo_stack: Vec::new() }
+    }
+
+    /// Run an action, making it the next one to undo
+    pub fn run(&mut self, action: FileAction) -> Result<FileOp> {
+        let op = self.perform(action)?;
+        self.undo_stack.push(op.clone());
+        self.redo_stack.clear();
+        Ok(op)
+    }
+
+    /// Undo the last operation; `None` if there is nothing to undo
+    pub fn undo(&mut self) -> Result<Option<FileOp>> {
+        let Some(op) = self.undo_stack.pop() else {
+            return Ok(None);
+        };
+        match self.perform(op.inverse()) {
+            Ok(undone) => {
+                self.redo_stack.push(undone.clone());
+                Ok(Some(undone))
+            }
+            Err(e) => {
+                // Keep it, e.g. to retry once a name in the way is gone
+                self.undo_stack.push(op);
+                Err(e)
+            }
+        }
+    }
+
+    /// Redo the last undone operation; `None` if there is nothing to redo
+    pub fn redo(&mut self) -> Result<Option<FileOp>> {
+        let Some(op) = self.redo_stack.pop() else {
+            return Ok(None);
+        };
+        match self.perform(op.inverse()) {
+            Ok(redone) => {
+                self.undo_stack.push(redone.clone());
+                Ok(Some(redone))
+            }
+            Err(e) => {
+                self.redo_stack.push(op);
+                Err(e)
+            }
+        }
+    }
+
+    fn perform(&self, action: FileAction) -> Result<FileOp> {
+        let vacant = |path: &Path| -> Result<()> {
+            if path.symlink_metadata().is_ok() {
+                bail!("{} already exists", path.display());
+            }
+            Ok(())
+        };
+        match action {
+            FileAction::Create { path, is_dir } => {
+                vacant(&path)?;
+                if is_dir {
+                    fs::create_dir_all(&path)?;
+                } else {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+                }
+                Ok(FileOp::Create { path, is_dir })
+            }
+            FileAction::Move { from, to } => {
+                vacant(&to)?;
+                if to.starts_with(&from) {
+                    bail!("Can't move {} into itself", from.display());
+                }
+                move_path(&from, &to)?;
+                Ok(FileOp::Move { from, to })
+            }
+            FileAction::Copy { from, to } => {
+                vacant(&to)?;
+                if to.starts_with(&from) {
+                    bail!("Can't copy {} into itself", from.display());
+                }
+                copy_path(&from, &to)?;
+                Ok(FileOp::Copy { from, to })
+            }
+            FileAction::Trash(path) => {
+                let trash = self.trash.as_deref().context("No trash directory to delete to")?;
+                let trashed = move_to_trash(trash, &path)?;
+                Ok(FileOp::Trash { path, trashed })
+            }
+            FileAction::Restore { trashed, path } => {
+                vacant(&path)?;
+                move_path(&trashed, &path)?;
+                if let Some(info) = self.trash.as_deref().and_then(|trash| info_path(trash, &trashed)) {
+                    let _ = fs::remove_file(info);
+                }
+                Ok(FileOp::Restore { trashed, path })
+            }
+        }
+    }
+}
+
+impl Default for FileOps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A name for a copy of `path` next to it that isn't taken:
+/// `name copy.rs`, then `name copy 2.rs`, ...
+pub fn copy_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    let extension = if path.is_dir() { String::new() } else { extension };
+    let stem = if path.is_dir() { path.file_name().unwrap_or_default().to_string_lossy() } else { stem };
+    (1..)
+        .map(|n| {
+            let suffix = if n == 1 { " copy".to_string() } else { format!(" copy {}", n) };
+            path.with_file_name(format!("{}{}{}", stem, suffix, extension))
+        })
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .unwrap_or_default()
+}
+
+/// Rename, or copy and delete when `to` is on another file system
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let is_dir = fs::symlink_metadata(from)?.is_dir();
+            copy_path(from, to)?;
+            if is_dir {
+                fs::remove_dir_all(from)?;
+            } else {
+                fs::remove_file(from)?;
+            }
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Copy a file or directory tree. Symlinks are copied as links, not
+/// followed, so a link loop can't recurse forever.
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        copy_link(from, to)?;
+    } else if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+/// Move `path` into `trash/files` under a free name, recording where it
+/// came from in `trash/info` as the freedesktop.org trash does
+fn move_to_trash(trash: &Path, path: &Path) -> Result<PathBuf> {
+    let file_name = path.file_name().context("Can't delete the file system root")?;
+    let name = file_name.to_string_lossy().to_string();
+    // Only the parent is resolved, so a symlink is trashed rather than its target
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let path = fs::canonicalize(parent)?.join(file_name);
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let trashed = (1..)
+        .map(|n| if n == 1 { name.clone() } else { format!("{}.{}", name, n) })
+        .map(|name| files.join(name))
+        .find(|candidate| candidate.symlink_metadata().is_err() && info_path(trash, candidate).is_some_and(|i| !i.exists()))
+        .unwrap_or_default();
+    let info_file = info_path(trash, &trashed).context("Invalid trash name")?;
+    fs::write(
+        &info_file,
+        format!("[Trash Info]\nPath={}\nDeletionDate={}\n", percent_encode(&path), deletion_date()),
+    )?;
+    if let Err(e) = move_path(&path, &trashed) {
+        let _ = fs::remove_file(info_file);
+        return Err(e);
+    }
+    Ok(trashed)
+}
+
+/// The `.trashinfo` file of a file in `trash/files`
+fn info_path(trash: &Path, trashed: &Path) -> Option<PathBuf> {
+    let name = trashed.file_name()?.to_string_lossy();
+    Some(trash.join("info").join(format!("{}.trashinfo", name)))
+}
+
+/// A path as a URI path: bytes other than unreserved characters and `/`
+/// percent-encoded
+fn percent_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The current time as `YYYY-MM-DDThh:mm:ss`, in UTC
+fn deletion_date() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_ops() {
+        let root = std::env::temp_dir().join("nexus_test_file_ops");
+        let _ = fs::remove_dir_all(&root);
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        let mut ops = FileOps::with_trash(Some(root.join("trash")));
+
+        let file = workspace.join("src/main.rs");
+        ops.run(FileAction::Create { path: file.clone(), is_dir: false }).unwrap();
+        fs::write(&file, "fn main() {}").unwrap();
+        assert!(ops.run(FileAction::Create { path: file.clone(), is_dir: false }).is_err());
+
+        let renamed = workspace.join("src/app.rs");
+        ops.run(FileAction::Move { from: file.clone(), to: renamed.clone() }).unwrap();
+        let copy = copy_name(&renamed);
+        assert_eq!(copy, workspace.join("src/app copy.rs"));
+        ops.run(FileAction::Copy { from: renamed.clone(), to: copy.clone() }).unwrap();
+        assert!(ops.run(FileAction::Move { from: workspace.join("src"), to: workspace.join("src/inner") }).is_err());
+
+        // Deleting goes to the trash, with where the file came from
+        let op = ops.run(FileAction::Trash(workspace.join("src"))).unwrap();
+        let FileOp::Trash { trashed, .. } = &op else { panic!("{:?}", op) };
+        assert!(!workspace.join("src").exists() && trashed.join("app.rs").exists());
+        let info = fs::read_to_string(root.join("trash/info/src.trashinfo")).unwrap();
+        assert!(info.contains("nexus_test_file_ops/workspace/src\nDeletionDate=2"), "{}", info);
+
+        // Each step undoes, and redoes, on its own
+        assert!(matches!(ops.undo().unwrap(), Some(FileOp::Restore { .. })));
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "fn main() {}");
+        assert!(!root.join("trash/info/src.trashinfo").exists());
+        ops.undo().unwrap();
+        assert!(!copy.exists());
+        ops.undo().unwrap();
+        assert!(file.exists() && !renamed.exists());
+        assert!(matches!(ops.redo().unwrap(), Some(FileOp::Move { .. })));
+        assert!(renamed.exists());
+        ops.undo().unwrap();
+        ops.undo().unwrap();
+        assert!(!file.exists());
+        assert_eq!(ops.undo().unwrap(), None);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let root = std::env::temp_dir().join("nexus_test_file_ops_symlinks");
+        let _ = fs::remove_dir_all(&root);
+        let (workspace, elsewhere) = (root.join("workspace"), root.join("elsewhere"));
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(elsewhere.join("keep.txt"), "").unwrap();
+        let mut ops = FileOps::with_trash(Some(root.join("trash")));
+
+        // Trashing a link leaves its target alone
+        let link = workspace.join("link");
+        std::os::unix::fs::symlink("../elsewhere", &link).unwrap();
+        let op = ops.run(FileAction::Trash(link.clone())).unwrap();
+        let FileOp::Trash { trashed, .. } = &op else { panic!("{:?}", op) };
+        assert!(link.symlink_metadata().is_err() && elsewhere.join("keep.txt").exists());
+        assert!(trashed.symlink_metadata().unwrap().is_symlink());
+        ops.undo().unwrap();
+        assert!(link.symlink_metadata().unwrap().is_symlink());
+
+        // A link back up the tree is copied as a link instead of followed
+        std::os::unix::fs::symlink("..", workspace.join("loop")).unwrap();
+        ops.run(FileAction::Copy { from: workspace.clone(), to: root.join("copy") }).unwrap();
+        assert_eq!(fs::read_link(root.join("copy/loop")).unwrap(), Path::new(".."));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
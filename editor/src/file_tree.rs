@@ -1,6 +1,14 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::fs;
-use anyhow::Result;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant, SystemTime};
+use ignore::{DirEntry, WalkBuilder};
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::file_ops::{self, FileAction};
+use crate::ui::text_input::TextInput;
+use crate::workspace::{Workspace, WorkspaceRoot};
 
 #[derive(Debug, Clone)]
 pub struct FileTreeNode {
@@ -11,80 +19,606 @@ pub struct FileTreeNode {
     pub depth: usize,
 }
 
+/// An entry of a directory listing
+#[derive(Debug, Clone)]
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    modified: Option<SystemTime>,
+}
+
+impl Entry {
+    fn new(entry: DirEntry) -> Self {
+        Self {
+            is_dir: entry.path().is_dir(),
+            name: entry.file_name().to_string_lossy().to_string(),
+            modified: entry.metadata().ok().and_then(|metadata| metadata.modified().ok()),
+            path: entry.into_path(),
+        }
+    }
+}
+
+/// How the entries of a directory are ordered. Directories come first in
+/// every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortMode {
+    #[default]
+    Name,
+    /// By extension, then name
+    Type,
+    /// Most recently modified first
+    Modified,
+}
+
+impl SortMode {
+    pub const ALL: &'static [(&'static str, SortMode)] = &[
+        ("File Tree: Sort by Name", SortMode::Name),
+        ("File Tree: Sort by Type", SortMode::Type),
+        ("File Tree: Sort by Modification Time", SortMode::Modified),
+    ];
+
+    fn sort(self, entries: &mut [Entry]) {
+        let extension = |entry: &Entry| Path::new(&entry.name).extension().map(|ext| ext.to_ascii_lowercase());
+        entries.sort_by(|a, b| {
+            let order = match self {
+                SortMode::Name => std::cmp::Ordering::Equal,
+                SortMode::Type => extension(a).cmp(&extension(b)),
+                SortMode::Modified => b.modified.cmp(&a.modified),
+            };
+            b.is_dir.cmp(&a.is_dir).then(order).then_with(|| a.name.cmp(&b.name))
+        });
+    }
+}
+
+/// Names typed to show only the matching entries of the tree, with the
+/// directories holding them
+#[derive(Debug, Clone)]
+pub struct TreeFilter {
+    pub input: TextInput,
+    /// Every directory under the roots, listed when the filter opened
+    listings: HashMap<PathBuf, Vec<Entry>>,
+}
+
+impl TreeFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        entry.name.to_lowercase().contains(&self.input.text().to_lowercase())
+    }
+
+    /// Rows for the entries whose names contain the typed text, case
+    /// insensitively, under their expanded ancestors. With several roots,
+    /// those without matches are left out.
+    fn nodes(&self, roots: &[WorkspaceRoot]) -> Vec<FileTreeNode> {
+        let is_root = |path: &Path| roots.iter().any(|root| root.path == path);
+        let mut shown = HashSet::new();
+        for entry in self.listings.values().flatten().filter(|entry| self.matches(entry)) {
+            shown.extend(entry.path.ancestors().take_while(|path| !is_root(path)).map(Path::to_path_buf));
+        }
+        let mut nodes = Vec::new();
+        match roots {
+            [root] => self.push_nodes(&root.path, 0, &shown, &mut nodes),
+            _ => {
+                for root in roots {
+                    if self.listings.get(&root.path).is_some_and(|entries| entries.iter().any(|e| shown.contains(&e.path))) {
+                        nodes.push(root_node(root, true));
+                        self.push_nodes(&root.path, 1, &shown, &mut nodes);
+                    }
+                }
+            }
+        }
+        nodes
+    }
+
+    fn push_nodes(&self, dir: &Path, depth: usize, shown: &HashSet<PathBuf>, nodes: &mut Vec<FileTreeNode>) {
+        for entry in self.listings.get(dir).into_iter().flatten().filter(|entry| shown.contains(&entry.path)) {
+            let is_expanded = entry.is_dir
+                && self.listings.get(&entry.path).is_some_and(|entries| entries.iter().any(|e| shown.contains(&e.path)));
+            nodes.push(FileTreeNode {
+                path: entry.path.clone(),
+                name: entry.name.clone(),
+                is_dir: entry.is_dir,
+                is_expanded,
+                depth,
+            });
+            if is_expanded {
+                self.push_nodes(&entry.path, depth + 1, shown, nodes);
+            }
+        }
+    }
+}
+
+/// What a name typed in the file tree is for
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptKind {
+    NewFile,
+    NewFolder,
+    /// Rename the file or directory at the path
+    Rename(PathBuf),
+}
+
+/// A name being typed in the file tree, for a new entry or a rename
+#[derive(Debug, Clone)]
+pub struct FilePrompt {
+    pub kind: PromptKind,
+    /// Directory the name is relative to
+    pub dir: PathBuf,
+    pub input: TextInput,
+}
+
+impl FilePrompt {
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            PromptKind::NewFile => "New File",
+            PromptKind::NewFolder => "New Folder",
+            PromptKind::Rename(_) => "Rename",
+        }
+    }
+
+    /// The action the typed name asks for; `None` if there is no name or
+    /// a rename keeps it. Names may contain `/` to reach into directories.
+    pub fn action(&self) -> Option<FileAction> {
+        let name = self.input.text().trim();
+        if name.is_empty() {
+            return None;
+        }
+        let path = self.dir.join(name);
+        match &self.kind {
+            PromptKind::NewFile => Some(FileAction::Create { path, is_dir: false }),
+            PromptKind::NewFolder => Some(FileAction::Create { path, is_dir: true }),
+            PromptKind::Rename(from) if *from != path => Some(FileAction::Move { from: from.clone(), to: path }),
+            PromptKind::Rename(_) => None,
+        }
+    }
+}
+
+/// The workspace's files. Directories are listed when first expanded, and
+/// the listings follow changes on disk.
 pub struct FileTree {
+    /// Rows shown: the root's entries and those of expanded directories.
+    /// With several roots, each root is a row of its own above its entries.
     pub nodes: Vec<FileTreeNode>,
     pub selected_index: usize,
     pub is_visible: bool,
-    pub root_path: PathBuf,
+    pub workspace: Workspace,
+    /// Also list dotfiles; ignored files stay out either way
+    pub show_hidden: bool,
+    pub sort_mode: SortMode,
+    /// Text narrowing the rows down to matching entries, while open
+    pub filter: Option<TreeFilter>,
+    /// Name being typed for a new file or folder, or a rename
+    pub prompt: Option<FilePrompt>,
+    /// Path cut (`true`) or copied to paste into another directory
+    pub clipboard: Option<(PathBuf, bool)>,
+    /// Directories shown expanded, kept across refreshes
+    expanded: HashSet<PathBuf>,
+    /// Start of the name typed to jump to an entry, and when it was last
+    /// typed to
+    type_ahead: String,
+    type_ahead_at: Option<Instant>,
+    /// Listings of the directories loaded so far
+    listings: HashMap<PathBuf, Vec<Entry>>,
+    /// Paths created, removed or renamed under the roots since the last poll
+    changes: Option<Receiver<Vec<PathBuf>>>,
+    _watcher: Option<RecommendedWatcher>,
 }
 
 impl FileTree {
-    pub fn new(root_path: PathBuf) -> Result<Self> {
+    /// A tree of the workspace's roots, expanded one level
+    pub fn new(workspace: Workspace) -> Self {
+        let expanded = match workspace.roots.as_slice() {
+            [_] => HashSet::new(),
+            roots => roots.iter().map(|root| root.path.clone()).collect(),
+        };
         let mut tree = Self {
             nodes: Vec::new(),
             selected_index: 0,
             is_visible: false,
-            root_path: root_path.clone(),
+            workspace,
+            show_hidden: false,
+            sort_mode: SortMode::default(),
+            filter: None,
+            prompt: None,
+            clipboard: None,
+            expanded,
+            type_ahead: String::new(),
+            type_ahead_at: None,
+            listings: HashMap::new(),
+            changes: None,
+            _watcher: None,
         };
+        tree.watch();
+        tree.rebuild();
+        tree
+    }
 
-        tree.refresh()?;
-        Ok(tree)
+    /// Follow changes under the roots. Without a watcher the tree only
+    /// changes on `refresh`.
+    fn watch(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Writes to files don't change the tree, except to ignore files
+            let structural = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)) | EventKind::Any
+            );
+            if structural || event.paths.iter().any(|path| is_ignore_file(path)) {
+                let _ = sender.send(event.paths);
+            }
+        })
+        .and_then(|mut watcher| {
+            for root in &self.workspace.roots {
+                watcher.watch(&root.path, RecursiveMode::Recursive)?;
+            }
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => {
+                self._watcher = Some(watcher);
+                self.changes = Some(receiver);
+            }
+            Err(e) => log::warn!("Not watching {} for changes: {}", self.workspace.name(), e),
+        }
     }
 
-    pub fn refresh(&mut self) -> Result<()> {
-        self.nodes.clear();
-        self.scan_directory(&self.root_path.clone(), 0)?;
-        Ok(())
+    /// List a directory in the sort mode's order. Files ignored by
+    /// `.gitignore` or `.ignore` files or excluded by the root, and
+    /// dotfiles unless `show_hidden` or the root shows them, are left out.
+    fn list(&self, dir: &Path) -> Vec<Entry> {
+        let walker = self.walker(dir).max_depth(Some(1)).build();
+        let mut entries: Vec<Entry> = walker
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.depth() == 1 && entry.file_name() != ".git")
+            .map(Entry::new)
+            .collect();
+        self.sort_mode.sort(&mut entries);
+        entries
     }
 
-    fn scan_directory(&mut self, path: &Path, depth: usize) -> Result<()> {
-        if depth > 5 {
-            // Limit depth to avoid too deep recursion
-            return Ok(());
+    /// List every directory under the roots, for the filter
+    fn list_all(&self) -> HashMap<PathBuf, Vec<Entry>> {
+        let walkers = self
+            .workspace
+            .roots
+            .iter()
+            .map(|root| self.walker(&root.path).filter_entry(|entry| entry.file_name() != ".git").build());
+        let mut listings: HashMap<PathBuf, Vec<Entry>> = HashMap::new();
+        for entry in walkers.flatten().filter_map(|entry| entry.ok()).filter(|entry| entry.depth() > 0) {
+            let Some(parent) = entry.path().parent() else {
+                continue;
+            };
+            listings.entry(parent.to_path_buf()).or_default().push(Entry::new(entry));
         }
+        for entries in listings.values_mut() {
+            self.sort_mode.sort(entries);
+        }
+        listings
+    }
 
-        let mut entries: Vec<_> = fs::read_dir(path)?
-            .filter_map(|e| e.ok())
-            .collect();
+    /// A walk of `dir` with the settings of its root
+    fn walker(&self, dir: &Path) -> WalkBuilder {
+        let root = self.workspace.root_of(dir);
+        let mut walker = WalkBuilder::new(dir);
+        walker.hidden(!self.show_hidden && !root.is_some_and(|root| root.show_hidden)).require_git(false);
+        if let Some(root) = root {
+            walker.overrides(root.overrides());
+        }
+        walker
+    }
 
-        // Sort: directories first, then files, alphabetically
-        entries.sort_by(|a, b| {
-            let a_is_dir = a.path().is_dir();
-            let b_is_dir = b.path().is_dir();
+    /// Lay out the rows again, listing newly expanded directories, and keep
+    /// the selected path selected, or the closest ancestor still shown
+    fn rebuild(&mut self) {
+        let selected = self.get_selected_path().map(Path::to_path_buf);
+        self.nodes.clear();
+        match self.filter.as_ref().filter(|filter| !filter.input.is_empty()) {
+            Some(filter) => self.nodes = filter.nodes(&self.workspace.roots),
+            None if self.workspace.roots.len() == 1 => {
+                let root = self.workspace.roots[0].path.clone();
+                self.push_entries(&root, 0);
+            }
+            None => {
+                for index in 0..self.workspace.roots.len() {
+                    let root = &self.workspace.roots[index];
+                    let is_expanded = self.expanded.contains(&root.path);
+                    let node = root_node(root, is_expanded);
+                    let path = node.path.clone();
+                    self.nodes.push(node);
+                    if is_expanded {
+                        self.push_entries(&path, 1);
+                    }
+                }
+            }
+        }
+        if let Some(path) = selected {
+            self.select_closest(&path);
+        }
+        self.selected_index = self.selected_index.min(self.nodes.len().saturating_sub(1));
+    }
+
+    /// Select `path`, or its closest ancestor shown
+    fn select_closest(&mut self, path: &Path) {
+        if let Some(index) = path.ancestors().find_map(|path| self.nodes.iter().position(|node| node.path == path)) {
+            self.selected_index = index;
+        }
+    }
 
-            match (a_is_dir, b_is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.file_name().cmp(&b.file_name()),
+    fn push_entries(&mut self, dir: &Path, depth: usize) {
+        if !self.listings.contains_key(dir) {
+            let entries = self.list(dir);
+            self.listings.insert(dir.to_path_buf(), entries);
+        }
+        for entry in self.listings[dir].clone() {
+            let is_expanded = entry.is_dir && self.expanded.contains(&entry.path);
+            self.nodes.push(FileTreeNode {
+                path: entry.path.clone(),
+                name: entry.name,
+                is_dir: entry.is_dir,
+                is_expanded,
+                depth,
+            });
+            if is_expanded {
+                self.push_entries(&entry.path, depth + 1);
             }
-        });
+        }
+    }
+
+    /// List every loaded directory again, keeping what is expanded and
+    /// selected
+    pub fn refresh(&mut self) {
+        self.listings.clear();
+        self.reload_filter();
+        self.rebuild();
+    }
+
+    /// Order every directory anew
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        self.sort_mode = sort_mode;
+        let listings = self.listings.values_mut().chain(self.filter.iter_mut().flat_map(|filter| filter.listings.values_mut()));
+        for entries in listings {
+            sort_mode.sort(entries);
+        }
+        self.rebuild();
+    }
+
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+        self.refresh();
+    }
 
-        for entry in entries {
-            let path = entry.path();
-            let name = entry.file_name().to_string_lossy().to_string();
+    /// Take in changes on disk since the last call; true if the tree changed
+    pub fn poll(&mut self) -> bool {
+        let Some(changes) = &self.changes else {
+            return false;
+        };
+        let paths: Vec<PathBuf> = changes.try_iter().flatten().collect();
+        !paths.is_empty() && self.apply_changes(&paths)
+    }
+
+    /// List again the loaded directories holding `paths`. A changed ignore
+    /// file affects every loaded directory below its own.
+    pub fn apply_changes(&mut self, paths: &[PathBuf]) -> bool {
+        // The filter shows entries from anywhere under the root
+        let filtering = self.filter.is_some();
+        self.reload_filter();
 
-            // Skip hidden files and common ignore patterns
-            if name.starts_with('.') || name == "target" || name == "node_modules" {
+        let mut stale = HashSet::new();
+        for path in paths {
+            let Some(parent) = path.parent() else {
                 continue;
+            };
+            if is_ignore_file(path) {
+                stale.extend(self.listings.keys().filter(|dir| dir.starts_with(parent)).cloned());
+            } else if self.listings.contains_key(parent) {
+                stale.insert(parent.to_path_buf());
             }
+        }
+        if stale.is_empty() && !filtering {
+            return false;
+        }
 
-            let is_dir = path.is_dir();
+        for dir in stale {
+            if dir.is_dir() {
+                let entries = self.list(&dir);
+                self.listings.insert(dir, entries);
+            } else {
+                self.listings.remove(&dir);
+            }
+        }
+        self.expanded.retain(|dir| dir.is_dir());
+        self.listings.retain(|dir, _| dir.is_dir());
+        self.rebuild();
+        true
+    }
 
-            self.nodes.push(FileTreeNode {
-                path: path.clone(),
-                name,
-                is_dir,
-                is_expanded: depth == 0, // Expand only root level by default
-                depth,
-            });
+    /// Keep what was expanded under a moved directory expanded at its new
+    /// place. Listings under it are loaded again when shown.
+    pub fn moved(&mut self, from: &Path, to: &Path) {
+        let moved: Vec<PathBuf> = self.expanded.iter().filter(|dir| dir.starts_with(from)).cloned().collect();
+        for dir in moved {
+            self.expanded.remove(&dir);
+            if let Ok(rest) = dir.strip_prefix(from) {
+                self.expanded.insert(to.join(rest));
+            }
+        }
+        self.listings.retain(|dir, _| !dir.starts_with(from) && !dir.starts_with(to));
+        self.rebuild();
+    }
+
+    /// Expand the directories down to `path` and select it; false if it
+    /// isn't in the tree, e.g. outside the roots or ignored
+    pub fn reveal(&mut self, path: &Path) -> bool {
+        let Some(root) = self.workspace.root_of(path) else {
+            return false;
+        };
+        let mut dir = root.path.clone();
+        let relative = path.strip_prefix(&dir).unwrap_or(path).to_path_buf();
+        self.expanded.insert(dir.clone());
+        for component in relative.parent().into_iter().flat_map(Path::components) {
+            dir.push(component);
+            self.expanded.insert(dir.clone());
+        }
+        self.rebuild();
+        match self.nodes.iter().position(|node| node.path == path) {
+            Some(index) => {
+                self.selected_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Show directories expanded `depth` levels below the roots, and deeper
+    /// ones collapsed; 0 collapses everything but the roots
+    pub fn expand_to_depth(&mut self, depth: usize) {
+        let selected = self.get_selected_path().map(Path::to_path_buf);
+        self.expanded = self.workspace.roots.iter().map(|root| root.path.clone()).collect();
+        self.rebuild();
+        // Root rows are the first level when there are several
+        let first = usize::from(self.workspace.roots.len() > 1);
+        for level in first..first + depth {
+            let dirs: Vec<PathBuf> =
+                self.nodes.iter().filter(|node| node.is_dir && node.depth == level).map(|node| node.path.clone()).collect();
+            if dirs.is_empty() {
+                break;
+            }
+            self.expanded.extend(dirs);
+            self.rebuild();
+        }
+        if let Some(path) = selected {
+            self.select_closest(&path);
+        }
+    }
+
+    /// Open the filter, listing everything under the roots to search
+    pub fn open_filter(&mut self) {
+        if self.filter.is_none() {
+            self.filter = Some(TreeFilter { input: TextInput::new(), listings: HashMap::new() });
+            self.reload_filter();
+        }
+    }
 
-            // Recursively scan subdirectories if expanded
-            if is_dir && depth == 0 {
-                self.scan_directory(&path, depth + 1)?;
+    /// Show the entries matching the filter's new text, with the first
+    /// match selected
+    pub fn filter_changed(&mut self) {
+        self.rebuild();
+        if let Some(filter) = self.filter.as_ref().filter(|filter| !filter.input.is_empty()) {
+            let listed = |node: &FileTreeNode| filter.listings.get(node.path.parent()?)?.iter().find(|e| e.path == node.path);
+            let first = self.nodes.iter().position(|node| listed(node).is_some_and(|entry| filter.matches(entry)));
+            self.selected_index = first.unwrap_or(0);
+        }
+    }
+
+    /// Close the filter, going back to the whole tree with the selected
+    /// entry revealed
+    pub fn close_filter(&mut self) {
+        let selected = self.get_selected_path().map(Path::to_path_buf);
+        self.filter = None;
+        match selected {
+            Some(path) => {
+                self.reveal(&path);
+            }
+            None => self.rebuild(),
+        }
+    }
+
+    fn reload_filter(&mut self) {
+        if self.filter.is_some() {
+            let listings = self.list_all();
+            if let Some(filter) = &mut self.filter {
+                filter.listings = listings;
             }
         }
+    }
+
+    /// Jump to the next entry whose name starts with what was typed, case
+    /// insensitively. Typing continues the name for a second; typing the
+    /// same letter again goes through the entries starting with it.
+    pub fn type_ahead(&mut self, text: &str, now: Instant) -> bool {
+        if self.type_ahead_at.is_none_or(|at| now.duration_since(at) > TYPE_AHEAD_TIMEOUT) {
+            self.type_ahead.clear();
+        }
+        self.type_ahead_at = Some(now);
+        self.type_ahead.push_str(&text.to_lowercase());
+
+        let mut chars = self.type_ahead.chars();
+        let first = chars.next();
+        let (prefix, start) = if chars.all(|c| Some(c) == first) {
+            // A new name or a repeated letter moves on from the selection
+            (first.map(String::from).unwrap_or_default(), self.selected_index + 1)
+        } else {
+            (self.type_ahead.clone(), self.selected_index)
+        };
+        let count = self.nodes.len();
+        let found = (0..count)
+            .map(|step| (start + step) % count)
+            .find(|&index| self.nodes[index].name.to_lowercase().starts_with(&prefix));
+        if let Some(index) = found {
+            self.selected_index = index;
+        }
+        found.is_some()
+    }
+
+    /// The selected directory, or the one holding the selected file; new
+    /// and pasted entries go there
+    pub fn selected_dir(&self) -> PathBuf {
+        let first_root = &self.workspace.roots[0].path;
+        match self.get_selected_node() {
+            Some(node) if node.is_dir => node.path.clone(),
+            Some(node) => node.path.parent().unwrap_or(first_root).to_path_buf(),
+            None => first_root.clone(),
+        }
+    }
+
+    /// The selected file or directory, unless it is a root, which file
+    /// operations leave alone
+    pub fn selected_entry(&self) -> Option<&Path> {
+        self.get_selected_path().filter(|path| !self.workspace.roots.iter().any(|root| root.path == *path))
+    }
 
-        Ok(())
+    /// Start typing a name. New entries go in the selected directory, which
+    /// is expanded to show them; a rename starts with the name's stem
+    /// selected.
+    pub fn start_prompt(&mut self, kind: PromptKind) {
+        let mut input = TextInput::new();
+        let dir = match &kind {
+            PromptKind::Rename(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let stem = match name.rfind('.') {
+                    Some(dot) if dot > 0 && !path.is_dir() => name[..dot].chars().count(),
+                    _ => name.chars().count(),
+                };
+                input.set_text(name);
+                input.move_to(0, false);
+                input.move_to(stem, true);
+                path.parent().unwrap_or(&self.workspace.roots[0].path).to_path_buf()
+            }
+            PromptKind::NewFile | PromptKind::NewFolder => {
+                let dir = self.selected_dir();
+                if self.expanded.insert(dir.clone()) {
+                    self.rebuild();
+                }
+                dir
+            }
+        };
+        self.prompt = Some(FilePrompt { kind, dir, input });
+    }
+
+    /// The action pasting the clipboard into the selected directory asks
+    /// for. A copy next to the original, or onto a taken name, is given a
+    /// free name; a cut is pasted once.
+    pub fn paste(&mut self) -> Option<FileAction> {
+        let (from, cut) = self.clipboard.clone()?;
+        let to = self.selected_dir().join(from.file_name()?);
+        if cut {
+            self.clipboard = None;
+            (to != from).then_some(FileAction::Move { from, to })
+        } else {
+            let to = if to.symlink_metadata().is_ok() { file_ops::copy_name(&to) } else { to };
+            Some(FileAction::Copy { from, to })
+        }
     }
 
     pub fn toggle_visibility(&mut self) {
@@ -103,20 +637,16 @@ impl FileTree {
         }
     }
 
-    pub fn toggle_selected_expand(&mut self) -> Result<()> {
-        if let Some(node) = self.nodes.get_mut(self.selected_index) {
-            if node.is_dir {
-                node.is_expanded = !node.is_expanded;
-                // Rebuild tree to show/hide children
-                let selected_path = node.path.clone();
-                self.refresh()?;
-                // Try to restore selection
-                if let Some(idx) = self.nodes.iter().position(|n| n.path == selected_path) {
-                    self.selected_index = idx;
-                }
-            }
+    /// Expand or collapse the selected directory, listing it the first time
+    pub fn toggle_selected_expand(&mut self) {
+        let Some(node) = self.nodes.get(self.selected_index).filter(|node| node.is_dir) else {
+            return;
+        };
+        let path = node.path.clone();
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
         }
-        Ok(())
+        self.rebuild();
     }
 
     pub fn get_selected_path(&self) -> Option<&Path> {
@@ -148,3 +678,191 @@ impl FileTree {
         text
     }
 }
+
+/// The row of a root, when there are several
+fn root_node(root: &WorkspaceRoot, is_expanded: bool) -> FileTreeNode {
+    FileTreeNode {
+        path: root.path.clone(),
+        name: root.name.clone(),
+        is_dir: true,
+        is_expanded,
+        depth: 0,
+    }
+}
+
+/// How long after the last key a typed name is continued
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == ".gitignore" || name == ".ignore")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_tree() {
+        let root = std::env::temp_dir().join("nexus_test_file_tree");
+        let _ = std::fs::remove_dir_all(&root);
+        let write = |path: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+        for path in [".gitignore", ".env", "README.md", "src/main.rs", "src/nested/deep.rs", "build/out.o"] {
+            write(path);
+        }
+        std::fs::write(root.join(".gitignore"), "build/\n").unwrap();
+
+        let rows = |tree: &FileTree| -> Vec<String> {
+            tree.nodes.iter().map(|node| format!("{}{}", "  ".repeat(node.depth), node.name)).collect()
+        };
+        let mut tree = FileTree::new(Workspace::folder(root.clone()));
+        assert_eq!(rows(&tree), ["src", "README.md"]);
+        assert_eq!(tree.listings.len(), 1);
+
+        // Expanding lists the directory; the expansion survives refreshes
+        tree.toggle_selected_expand();
+        assert_eq!(rows(&tree), ["src", "  nested", "  main.rs", "README.md"]);
+        tree.selected_index = 2;
+        tree.refresh();
+        assert_eq!((rows(&tree).len(), tree.get_selected_node().unwrap().name.as_str()), (4, "main.rs"));
+
+        // Changes reload only the directories they are in
+        write("src/lib.rs");
+        write("build/more.o");
+        assert!(tree.apply_changes(&[root.join("src/lib.rs"), root.join("build/more.o")]));
+        assert_eq!(rows(&tree), ["src", "  nested", "  lib.rs", "  main.rs", "README.md"]);
+        assert_eq!(tree.get_selected_node().unwrap().name, "main.rs");
+        assert!(!tree.apply_changes(&[root.join("src/nested/deep.rs")]));
+
+        std::fs::write(root.join(".gitignore"), "build/\n*.md\n").unwrap();
+        assert!(tree.apply_changes(&[root.join(".gitignore")]));
+        assert_eq!(rows(&tree).last().unwrap(), "  main.rs");
+
+        tree.set_show_hidden(true);
+        assert_eq!(rows(&tree)[..2], ["src", "  nested"]);
+        assert_eq!(rows(&tree)[4..], [".env", ".gitignore"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_file_tree_edits() {
+        let root = std::env::temp_dir().join("nexus_test_file_tree_edits");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+        let mut tree = FileTree::new(Workspace::folder(root.clone()));
+
+        // Revealing expands the way down
+        assert!(tree.reveal(&root.join("src/main.rs")));
+        assert_eq!(tree.get_selected_node().unwrap().name, "main.rs");
+        assert_eq!(tree.selected_dir(), root.join("src"));
+
+        tree.start_prompt(PromptKind::Rename(root.join("src/main.rs")));
+        let prompt = tree.prompt.as_mut().unwrap();
+        assert_eq!(prompt.input.selected_text(), Some("main"));
+        prompt.input.insert("lib");
+        assert_eq!(prompt.action(), Some(FileAction::Move { from: root.join("src/main.rs"), to: root.join("src/lib.rs") }));
+
+        // Pasting a copy beside the original picks a free name; a cut pastes once
+        tree.clipboard = Some((root.join("src/main.rs"), false));
+        assert_eq!(tree.paste(), Some(FileAction::Copy { from: root.join("src/main.rs"), to: root.join("src/main copy.rs") }));
+        tree.clipboard = Some((root.join("README.md"), true));
+        assert_eq!(tree.paste(), Some(FileAction::Move { from: root.join("README.md"), to: root.join("src/README.md") }));
+        assert_eq!(tree.paste(), None);
+
+        // Expanded directories stay expanded when moved
+        tree.reveal(&root.join("src/nested"));
+        tree.toggle_selected_expand();
+        std::fs::rename(root.join("src"), root.join("app")).unwrap();
+        tree.moved(&root.join("src"), &root.join("app"));
+        assert!(tree.apply_changes(&[root.join("src"), root.join("app")]));
+        assert!(tree.expanded.contains(&root.join("app/nested")));
+        assert!(tree.nodes.iter().any(|node| node.path == root.join("app/main.rs")));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_filter_and_type_ahead() {
+        let root = std::env::temp_dir().join("nexus_test_file_tree_filter");
+        let _ = std::fs::remove_dir_all(&root);
+        for path in ["src/ui/button.rs", "src/ui/bar.toml", "src/main.rs", "docs/banner.md", "build.rs"] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let rows = |tree: &FileTree| -> Vec<String> {
+            tree.nodes.iter().map(|node| format!("{}{}", "  ".repeat(node.depth), node.name)).collect()
+        };
+        let mut tree = FileTree::new(Workspace::folder(root.clone()));
+
+        // Only matches show, under their directories
+        tree.open_filter();
+        tree.filter.as_mut().unwrap().input.insert("BU");
+        tree.filter_changed();
+        assert_eq!(rows(&tree), ["src", "  ui", "    button.rs", "build.rs"]);
+        assert_eq!(tree.get_selected_node().unwrap().name, "button.rs");
+        tree.close_filter();
+        assert_eq!(rows(&tree), ["docs", "src", "  ui", "    bar.toml", "    button.rs", "  main.rs", "build.rs"]);
+        assert_eq!(tree.get_selected_node().unwrap().name, "button.rs");
+
+        tree.set_sort_mode(SortMode::Type);
+        assert_eq!(rows(&tree)[3..5], ["    button.rs", "    bar.toml"]);
+        tree.expand_to_depth(1);
+        assert_eq!(rows(&tree), ["docs", "  banner.md", "src", "  ui", "  main.rs", "build.rs"]);
+        assert_eq!(tree.get_selected_node().unwrap().name, "ui");
+
+        // Typing jumps by name; a repeated letter steps through the matches
+        let now = Instant::now();
+        assert!(tree.type_ahead("b", now));
+        assert_eq!(tree.get_selected_node().unwrap().name, "build.rs");
+        assert!(tree.type_ahead("b", now));
+        assert_eq!(tree.get_selected_node().unwrap().name, "banner.md");
+        assert!(!tree.type_ahead("x", now));
+        assert!(tree.type_ahead("m", now + Duration::from_secs(2)));
+        assert_eq!(tree.get_selected_node().unwrap().name, "main.rs");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_multi_root() {
+        let dir = std::env::temp_dir().join("nexus_test_file_tree_roots");
+        let _ = std::fs::remove_dir_all(&dir);
+        for path in ["app/src/main.rs", "app/app.log", "docs/guide.md", "docs/.notes/todo.md"] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let file = dir.join("all.nexus-workspace");
+        let config = "[[folders]]\npath = \"app\"\nexclude = [\"*.log\"]\n\n[[folders]]\npath = \"docs\"\nname = \"Docs\"\nshow_hidden = true\n";
+        std::fs::write(&file, config).unwrap();
+        let rows = |tree: &FileTree| -> Vec<String> {
+            tree.nodes.iter().map(|node| format!("{}{}", "  ".repeat(node.depth), node.name)).collect()
+        };
+
+        // Each root is a row, with its own settings
+        let mut tree = FileTree::new(Workspace::load(&file).unwrap());
+        assert_eq!(rows(&tree), ["app", "  src", "Docs", "  .notes", "  guide.md"]);
+        assert_eq!(tree.selected_entry(), None);
+        tree.expand_to_depth(0);
+        assert_eq!(rows(&tree).len(), 5);
+
+        let docs = dir.join("docs").canonicalize().unwrap();
+        assert!(tree.reveal(&docs.join(".notes/todo.md")));
+        assert_eq!(tree.selected_entry(), Some(docs.join(".notes/todo.md").as_path()));
+
+        // The filter searches every root and leaves out roots without matches
+        tree.open_filter();
+        tree.filter.as_mut().unwrap().input.insert("main");
+        tree.filter_changed();
+        assert_eq!(rows(&tree), ["app", "  src", "    main.rs"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
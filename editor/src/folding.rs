@@ -0,0 +1,398 @@
+use crate::buffer::Edit;
+use std::collections::BTreeMap;
+use tree_sitter::{Query, QueryCursor, StreamingIterator, Tree};
+
+/// A foldable region. `start_line` stays visible as the fold's header;
+/// the lines after it up to and including `end_line` are hidden when folded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FoldRange {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl FoldRange {
+    /// True if `line` is the header or one of the lines the fold hides
+    pub fn contains(&self, line: usize) -> bool {
+        line >= self.start_line && line <= self.end_line
+    }
+
+    /// True if folding this range hides `line`
+    pub fn hides(&self, line: usize) -> bool {
+        line > self.start_line && line <= self.end_line
+    }
+}
+
+/// Fold ranges from the `@fold` captures of a `folds.scm` query. A last line
+/// holding only closing brackets stays visible, so `fn main() {` folds down
+/// to itself and its `}`.
+pub fn query_fold_ranges(tree: &Tree, query: &Query, source: &str) -> Vec<FoldRange> {
+    let fold_index = match query.capture_index_for_name("fold") {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+
+    let mut ranges = BTreeMap::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+    while let Some(match_item) = matches.next() {
+        for capture in match_item.captures.iter().filter(|c| c.index == fold_index) {
+            let node = capture.node;
+            let start_line = node.start_position().row;
+            let mut end_line = node.end_position().row;
+
+            let end = node.end_byte();
+            let line_start = source[..end].rfind('\n').map_or(0, |i| i + 1);
+            if source[line_start..end]
+                .chars()
+                .all(|c| c.is_whitespace() || matches!(c, ')' | ']' | '}' | '>' | ';' | ','))
+            {
+                end_line = end_line.saturating_sub(1);
+            }
+
+            if end_line > start_line {
+                // Several nodes can start on one line (an item and its body);
+                // the outermost one wins
+                let entry = ranges.entry(start_line).or_insert(end_line);
+                *entry = (*entry).max(end_line);
+            }
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start_line, end_line)| FoldRange { start_line, end_line })
+        .collect()
+}
+
+/// Fold ranges from indentation, for languages without a fold query: a line
+/// folds the lines below it that are indented deeper. Blank lines never
+/// start or end a fold.
+pub fn indent_fold_ranges(source: &str, tab_size: usize) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_line = 0;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let indent = indent_width(line, tab_size);
+        while let Some(&(start_line, start_indent)) = open.last() {
+            if indent > start_indent {
+                break;
+            }
+            open.pop();
+            if last_line > start_line {
+                ranges.push(FoldRange { start_line, end_line: last_line });
+            }
+        }
+
+        open.push((line_idx, indent));
+        last_line = line_idx;
+    }
+
+    for (start_line, _) in open {
+        if last_line > start_line {
+            ranges.push(FoldRange { start_line, end_line: last_line });
+        }
+    }
+
+    ranges.sort();
+    ranges
+}
+
+fn indent_width(line: &str, tab_size: usize) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += tab_size - width % tab_size,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// The fold ranges of a document and which of them are collapsed
+#[derive(Debug, Default, Clone)]
+pub struct FoldState {
+    /// Every foldable range, sorted by start line
+    ranges: Vec<FoldRange>,
+    /// Collapsed ranges, sorted by start line
+    folded: Vec<FoldRange>,
+}
+
+impl FoldState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the foldable ranges after a reparse. Collapsed ranges stay
+    /// collapsed if a range still starts on their header line; the others
+    /// are dropped.
+    pub fn set_ranges(&mut self, ranges: Vec<FoldRange>) {
+        self.folded = self
+            .folded
+            .iter()
+            .filter_map(|fold| ranges.iter().find(|r| r.start_line == fold.start_line).copied())
+            .collect();
+        self.folded.dedup();
+        self.ranges = ranges;
+    }
+
+    /// Move collapsed ranges along with an edit. Edits inside a fold resize
+    /// it; edits that touch its header from above or cross its end unfold it.
+    pub fn apply_edit(&mut self, edit: &Edit) {
+        let delta = edit.new_end_line as isize - edit.old_end_line as isize;
+        let shift = |line: usize| (line as isize + delta).max(0) as usize;
+
+        self.folded.retain_mut(|fold| {
+            let before_header = edit.old_end_line < fold.start_line
+                || (edit.start_line == fold.start_line
+                    && edit.old_end_line == fold.start_line
+                    && edit.start_column == 0
+                    && delta > 0);
+
+            if before_header {
+                fold.start_line = shift(fold.start_line);
+                fold.end_line = shift(fold.end_line);
+                true
+            } else if edit.start_line > fold.end_line {
+                true
+            } else if edit.start_line >= fold.start_line && edit.old_end_line <= fold.end_line {
+                fold.end_line = shift(fold.end_line);
+                fold.end_line > fold.start_line
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Fold the innermost unfolded range around `line`
+    pub fn fold_at(&mut self, line: usize) -> bool {
+        let range = self
+            .ranges
+            .iter()
+            .filter(|r| r.contains(line) && !self.folded.contains(r))
+            .max_by_key(|r| r.start_line)
+            .copied();
+
+        match range {
+            Some(range) => {
+                self.insert_folded(range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unfold the innermost folded range around `line`
+    pub fn unfold_at(&mut self, line: usize) -> bool {
+        let index = self
+            .folded
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.contains(line))
+            .max_by_key(|(_, f)| f.start_line)
+            .map(|(i, _)| i);
+
+        match index {
+            Some(index) => {
+                self.folded.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unfold the range headed by `line` if it is folded, otherwise fold
+    /// the innermost range around `line`
+    pub fn toggle_at(&mut self, line: usize) -> bool {
+        match self.folded.iter().position(|f| f.start_line == line) {
+            Some(index) => {
+                self.folded.remove(index);
+                true
+            }
+            None => self.fold_at(line),
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.ranges.clone();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    /// Fold every range nested `level` deep; top-level ranges are level 1
+    pub fn fold_level(&mut self, level: usize) {
+        let ranges: Vec<FoldRange> = self
+            .ranges
+            .iter()
+            .filter(|r| {
+                let depth = self
+                    .ranges
+                    .iter()
+                    .filter(|outer| *outer != *r && outer.contains(r.start_line) && outer.contains(r.end_line))
+                    .count();
+                depth + 1 == level
+            })
+            .copied()
+            .collect();
+
+        for range in ranges {
+            if !self.folded.contains(&range) {
+                self.insert_folded(range);
+            }
+        }
+    }
+
+    /// True if a foldable range starts on `line`
+    pub fn is_foldable(&self, line: usize) -> bool {
+        self.ranges.iter().any(|r| r.start_line == line)
+    }
+
+    /// True if `line` is the header of a folded range
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folded.iter().any(|f| f.start_line == line)
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.hiding_fold(line).is_some()
+    }
+
+    /// Unfold every range hiding `line`, e.g. to show a search match
+    pub fn reveal(&mut self, line: usize) -> bool {
+        let before = self.folded.len();
+        self.folded.retain(|f| !f.hides(line));
+        self.folded.len() != before
+    }
+
+    /// `line`, or the first line after the folds hiding it
+    pub fn next_visible(&self, mut line: usize) -> usize {
+        while let Some(fold) = self.hiding_fold(line) {
+            line = fold.end_line + 1;
+        }
+        line
+    }
+
+    /// `line`, or the header of the fold hiding it
+    pub fn prev_visible(&self, line: usize) -> usize {
+        self.hiding_fold(line).map_or(line, |f| f.start_line)
+    }
+
+    /// Outermost folded range hiding `line`
+    fn hiding_fold(&self, line: usize) -> Option<FoldRange> {
+        self.folded.iter().find(|f| f.hides(line)).copied()
+    }
+
+    fn insert_folded(&mut self, range: FoldRange) {
+        let index = self.folded.partition_point(|f| *f < range);
+        self.folded.insert(index, range);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+
+    fn fold(start_line: usize, end_line: usize) -> FoldRange {
+        FoldRange { start_line, end_line }
+    }
+
+    fn visible_lines(folds: &FoldState, line_count: usize) -> Vec<usize> {
+        (0..line_count).filter(|&line| !folds.is_hidden(line)).collect()
+    }
+
+    #[test]
+    fn test_query_fold_ranges() {
+        let registry = LanguageRegistry::builtin();
+        let rust = registry.get("rust").unwrap();
+        let source = "fn main() {\n    let x = [\n        1,\n    ];\n}\n\nstruct A;\n";
+
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&rust.grammar).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        // The closing lines stay visible; the single-line struct can't fold
+        let ranges = query_fold_ranges(&tree, rust.query("folds").unwrap(), source);
+        assert_eq!(ranges, vec![fold(0, 3), fold(1, 2)]);
+    }
+
+    #[test]
+    fn test_indent_fold_ranges() {
+        let source = "a:\n    b\n\n    c:\n\t  d\ne\n";
+        assert_eq!(indent_fold_ranges(source, 4), vec![fold(0, 4), fold(3, 4)]);
+        assert!(indent_fold_ranges("a\nb\n", 4).is_empty());
+    }
+
+    #[test]
+    fn test_fold_navigation() {
+        let mut folds = FoldState::new();
+        folds.set_ranges(vec![fold(0, 8), fold(2, 4), fold(6, 7)]);
+
+        assert!(folds.fold_at(3));
+        assert!(folds.is_folded(2));
+        assert_eq!(visible_lines(&folds, 10), vec![0, 1, 2, 5, 6, 7, 8, 9]);
+        assert_eq!(folds.next_visible(3), 5);
+        assert_eq!(folds.prev_visible(4), 2);
+
+        // Folding again from the header folds the enclosing range
+        assert!(folds.fold_at(2));
+        assert_eq!(visible_lines(&folds, 10), vec![0, 9]);
+        assert!(folds.unfold_at(0));
+        assert!(folds.is_hidden(3));
+
+        assert!(folds.reveal(3));
+        assert!(!folds.is_hidden(3));
+
+        folds.fold_level(2);
+        assert_eq!(folds.folded, [fold(2, 4), fold(6, 7)]);
+        folds.fold_all();
+        assert_eq!(folds.folded.len(), 3);
+        folds.unfold_all();
+        assert!(folds.folded.is_empty());
+    }
+
+    #[test]
+    fn test_folds_follow_edits() {
+        let edit = |start_line, start_column, old_end_line, new_end_line| Edit {
+            start: 0,
+            old_end: 0,
+            new_end: 0,
+            start_line,
+            start_column,
+            old_end_line,
+            new_end_line,
+        };
+
+        let mut folds = FoldState::new();
+        folds.set_ranges(vec![fold(2, 5), fold(8, 9)]);
+        folds.fold_all();
+
+        // Two lines inserted above both folds
+        folds.apply_edit(&edit(0, 3, 0, 2));
+        assert_eq!(folds.folded, [fold(4, 7), fold(10, 11)]);
+
+        // A line inserted in front of a header moves the fold down
+        folds.apply_edit(&edit(4, 0, 4, 5));
+        assert_eq!(folds.folded, [fold(5, 8), fold(11, 12)]);
+
+        // A line removed inside a fold shrinks it
+        folds.apply_edit(&edit(6, 4, 7, 6));
+        assert_eq!(folds.folded, [fold(5, 7), fold(10, 11)]);
+
+        // Deleting across a fold's end unfolds it
+        folds.apply_edit(&edit(7, 0, 9, 7));
+        assert_eq!(folds.folded, [fold(8, 9)]);
+
+        // A reparse keeps folds whose header still starts a range
+        folds.set_ranges(vec![fold(8, 10)]);
+        assert_eq!(folds.folded, [fold(8, 10)]);
+        folds.set_ranges(vec![fold(7, 10)]);
+        assert!(folds.folded.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Entries kept per history
+const MAX_ENTRIES: usize = 100;
+
+/// Past entries of an input field, oldest first, browsed with Up / Down
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    entries: Vec<String>,
+    /// Entry shown while browsing
+    #[serde(skip)]
+    position: Option<usize>,
+    /// What the field held before browsing started
+    #[serde(skip)]
+    draft: String,
+}
+
+impl History {
+    /// Add an entry as the newest, dropping an older copy of it
+    pub fn push(&mut self, entry: &str) {
+        self.position = None;
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|existing| existing != entry);
+        self.entries.push(entry.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+
+    /// The entry before the one shown, starting from the newest. `current`
+    /// is what the field holds and comes back after the newest entry.
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(position) => position - 1,
+        };
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    /// The entry after the one shown, or the text from before browsing
+    pub fn next(&mut self) -> Option<String> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(self.entries[position + 1].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    /// True while an entry is shown in place of typed text
+    pub fn is_browsing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Stop browsing, e.g. because the field was edited
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+}
+
+/// Histories saved across sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedHistories {
+    pub search: History,
+    pub replace: History,
+    pub commands: History,
+}
+
+impl SavedHistories {
+    /// `history.json` in the Nexus data directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("nexus").join("history.json"))
+    }
+
+    /// Load saved histories; missing or corrupt ones load empty
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename, so a crash never leaves half a file behind
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(temp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::default();
+        assert_eq!(history.previous("typed"), None);
+        for entry in ["a", "b", "", "a"] {
+            history.push(entry);
+        }
+        assert_eq!(history.last(), Some("a"));
+
+        // Up goes back from the newest, Down returns to what was typed
+        assert_eq!(history.previous("typed").as_deref(), Some("a"));
+        assert_eq!(history.previous("a").as_deref(), Some("b"));
+        assert_eq!(history.previous("b"), None);
+        assert_eq!(history.next().as_deref(), Some("a"));
+        assert_eq!(history.next().as_deref(), Some("typed"));
+        assert!(!history.is_browsing());
+        assert_eq!(history.next(), None);
+
+        let path = std::env::temp_dir().join("nexus_test_history/history.json");
+        let saved = SavedHistories { search: history, ..Default::default() };
+        saved.save(&path).unwrap();
+        let loaded = SavedHistories::load(&path);
+        assert_eq!(loaded.search.entries, ["b", "a"]);
+        assert!(loaded.commands.entries.is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::fmt;
+use tree_sitter::{Query, QueryCursor, StreamingIterator, Tree};
+
+use crate::brackets::Bracket;
+
+/// How a document indents: with tabs, or with a number of spaces per level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+impl IndentStyle {
+    /// Guess the style of existing text from its indented lines: tabs if
+    /// most lines start with one, otherwise the most common step between
+    /// the indentation of neighbouring lines. `None` if nothing is indented.
+    pub fn detect(source: &str) -> Option<Self> {
+        let (mut tab_lines, mut space_lines) = (0, 0);
+        let mut steps = [0usize; 9];
+        let mut previous = 0;
+        for line in source.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if spaces > 0 {
+                space_lines += 1;
+            }
+            if spaces > previous && spaces - previous < steps.len() {
+                steps[spaces - previous] += 1;
+            }
+            previous = spaces;
+        }
+
+        if tab_lines == 0 && space_lines == 0 {
+            None
+        } else if tab_lines > space_lines {
+            Some(Self::Tabs)
+        } else {
+            // Prefer the wider step on ties: 4-space code has 2-space continuations
+            let width = (2..steps.len()).max_by_key(|&width| steps[width]).unwrap_or(4);
+            Some(Self::Spaces(width))
+        }
+    }
+
+    /// Columns per indent level
+    pub fn width(&self, tab_size: usize) -> usize {
+        match self {
+            Self::Tabs => tab_size,
+            Self::Spaces(width) => *width,
+        }
+    }
+
+    /// Whitespace indenting a line by `columns`
+    pub fn render(&self, columns: usize, tab_size: usize) -> String {
+        match self {
+            Self::Tabs => format!("{}{}", "\t".repeat(columns / tab_size), " ".repeat(columns % tab_size)),
+            Self::Spaces(_) => " ".repeat(columns),
+        }
+    }
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        Self::Spaces(4)
+    }
+}
+
+impl fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tabs => write!(f, "Tabs"),
+            Self::Spaces(width) => write!(f, "Spaces: {}", width),
+        }
+    }
+}
+
+/// Leading spaces and tabs of a line
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Width of a line's indentation in columns
+pub fn indent_columns(line: &str, tab_size: usize) -> usize {
+    leading_whitespace(line).chars().fold(0, |columns, c| match c {
+        '\t' => (columns / tab_size + 1) * tab_size,
+        _ => columns + 1,
+    })
+}
+
+/// A region whose lines are indented one level deeper than its first line
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    row: usize,
+    start: usize,
+    /// `usize::MAX` for a bracket that isn't closed
+    end: usize,
+    /// End of an `@indent` token like Python's `:`, which also indents the
+    /// line after it while the block has no body yet
+    token_end: Option<usize>,
+}
+
+/// Indentation structure of a document, from the `@indent` and `@outdent`
+/// captures of its `indents.scm` and from its brackets. The brackets cover
+/// code the grammar can't parse yet, such as a block whose `}` isn't typed.
+pub struct IndentRules<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    scopes: Vec<Scope>,
+    /// Start offsets of `@outdent` captures
+    outdents: HashSet<usize>,
+    brackets: &'a [Bracket],
+}
+
+impl<'a> IndentRules<'a> {
+    pub fn new(tree: Option<&Tree>, query: Option<&Query>, source: &'a str, brackets: &'a [Bracket]) -> Self {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let row_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+        let mut scopes: Vec<Scope> = brackets
+            .iter()
+            .filter(|b| matches!(source.as_bytes()[b.offset], b'(' | b'[' | b'{'))
+            .map(|b| Scope {
+                row: row_of(b.offset),
+                start: b.offset,
+                end: b.partner.map_or(usize::MAX, |partner| partner + 1),
+                token_end: None,
+            })
+            .collect();
+        let mut outdents = HashSet::new();
+        if let (Some(tree), Some(query)) = (tree, query) {
+            let indent = query.capture_index_for_name("indent");
+            let outdent = query.capture_index_for_name("outdent");
+            let mut cursor = QueryCursor::new();
+            let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+            while let Some(match_item) = matches.next() {
+                for capture in match_item.captures {
+                    let node = capture.node;
+                    if Some(capture.index) == indent {
+                        // A token indents the node after it, like the block after a `:`
+                        let leaf = node.child_count() == 0;
+                        let body = node.next_sibling().filter(|_| leaf).unwrap_or(node);
+                        scopes.push(Scope {
+                            row: node.start_position().row,
+                            start: node.start_byte(),
+                            end: body.end_byte(),
+                            token_end: leaf.then(|| node.end_byte()),
+                        });
+                    } else if Some(capture.index) == outdent {
+                        outdents.insert(node.start_byte());
+                    }
+                }
+            }
+        }
+
+        Self { source, line_starts, scopes, outdents, brackets }
+    }
+
+    /// Change in indent levels from line `previous`, the last non-blank
+    /// line before `line`, to `line`
+    pub fn delta(&self, previous: usize, line: usize) -> isize {
+        self.level(line) - self.level(previous)
+    }
+
+    /// Nesting depth of a line: the scopes around its first character, at
+    /// most one per row they start on, less one if it starts by closing one
+    fn level(&self, line: usize) -> isize {
+        let (line_start, text) = self.line(line);
+        let first = line_start + leading_whitespace(text).len();
+        let previous_end = (0..line).rev().map(|l| self.line(l)).find(|(_, text)| !text.trim().is_empty()).map(
+            |(start, text)| start + text.trim_end().len(),
+        );
+
+        let rows: HashSet<usize> = self
+            .scopes
+            .iter()
+            .filter(|scope| {
+                scope.row < line
+                    && scope.start < first
+                    && (first < scope.end || scope.token_end.is_some() && scope.token_end == previous_end)
+            })
+            .map(|scope| scope.row)
+            .collect();
+
+        let closes = self.outdents.contains(&first)
+            || self.brackets.iter().any(|b| b.offset == first && b.partner.is_some_and(|partner| partner < line_start));
+        rows.len() as isize - closes as isize
+    }
+
+    fn line(&self, line: usize) -> (usize, &'a str) {
+        let start = self.line_starts.get(line).copied().unwrap_or(self.source.len());
+        let end = self.line_starts.get(line + 1).map_or(self.source.len(), |next| next - 1);
+        (start, &self.source[start..end.max(start)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brackets;
+    use crate::language::LanguageRegistry;
+
+    /// Indent every line of `source` from scratch, one level being 4 spaces
+    fn reindent(language: &str, source: &str) -> String {
+        let registry = LanguageRegistry::builtin();
+        let language = registry.get(language).unwrap();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&language.grammar).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let brackets = brackets::find_brackets(Some(&tree), source);
+        let rules = IndentRules::new(Some(&tree), language.query("indents"), source, &brackets);
+
+        let mut levels: Vec<isize> = Vec::new();
+        let mut previous = None;
+        let mut out = String::new();
+        for (line, text) in source.lines().enumerate() {
+            let level = match previous {
+                Some(previous) => (levels[previous] + rules.delta(previous, line)).max(0),
+                None => 0,
+            };
+            levels.push(level);
+            if !text.trim().is_empty() {
+                previous = Some(line);
+                out.push_str(&"    ".repeat(level as usize));
+            }
+            out.push_str(text.trim_start());
+            out.push('\n');
+        }
+        out
+    }
+
+    #[test]
+    fn test_indent_rules() {
+        let rust = "fn f(\na: u8,\n) {\nif a {\ng(1,\n2);\n}\n}\n";
+        assert_eq!(reindent("rust", rust), "fn f(\n    a: u8,\n) {\n    if a {\n        g(1,\n            2);\n    }\n}\n");
+
+        // Unclosed brackets still indent
+        assert_eq!(reindent("rust", "fn f() {\nlet v = [\n1"), "fn f() {\n    let v = [\n        1\n");
+
+        // Python's indentation is its structure, so it survives reindenting
+        let python = "class A:\n    def f(self):\n        if x:\n            pass\n        else:\n            return [\n                1]\n";
+        assert_eq!(reindent("python", python), python);
+
+        // A block without a body yet still indents the line after its `:`
+        let source = "if x:\n\n";
+        let registry = LanguageRegistry::builtin();
+        let language = registry.get("python").unwrap();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&language.grammar).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let rules = IndentRules::new(Some(&tree), language.query("indents"), source, &[]);
+        assert_eq!(rules.delta(0, 1), 1);
+    }
+
+    #[test]
+    fn test_detect_style() {
+        assert_eq!(IndentStyle::detect("a\n\tb\n\t\tc\n"), Some(IndentStyle::Tabs));
+        assert_eq!(IndentStyle::detect("a\n  b\n    c\n  d\n"), Some(IndentStyle::Spaces(2)));
+        assert_eq!(IndentStyle::detect("a {\n    b(1,\n      2)\n    c {\n        d\n"), Some(IndentStyle::Spaces(4)));
+        assert_eq!(IndentStyle::detect("a\nb\n"), None);
+
+        assert_eq!(IndentStyle::Tabs.render(6, 4), "\t  ");
+        assert_eq!(indent_columns("\t  x", 4), 6);
+    }
+}
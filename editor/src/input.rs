@@ -15,6 +15,18 @@ impl InputHandler {
         }
     }
 
+    /// Text on the system clipboard, if it holds any
+    pub fn clipboard_text(&mut self) -> Option<String> {
+        self.clipboard.as_mut()?.get_text().ok()
+    }
+
+    pub fn set_clipboard_text(&mut self, text: &str) {
+        if let Some(ref mut clipboard) = self.clipboard {
+            let _ = clipboard.set_text(text);
+            log::info!("📋 Copied to clipboard");
+        }
+    }
+
     pub fn copy(&mut self, buffer: &Buffer, cursor: &Cursor) {
         if let Some(ref selection) = cursor.selection {
             let start = buffer.position_to_offset(selection.start.line, selection.start.column);
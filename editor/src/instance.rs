@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::OpenRequest;
+
+/// Connection to a process that ran `nexus --wait`, closed once its tabs are
+#[cfg(unix)]
+pub type Caller = std::os::unix::net::UnixStream;
+/// No instance sockets off Unix, so no callers are ever waiting
+#[cfg(not(unix))]
+pub type Caller = std::io::Sink;
+
+/// A request sent by another `nexus` process to the running window
+#[derive(Debug)]
+pub struct RemoteOpen {
+    pub request: OpenRequest,
+    /// Set with `--wait`; dropping it lets the caller exit
+    pub caller: Option<Caller>,
+}
+
+/// Socket the first window listens on for later `nexus` invocations
+pub fn socket_path() -> PathBuf {
+    let user = std::env::var("USER").unwrap_or_default();
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("nexus-{}.sock", user))
+}
+
+/// Send `request` to the window listening on `socket`. Returns false if no
+/// window is running. With `request.wait`, blocks until its tabs are closed.
+#[cfg(unix)]
+pub fn forward(socket: &Path, request: &OpenRequest) -> Result<bool> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(socket) else {
+        return Ok(false);
+    };
+    stream.write_all(&serde_json::to_vec(request)?)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    // The window closes the connection when done with the request
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).context("Lost the connection to Nexus")?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+pub fn forward(_socket: &Path, _request: &OpenRequest) -> Result<bool> {
+    Ok(false)
+}
+
+/// Take requests from later `nexus` invocations on a background thread,
+/// handing each to `handle` until it returns false. Does nothing if
+/// another window already listens on `socket`.
+#[cfg(unix)]
+pub fn listen(socket: PathBuf, handle: impl Fn(RemoteOpen) -> bool + Send + 'static) -> Result<()> {
+    use std::io::Read;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if socket.exists() {
+        if UnixStream::connect(&socket).is_ok() {
+            return Ok(());
+        }
+        // Left behind by a window that didn't shut down cleanly
+        std::fs::remove_file(&socket)?;
+    }
+    let listener = UnixListener::bind(&socket).with_context(|| format!("Failed to listen on {}", socket.display()))?;
+
+    std::thread::Builder::new().name("instance".into()).spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut text = Vec::new();
+            let request: OpenRequest = match stream.read_to_end(&mut text).map(|_| serde_json::from_slice(&text)) {
+                Ok(Ok(request)) => request,
+                _ => {
+                    log::warn!("Ignored an invalid request from another nexus process");
+                    continue;
+                }
+            };
+            let caller = request.wait.then_some(stream);
+            if !handle(RemoteOpen { request, caller }) {
+                break;
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen(_socket: PathBuf, _handle: impl Fn(RemoteOpen) -> bool + Send + 'static) -> Result<()> {
+    Ok(())
+}
+
+/// Tabs opened with `--wait`, by the caller waiting for them
+struct Waiter {
+    tabs: Vec<usize>,
+    /// `None` when this process was started with `--wait`
+    caller: Option<Caller>,
+}
+
+/// Callers waiting for tabs to be closed
+#[derive(Default)]
+pub struct WaitList {
+    waiters: Vec<Waiter>,
+}
+
+impl WaitList {
+    /// Keep `caller` waiting until the tabs with these ids are closed.
+    /// `None` stands for this process.
+    pub fn add(&mut self, tabs: Vec<usize>, caller: Option<Caller>) {
+        if !tabs.is_empty() {
+            self.waiters.push(Waiter { tabs, caller });
+        }
+    }
+
+    pub fn is_waiting_for(&self, tab: usize) -> bool {
+        self.waiters.iter().any(|waiter| waiter.tabs.contains(&tab))
+    }
+
+    /// Forget a closed tab, letting callers whose tabs are all closed go.
+    /// Returns true if this process was waiting and should exit.
+    pub fn tab_closed(&mut self, tab: usize) -> bool {
+        let mut exit = false;
+        self.waiters.retain_mut(|waiter| {
+            waiter.tabs.retain(|&id| id != tab);
+            let done = waiter.tabs.is_empty();
+            exit |= done && waiter.caller.is_none();
+            !done
+        });
+        exit
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::cli::FileArg;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_forward_and_wait() {
+        let socket = std::env::temp_dir().join("nexus_test_instance.sock");
+        let _ = std::fs::remove_file(&socket);
+        let request = OpenRequest { wait: true, ..Default::default() };
+        assert!(!forward(&socket, &request).unwrap());
+
+        let (sender, receiver) = mpsc::channel();
+        listen(socket.clone(), move |open| sender.send(open).is_ok()).unwrap();
+        let file = FileArg { path: PathBuf::from("/w/main.rs"), line: Some(3), column: None };
+        let request = OpenRequest { files: vec![file], wait: true, ..Default::default() };
+        let client = {
+            let socket = socket.clone();
+            std::thread::spawn(move || forward(&socket, &request).unwrap())
+        };
+
+        // The caller is held until both of its tabs are closed
+        let open = receiver.recv().unwrap();
+        assert_eq!(open.request.files[0].line, Some(3));
+        let mut waits = WaitList::default();
+        waits.add(vec![1, 2], open.caller);
+        waits.add(vec![2], None);
+        assert!(!waits.tab_closed(1));
+        assert!(!client.is_finished());
+        assert!(waits.tab_closed(2));
+        assert!(!waits.is_waiting_for(2));
+        assert!(client.join().unwrap());
+
+        let _ = std::fs::remove_file(&socket);
+    }
+}
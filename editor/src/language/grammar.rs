@@ -0,0 +1,112 @@
+// Load tree-sitter grammars at runtime, compiled either as shared libraries
+// or to WebAssembly.
+//
+// A grammar built with `tree-sitter generate` and a C compiler exports one
+// function, `tree_sitter_<name>`, that returns the language tables. The
+// library has to stay loaded for as long as the language is used, which for
+// an editor session means forever, so loaded libraries are never closed.
+//
+// A `.wasm` grammar (`tree-sitter build --wasm`) is run by wasmtime. Every
+// parser using one needs a wasm store made from the same engine as the store
+// that loaded it, so set languages through `set_language` below rather than
+// on the parser directly.
+
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use tree_sitter::Parser;
+
+/// Look for a grammar file in a language directory. Tried in order:
+/// `<name>.<dll ext>`, `libtree-sitter-<name>.<dll ext>`, `tree-sitter-<name>.<dll ext>`,
+/// then the same names with `.wasm`.
+pub fn find(dir: &Path, name: &str) -> Option<PathBuf> {
+    [std::env::consts::DLL_EXTENSION, "wasm"]
+        .iter()
+        .flat_map(|ext| {
+            [
+                format!("{}.{}", name, ext),
+                format!("libtree-sitter-{}.{}", name, ext),
+                format!("tree-sitter-{}.{}", name, ext),
+            ]
+        })
+        .map(|file| dir.join(file))
+        .find(|path| path.is_file())
+}
+
+/// Load the grammar `name` from a shared library or a `.wasm` file
+pub fn load(path: &Path, name: &str) -> Result<tree_sitter::Language> {
+    let language = if path.extension().and_then(|e| e.to_str()) == Some("wasm") {
+        load_wasm(path, name)?
+    } else {
+        load_library(path, name)?
+    };
+
+    let version = language.abi_version();
+    if !(tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION).contains(&version) {
+        bail!(
+            "{}: grammar ABI version {} is not supported (expected {} to {})",
+            path.display(),
+            version,
+            tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+            tree_sitter::LANGUAGE_VERSION
+        );
+    }
+    Ok(language)
+}
+
+/// Set the language of `parser`, giving it a wasm store first if the
+/// language was loaded from a `.wasm` grammar
+pub fn set_language(parser: &mut Parser, language: &tree_sitter::Language) -> Result<()> {
+    #[cfg(feature = "wasm")]
+    if language.is_wasm() {
+        let store = match parser.take_wasm_store() {
+            Some(store) => store,
+            None => tree_sitter::WasmStore::new(wasm::engine())?,
+        };
+        parser.set_wasm_store(store)?;
+    }
+    parser.set_language(language)?;
+    Ok(())
+}
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use std::sync::OnceLock;
+    use tree_sitter::wasmtime::Engine;
+
+    /// Stores can only run languages loaded by a store of the same engine
+    pub fn engine() -> &'static Engine {
+        static ENGINE: OnceLock<Engine> = OnceLock::new();
+        ENGINE.get_or_init(Engine::default)
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm(path: &Path, name: &str) -> Result<tree_sitter::Language> {
+    let bytes = std::fs::read(path)?;
+    let mut store = tree_sitter::WasmStore::new(wasm::engine())?;
+    Ok(store.load_language(name, &bytes)?)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm(path: &Path, _name: &str) -> Result<tree_sitter::Language> {
+    bail!(
+        "{}: this build has no WASM runtime, rebuild with `--features wasm` or compile the grammar as a shared library",
+        path.display()
+    );
+}
+
+fn load_library(path: &Path, name: &str) -> Result<tree_sitter::Language> {
+    let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+    let (library, language) = unsafe {
+        let library = libloading::Library::new(path)?;
+        let constructor: libloading::Symbol<unsafe extern "C" fn() -> tree_sitter::Language> =
+            library.get(symbol.as_bytes())?;
+        let language = constructor();
+        (library, language)
+    };
+
+    // `language` points into the library's data
+    std::mem::forget(library);
+    Ok(language)
+}
@@ -0,0 +1,476 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tree_sitter::Query;
+
+pub mod grammar;
+
+/// Built-in language definitions, shipped with the binary
+const BUILTIN_LANGUAGES: &str = include_str!("../../languages.toml");
+
+/// Query files a language can provide, by file stem (`highlights.scm`, ...)
+pub const QUERY_KINDS: &[&str] = &["highlights", "injections", "folds", "textobjects", "tags", "indents"];
+
+/// Language definition as written in `languages.toml` or a user `language.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Identifier used in config files and the language picker, e.g. `rust`
+    pub name: String,
+    /// Name shown to the user, e.g. `Rust`
+    pub display_name: Option<String>,
+    /// Grammar name: the library exports `tree_sitter_<grammar>`. Defaults to `name`.
+    pub grammar: Option<String>,
+    /// File extensions without the dot; may contain dots themselves (`d.ts`)
+    pub extensions: Vec<String>,
+    /// Exact file names such as `Makefile`
+    pub filenames: Vec<String>,
+    /// Interpreters named in a `#!` line. `python` also matches `python3.12`.
+    pub shebangs: Vec<String>,
+    /// Regex matched against the first line of the file
+    pub first_line: Option<String>,
+    /// Pairs closed automatically while typing, as two characters each
+    /// (`"()"`, `"''"`). Empty uses brackets and double quotes.
+    pub auto_pairs: Vec<String>,
+    /// Token starting a line comment, e.g. `//`
+    pub line_comment: Option<String>,
+    /// Tokens opening and closing a block comment, e.g. `["/*", "*/"]`
+    pub block_comment: Option<(String, String)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LanguagesFile {
+    #[serde(default)]
+    language: Vec<LanguageConfig>,
+}
+
+/// A language with a loaded grammar and its query sources
+pub struct Language {
+    pub config: LanguageConfig,
+    pub grammar: tree_sitter::Language,
+    first_line: Option<Regex>,
+    queries: HashMap<&'static str, Cow<'static, str>>,
+    /// Queries compiled on first use, in `QUERY_KINDS` order
+    compiled: Vec<OnceLock<Option<Query>>>,
+}
+
+impl Language {
+    fn new(
+        config: LanguageConfig,
+        grammar: tree_sitter::Language,
+        queries: HashMap<&'static str, Cow<'static, str>>,
+    ) -> Self {
+        let first_line = config.first_line.as_deref().and_then(|pattern| {
+            Regex::new(pattern)
+                .map_err(|e| log::warn!("Invalid first_line pattern for {}: {}", config.name, e))
+                .ok()
+        });
+
+        Self {
+            config,
+            grammar,
+            first_line,
+            queries,
+            compiled: QUERY_KINDS.iter().map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.config.display_name.as_deref().unwrap_or(&self.config.name)
+    }
+
+    /// Open and close characters of the pairs closed automatically
+    pub fn auto_pairs(&self) -> Vec<(char, char)> {
+        let pairs: Vec<(char, char)> = self
+            .config
+            .auto_pairs
+            .iter()
+            .filter_map(|pair| {
+                let mut chars = pair.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(open), Some(close), None) => Some((open, close)),
+                    _ => None,
+                }
+            })
+            .collect();
+        if pairs.is_empty() {
+            crate::brackets::DEFAULT_PAIRS.to_vec()
+        } else {
+            pairs
+        }
+    }
+
+    /// Source of a query file (`highlights`, ...) if the language has one
+    pub fn query_source(&self, kind: &str) -> Option<&str> {
+        self.queries.get(kind).map(|source| source.as_ref())
+    }
+
+    /// Compiled query of the given kind. Compiled once on first use;
+    /// `None` if the language has no such query or it fails to compile.
+    pub fn query(&self, kind: &str) -> Option<&Query> {
+        let index = QUERY_KINDS.iter().position(|k| *k == kind)?;
+        self.compiled[index]
+            .get_or_init(|| {
+                let source = self.query_source(kind)?;
+                Query::new(&self.grammar, source)
+                    .map_err(|e| log::warn!("Invalid {} query for {}: {:?}", kind, self.name(), e))
+                    .ok()
+            })
+            .as_ref()
+    }
+}
+
+impl std::fmt::Debug for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Language").field("name", &self.config.name).finish()
+    }
+}
+
+/// Grammars compiled into the binary
+fn builtin_grammar(name: &str) -> Option<tree_sitter::Language> {
+    match name {
+        "rust" => Some(tree_sitter_rust::LANGUAGE.into()),
+        "javascript" => Some(tree_sitter_javascript::LANGUAGE.into()),
+        "typescript" => Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
+        "tsx" => Some(tree_sitter_typescript::LANGUAGE_TSX.into()),
+        "python" => Some(tree_sitter_python::LANGUAGE.into()),
+        _ => None,
+    }
+}
+
+/// Queries shipped for the built-in grammars
+fn builtin_query(name: &str, kind: &str) -> Option<&'static str> {
+    match (name, kind) {
+        ("tsx", _) => builtin_query("typescript", kind),
+        ("rust", "highlights") => Some(include_str!("../../queries/rust/highlights.scm")),
+        ("javascript", "highlights") => Some(include_str!("../../queries/javascript/highlights.scm")),
+        ("typescript", "highlights") => Some(include_str!("../../queries/typescript/highlights.scm")),
+        ("python", "highlights") => Some(include_str!("../../queries/python/highlights.scm")),
+        ("rust", "injections") => Some(include_str!("../../queries/rust/injections.scm")),
+        ("javascript", "injections") => Some(include_str!("../../queries/javascript/injections.scm")),
+        ("typescript", "injections") => Some(include_str!("../../queries/typescript/injections.scm")),
+        ("python", "injections") => Some(include_str!("../../queries/python/injections.scm")),
+        ("rust", "folds") => Some(include_str!("../../queries/rust/folds.scm")),
+        ("javascript", "folds") => Some(include_str!("../../queries/javascript/folds.scm")),
+        ("typescript", "folds") => Some(include_str!("../../queries/typescript/folds.scm")),
+        ("python", "folds") => Some(include_str!("../../queries/python/folds.scm")),
+        ("rust", "textobjects") => Some(include_str!("../../queries/rust/textobjects.scm")),
+        ("javascript", "textobjects") => Some(include_str!("../../queries/javascript/textobjects.scm")),
+        ("typescript", "textobjects") => Some(include_str!("../../queries/typescript/textobjects.scm")),
+        ("python", "textobjects") => Some(include_str!("../../queries/python/textobjects.scm")),
+        ("rust", "tags") => Some(include_str!("../../queries/rust/tags.scm")),
+        ("javascript", "tags") => Some(include_str!("../../queries/javascript/tags.scm")),
+        ("typescript", "tags") => Some(include_str!("../../queries/typescript/tags.scm")),
+        ("python", "tags") => Some(include_str!("../../queries/python/tags.scm")),
+        ("rust", "indents") => Some(include_str!("../../queries/rust/indents.scm")),
+        ("javascript", "indents") => Some(include_str!("../../queries/javascript/indents.scm")),
+        ("typescript", "indents") => Some(include_str!("../../queries/typescript/indents.scm")),
+        ("python", "indents") => Some(include_str!("../../queries/python/indents.scm")),
+        _ => None,
+    }
+}
+
+/// Every known language, and detection of which one a file is written in
+pub struct LanguageRegistry {
+    languages: Vec<Arc<Language>>,
+}
+
+impl LanguageRegistry {
+    /// Built-in languages plus any user languages
+    pub fn new() -> Self {
+        let mut registry = Self::builtin();
+
+        if let Some(dir) = Self::languages_dir() {
+            registry.load_dir(&dir);
+        }
+
+        registry
+    }
+
+    /// Only the languages compiled into the binary
+    pub fn builtin() -> Self {
+        let mut registry = Self { languages: Vec::new() };
+
+        let file: LanguagesFile =
+            toml::from_str(BUILTIN_LANGUAGES).expect("built-in languages.toml is valid");
+
+        for config in file.language {
+            let grammar_name = config.grammar.clone().unwrap_or_else(|| config.name.clone());
+            let grammar = match builtin_grammar(&grammar_name) {
+                Some(grammar) => grammar,
+                None => continue,
+            };
+
+            let queries = QUERY_KINDS
+                .iter()
+                .filter_map(|kind| builtin_query(&grammar_name, kind).map(|q| (*kind, Cow::Borrowed(q))))
+                .collect();
+
+            registry.add(Language::new(config, grammar, queries));
+        }
+
+        registry
+    }
+
+    /// Directory user languages are loaded from (`<config>/nexus/languages`)
+    pub fn languages_dir() -> Option<PathBuf> {
+        crate::config::Config::config_dir().ok().map(|dir| dir.join("languages"))
+    }
+
+    /// Load every `<dir>/<name>/language.toml`, replacing built-in languages
+    /// with the same name
+    pub fn load_dir(&mut self, dir: &Path) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.join("language.toml").is_file())
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match Self::load_language(&path) {
+                Ok(language) => {
+                    log::info!("🧩 Loaded language '{}' from {}", language.name(), path.display());
+                    self.add(language);
+                }
+                Err(e) => log::warn!("Failed to load language {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Load one user language directory: `language.toml`, a grammar library
+    /// and `.scm` query files. Without a grammar library the built-in grammar
+    /// of the same name is used, so a directory can just add file types or
+    /// replace queries of a built-in language.
+    fn load_language(dir: &Path) -> Result<Language> {
+        let content = std::fs::read_to_string(dir.join("language.toml"))?;
+        let mut config: LanguageConfig = toml::from_str(&content)?;
+
+        if config.name.is_empty() {
+            config.name = dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+        }
+
+        let grammar_name = config.grammar.clone().unwrap_or_else(|| config.name.clone());
+        let grammar = match grammar::find(dir, &grammar_name) {
+            Some(path) => grammar::load(&path, &grammar_name)?,
+            None => builtin_grammar(&grammar_name).ok_or_else(|| {
+                anyhow::anyhow!("No grammar library for '{}' in {}", grammar_name, dir.display())
+            })?,
+        };
+
+        let mut queries = HashMap::new();
+        for kind in QUERY_KINDS {
+            let path = dir.join(format!("{}.scm", kind));
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                queries.insert(*kind, Cow::Owned(source));
+            } else if let Some(source) = builtin_query(&grammar_name, kind) {
+                queries.insert(*kind, Cow::Borrowed(source));
+            }
+        }
+
+        Ok(Language::new(config, grammar, queries))
+    }
+
+    /// Add a language, replacing one with the same name
+    pub fn add(&mut self, language: Language) {
+        let language = Arc::new(language);
+        match self.languages.iter().position(|l| l.name() == language.name()) {
+            Some(idx) => self.languages[idx] = language,
+            None => self.languages.push(language),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Language>> {
+        self.languages.iter().find(|l| l.name() == name).cloned()
+    }
+
+    /// Resolve a language named by an injection query or a Markdown code
+    /// fence: `python`, `py` and `Python` all find Python
+    pub fn for_injection(&self, name: &str) -> Option<Arc<Language>> {
+        let name = name.trim();
+        let find = |matches: &dyn Fn(&Language) -> bool| self.languages.iter().find(|l| matches(l)).cloned();
+
+        find(&|l| l.name().eq_ignore_ascii_case(name))
+            .or_else(|| find(&|l| l.config.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(name))))
+            .or_else(|| find(&|l| l.display_name().eq_ignore_ascii_case(name)))
+    }
+
+    pub fn languages(&self) -> &[Arc<Language>] {
+        &self.languages
+    }
+
+    /// Detect the language of a file from its name, then its first line.
+    /// Returns `None` for plain text.
+    pub fn detect(&self, path: Option<&Path>, first_line: &str) -> Option<Arc<Language>> {
+        let file_name = path.and_then(|p| p.file_name()).and_then(|n| n.to_str());
+
+        if let Some(file_name) = file_name {
+            if let Some(language) = self
+                .languages
+                .iter()
+                .find(|l| l.config.filenames.iter().any(|f| f == file_name))
+            {
+                return Some(language.clone());
+            }
+
+            // Longest matching extension wins, so `d.ts` beats `ts`
+            let by_extension = self
+                .languages
+                .iter()
+                .flat_map(|l| l.config.extensions.iter().map(move |ext| (l, ext)))
+                .filter(|(_, ext)| has_extension(file_name, ext))
+                .max_by_key(|(_, ext)| ext.len());
+            if let Some((language, _)) = by_extension {
+                return Some(language.clone());
+            }
+        }
+
+        if let Some(interpreter) = shebang_interpreter(first_line) {
+            if let Some(language) = self
+                .languages
+                .iter()
+                .find(|l| l.config.shebangs.iter().any(|s| interpreter_matches(interpreter, s)))
+            {
+                return Some(language.clone());
+            }
+        }
+
+        self.languages
+            .iter()
+            .find(|l| l.first_line.as_ref().is_some_and(|re| re.is_match(first_line)))
+            .cloned()
+    }
+}
+
+impl Default for LanguageRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// True if `file_name` ends in `.ext` (case-insensitively) and has a stem
+fn has_extension(file_name: &str, ext: &str) -> bool {
+    let suffix_start = match file_name.len().checked_sub(ext.len() + 1) {
+        Some(start) if start > 0 => start,
+        _ => return false,
+    };
+    file_name.is_char_boundary(suffix_start)
+        && file_name[suffix_start..].starts_with('.')
+        && file_name[suffix_start + 1..].eq_ignore_ascii_case(ext)
+}
+
+/// Interpreter named by a `#!` line: `#!/usr/bin/env -S python3 -u` → `python3`
+fn shebang_interpreter(first_line: &str) -> Option<&str> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+
+    if program != "env" {
+        return Some(program);
+    }
+
+    // Skip env's flags and VAR=value assignments
+    words
+        .find(|w| !w.starts_with('-') && !w.contains('='))
+        .and_then(|w| w.rsplit('/').next())
+}
+
+/// `python` matches `python`, `python3` and `python3.12`
+fn interpreter_matches(interpreter: &str, shebang: &str) -> bool {
+    interpreter
+        .strip_prefix(shebang)
+        .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(registry: &LanguageRegistry, path: &str, first_line: &str) -> Option<String> {
+        let path = (!path.is_empty()).then(|| Path::new(path));
+        registry
+            .detect(path, first_line)
+            .map(|l| l.name().to_string())
+    }
+
+    #[test]
+    fn test_builtin_languages() {
+        let registry = LanguageRegistry::builtin();
+        for name in ["rust", "javascript", "typescript", "tsx", "python"] {
+            let language = registry.get(name).unwrap();
+            assert!(language.query_source("highlights").is_some(), "{} has no highlights", name);
+            assert!(language.query("folds").is_some(), "folds for {} failed to compile", name);
+            assert!(language.query("textobjects").is_some(), "textobjects for {} failed to compile", name);
+            assert!(language.query("tags").is_some(), "tags for {} failed to compile", name);
+            assert!(language.query("indents").is_some(), "indents for {} failed to compile", name);
+        }
+    }
+
+    #[test]
+    fn test_detection() {
+        let registry = LanguageRegistry::builtin();
+
+        assert_eq!(detect(&registry, "src/main.rs", "").as_deref(), Some("rust"));
+        assert_eq!(detect(&registry, "App.JSX", "").as_deref(), Some("javascript"));
+        assert_eq!(detect(&registry, "App.tsx", "").as_deref(), Some("tsx"));
+        assert_eq!(detect(&registry, "SConstruct", "").as_deref(), Some("python"));
+        assert_eq!(detect(&registry, "script", "#!/usr/bin/env -S python3.12 -u").as_deref(), Some("python"));
+        assert_eq!(detect(&registry, "", "#!/usr/local/bin/node").as_deref(), Some("javascript"));
+        assert_eq!(detect(&registry, "notes", "# -*- mode: python -*-").as_deref(), Some("python"));
+
+        assert_eq!(registry.for_injection("py").map(|l| l.name().to_string()).as_deref(), Some("python"));
+        assert_eq!(registry.for_injection("TypeScript").map(|l| l.name().to_string()).as_deref(), Some("typescript"));
+        assert!(registry.for_injection("sql").is_none());
+
+        // Unknown files are plain text rather than some default grammar
+        assert_eq!(detect(&registry, "notes.txt", "hello"), None);
+        assert_eq!(detect(&registry, ".rs", ""), None);
+        assert_eq!(detect(&registry, "run", "#!/bin/sh"), None);
+    }
+
+    #[test]
+    fn test_user_language_dir() {
+        let dir = std::env::temp_dir().join("nexus_test_languages");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("starlark")).unwrap();
+        std::fs::write(
+            dir.join("starlark/language.toml"),
+            "grammar = \"python\"\nextensions = [\"bzl\", \"star\"]\nfilenames = [\"BUILD\"]\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("starlark/highlights.scm"), "(comment) @comment\n").unwrap();
+        // A grammar that isn't WebAssembly fails to load, leaving the language out
+        std::fs::create_dir_all(dir.join("broken")).unwrap();
+        std::fs::write(dir.join("broken/broken.wasm"), "not wasm").unwrap();
+        let wasm = grammar::find(&dir.join("broken"), "broken").unwrap();
+        assert_eq!(wasm, dir.join("broken/broken.wasm"));
+        assert!(grammar::load(&wasm, "broken").is_err());
+
+        let mut registry = LanguageRegistry::builtin();
+        registry.load_dir(&dir);
+
+        let language = registry.get("starlark").unwrap();
+        assert_eq!(language.query_source("highlights"), Some("(comment) @comment\n"));
+        assert_eq!(detect(&registry, "pkg/BUILD", "").as_deref(), Some("starlark"));
+        assert_eq!(detect(&registry, "defs.bzl", "").as_deref(), Some("starlark"));
+        assert!(registry.get("broken").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
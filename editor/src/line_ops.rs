@@ -0,0 +1,534 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::buffer::Buffer;
+use crate::cursor::{Cursor, Position};
+use crate::indent::leading_whitespace;
+
+/// How `LineCommand::Sort` orders lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Compare runs of digits by value, so `a2` sorts before `a10`
+    pub natural: bool,
+    pub case_insensitive: bool,
+    /// Keep only the first of lines that compare equal
+    pub unique: bool,
+}
+
+/// Case styles for `LineCommand::ChangeCase`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Camel,
+    Kebab,
+}
+
+/// Editing commands on whole lines, or on the selection for case changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCommand {
+    MoveUp,
+    MoveDown,
+    /// Duplicate the selection, or the cursor's line without one
+    Duplicate,
+    Delete,
+    /// Join the selected lines, or the cursor's line with the next
+    Join,
+    InsertAbove,
+    InsertBelow,
+    Sort(SortOptions),
+    Reverse,
+    TrimTrailingWhitespace,
+    /// Change the case of the selection, or of the word at the cursor
+    ChangeCase(Case),
+}
+
+impl LineCommand {
+    /// Commands with their command palette labels
+    pub const ALL: &'static [(&'static str, LineCommand)] = &[
+        ("Move Line Up", LineCommand::MoveUp),
+        ("Move Line Down", LineCommand::MoveDown),
+        ("Duplicate Line or Selection", LineCommand::Duplicate),
+        ("Delete Line", LineCommand::Delete),
+        ("Join Lines", LineCommand::Join),
+        ("Insert Line Above", LineCommand::InsertAbove),
+        ("Insert Line Below", LineCommand::InsertBelow),
+        ("Sort Lines", LineCommand::Sort(SortOptions { natural: false, case_insensitive: false, unique: false })),
+        ("Sort Lines (Natural)", LineCommand::Sort(SortOptions { natural: true, case_insensitive: false, unique: false })),
+        (
+            "Sort Lines (Case Insensitive)",
+            LineCommand::Sort(SortOptions { natural: false, case_insensitive: true, unique: false }),
+        ),
+        ("Sort Lines (Unique)", LineCommand::Sort(SortOptions { natural: false, case_insensitive: false, unique: true })),
+        ("Reverse Lines", LineCommand::Reverse),
+        ("Trim Trailing Whitespace", LineCommand::TrimTrailingWhitespace),
+        ("Transform to Uppercase", LineCommand::ChangeCase(Case::Upper)),
+        ("Transform to Lowercase", LineCommand::ChangeCase(Case::Lower)),
+        ("Transform to Title Case", LineCommand::ChangeCase(Case::Title)),
+        ("Transform to Snake Case", LineCommand::ChangeCase(Case::Snake)),
+        ("Transform to Camel Case", LineCommand::ChangeCase(Case::Camel)),
+        ("Transform to Kebab Case", LineCommand::ChangeCase(Case::Kebab)),
+    ];
+}
+
+/// Run a command as a single undo step. The selection follows the text
+/// the command moved or changed.
+pub fn run(command: LineCommand, buffer: &mut Buffer, cursor: &mut Cursor) {
+    buffer.transaction(|buffer| match command {
+        LineCommand::MoveUp => move_lines(buffer, cursor, true),
+        LineCommand::MoveDown => move_lines(buffer, cursor, false),
+        LineCommand::Duplicate => duplicate(buffer, cursor),
+        LineCommand::Delete => delete_lines(buffer, cursor),
+        LineCommand::Join => join_lines(buffer, cursor),
+        LineCommand::InsertAbove => insert_line(buffer, cursor, true),
+        LineCommand::InsertBelow => insert_line(buffer, cursor, false),
+        LineCommand::Sort(options) => rewrite_lines(buffer, cursor, |lines| {
+            lines.sort_by(|a, b| compare_lines(a, b, options));
+            if options.unique {
+                lines.dedup_by(|a, b| compare_lines(a, b, options) == Ordering::Equal);
+            }
+        }),
+        LineCommand::Reverse => rewrite_lines(buffer, cursor, |lines| lines.reverse()),
+        LineCommand::TrimTrailingWhitespace => trim_trailing_whitespace(buffer, cursor),
+        LineCommand::ChangeCase(case) => change_case(buffer, cursor, case),
+    });
+    cursor.reset_blink();
+}
+
+/// Swap the target lines with the line above or below them
+fn move_lines(buffer: &mut Buffer, cursor: &mut Cursor, up: bool) {
+    let lines = target_lines(cursor);
+    let (range, delta) = if up {
+        if lines.start == 0 {
+            return;
+        }
+        (lines.start - 1..lines.end, -1)
+    } else {
+        if lines.end >= buffer.line_count() {
+            return;
+        }
+        (lines.start..lines.end + 1, 1)
+    };
+
+    let mut texts = line_texts(buffer, range.clone());
+    if up {
+        texts.rotate_left(1);
+    } else {
+        texts.rotate_right(1);
+    }
+    replace_lines(buffer, range, &texts);
+
+    let shift = |position: Position| Position::new((position.line as isize + delta) as usize, position.column);
+    cursor.selection = cursor.selection.clone().map(|s| shift(s.start)..shift(s.end));
+    cursor.position = shift(cursor.position);
+}
+
+fn duplicate(buffer: &mut Buffer, cursor: &mut Cursor) {
+    match selection_bounds(cursor) {
+        Some((start, end)) if start != end => {
+            // The copy goes after the selection and becomes the selection
+            let text = buffer.text_range(offset(buffer, start)..offset(buffer, end));
+            let end_offset = offset(buffer, end);
+            buffer.insert_text(end_offset, &text);
+            let copy_end = position(buffer, end_offset + text.len());
+            cursor.selection = Some(end..copy_end);
+            cursor.position = copy_end;
+        }
+        _ => {
+            let line = cursor.position.line;
+            let end = offset(buffer, Position::new(line, buffer.line_len(line)));
+            buffer.insert_text(end, &format!("\n{}", line_text(buffer, line)));
+            cursor.clear_selection();
+            cursor.position.line += 1;
+        }
+    }
+}
+
+fn delete_lines(buffer: &mut Buffer, cursor: &mut Cursor) {
+    let lines = target_lines(cursor);
+    let mut start = offset(buffer, Position::new(lines.start, 0));
+    let end = if lines.end < buffer.line_count() {
+        offset(buffer, Position::new(lines.end, 0))
+    } else {
+        // The last line has no line break of its own; take the one before it
+        start = start.saturating_sub(1);
+        buffer.len()
+    };
+    buffer.delete_range(start..end);
+
+    let line = lines.start.min(buffer.line_count().saturating_sub(1));
+    cursor.clear_selection();
+    cursor.position = Position::new(line, cursor.position.column.min(buffer.line_len(line)));
+}
+
+fn join_lines(buffer: &mut Buffer, cursor: &mut Cursor) {
+    let mut lines = target_lines(cursor);
+    let selected = lines.len() > 1;
+    if !selected {
+        if lines.end >= buffer.line_count() {
+            return;
+        }
+        lines.end += 1;
+    }
+
+    // Each joined line loses its indentation and gets one space before it
+    let texts = line_texts(buffer, lines.clone());
+    let mut joined = texts[0].clone();
+    let mut join_column = 0;
+    for text in &texts[1..] {
+        joined.truncate(joined.trim_end().len());
+        join_column = joined.chars().count();
+        let text = text.trim();
+        if !text.is_empty() && !joined.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(text);
+    }
+    replace_lines(buffer, lines.clone(), &[joined.clone()]);
+
+    if selected {
+        let start = selection_bounds(cursor).map_or(0, |(start, _)| start.column);
+        let end = Position::new(lines.start, joined.chars().count());
+        cursor.selection = Some(Position::new(lines.start, start)..end);
+        cursor.position = end;
+    } else {
+        cursor.clear_selection();
+        cursor.position = Position::new(lines.start, join_column);
+    }
+}
+
+/// Open an empty line above or below the cursor's, indented like it
+fn insert_line(buffer: &mut Buffer, cursor: &mut Cursor, above: bool) {
+    let line = cursor.position.line;
+    let text = line_text(buffer, line);
+    let indent = leading_whitespace(&text);
+    if above {
+        buffer.insert_text(offset(buffer, Position::new(line, 0)), &format!("{}\n", indent));
+    } else {
+        buffer.insert_text(offset(buffer, Position::new(line, text.len())), &format!("\n{}", indent));
+    }
+    cursor.clear_selection();
+    cursor.position = Position::new(if above { line } else { line + 1 }, indent.chars().count());
+}
+
+/// Replace the selected lines, or the whole document without a selection
+/// across lines, with `rewrite` of them and select the result
+fn rewrite_lines(buffer: &mut Buffer, cursor: &mut Cursor, rewrite: impl FnOnce(&mut Vec<String>)) {
+    let lines = block_lines(buffer, cursor);
+    let mut texts = line_texts(buffer, lines.clone());
+    rewrite(&mut texts);
+    replace_lines(buffer, lines.clone(), &texts);
+
+    let last = lines.start + texts.len().max(1) - 1;
+    let end = Position::new(last, buffer.line_len(last));
+    cursor.selection = Some(Position::new(lines.start, 0)..end);
+    cursor.position = end;
+}
+
+fn trim_trailing_whitespace(buffer: &mut Buffer, cursor: &mut Cursor) {
+    let lines = block_lines(buffer, cursor);
+    let texts: Vec<String> = line_texts(buffer, lines.clone()).iter().map(|text| text.trim_end().to_string()).collect();
+    replace_lines(buffer, lines, &texts);
+
+    let clamp = |position: Position| Position::new(position.line, position.column.min(buffer.line_len(position.line)));
+    cursor.selection = cursor.selection.clone().map(|s| clamp(s.start)..clamp(s.end));
+    cursor.position = clamp(cursor.position);
+}
+
+fn change_case(buffer: &mut Buffer, cursor: &mut Cursor, case: Case) {
+    let (start, end) = match selection_bounds(cursor) {
+        Some((start, end)) if start != end => (start, end),
+        _ => match word_at(buffer, cursor.position) {
+            Some(word) => word,
+            None => return,
+        },
+    };
+    let range = offset(buffer, start)..offset(buffer, end);
+    let text = buffer.text_range(range.clone());
+    let converted = convert_case(&text, case);
+    if converted != text {
+        buffer.delete_range(range.clone());
+        buffer.insert_text(range.start, &converted);
+    }
+
+    let end = position(buffer, range.start + converted.len());
+    cursor.selection = Some(start..end);
+    cursor.position = end;
+}
+
+/// `text` in another case. Snake, camel and kebab case rejoin the words of
+/// each line, split at separators and at lower-to-upper case changes.
+pub fn convert_case(text: &str, case: Case) -> String {
+    let join = |separator: &str, camel: bool| {
+        let lines: Vec<String> = text
+            .split('\n')
+            .map(|line| {
+                let lead = leading_whitespace(line);
+                let content = line.trim();
+                if content.is_empty() {
+                    return line.to_string();
+                }
+                let words: Vec<String> = words(content)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, word)| if camel && i > 0 { capitalize(word) } else { word.to_lowercase() })
+                    .collect();
+                format!("{}{}{}", lead, words.join(separator), &line[lead.len() + content.len()..])
+            })
+            .collect();
+        lines.join("\n")
+    };
+
+    match case {
+        Case::Upper => text.to_uppercase(),
+        Case::Lower => text.to_lowercase(),
+        Case::Title => {
+            let mut out = String::with_capacity(text.len());
+            let mut in_word = false;
+            for c in text.chars() {
+                if c.is_alphanumeric() {
+                    if in_word {
+                        out.extend(c.to_lowercase());
+                    } else {
+                        out.extend(c.to_uppercase());
+                    }
+                } else {
+                    out.push(c);
+                }
+                in_word = c.is_alphanumeric();
+            }
+            out
+        }
+        Case::Snake => join("_", false),
+        Case::Camel => join("", true),
+        Case::Kebab => join("-", false),
+    }
+}
+
+/// Words of an identifier or phrase: `parseHTTPRequest_v2` is `parse`,
+/// `HTTP`, `Request`, `v2`
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        // Capitals start a word after lowercase or digits, and the last
+        // capital of an acronym starts the word after it
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_lowercase() || p.is_ascii_digit() || p.is_uppercase() && next.is_some_and(|n| n.is_lowercase())
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+fn compare_lines(a: &str, b: &str, options: SortOptions) -> Ordering {
+    let (a, b) = if options.case_insensitive {
+        (a.to_lowercase(), b.to_lowercase())
+    } else {
+        (a.to_string(), b.to_string())
+    };
+    if !options.natural {
+        return a.cmp(&b);
+    }
+
+    let (mut a, mut b) = (a.as_str(), b.as_str());
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                let (a_len, b_len) = (digits(a), digits(b));
+                let (a_number, b_number) = (a[..a_len].trim_start_matches('0'), b[..b_len].trim_start_matches('0'));
+                let order = a_number.len().cmp(&b_number.len()).then_with(|| a_number.cmp(b_number));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+/// Selection start and end in document order
+fn selection_bounds(cursor: &Cursor) -> Option<(Position, Position)> {
+    let selection = cursor.selection.as_ref()?;
+    Some((selection.start.min(selection.end), selection.start.max(selection.end)))
+}
+
+/// The selected lines, or the cursor's line. A selection ending at the
+/// start of a line doesn't include that line.
+fn target_lines(cursor: &Cursor) -> Range<usize> {
+    match selection_bounds(cursor) {
+        Some((start, end)) if end.line > start.line => {
+            let last = if end.column == 0 { end.line - 1 } else { end.line };
+            start.line..last + 1
+        }
+        _ => cursor.position.line..cursor.position.line + 1,
+    }
+}
+
+/// The selected lines, or every line but a final empty one when the
+/// selection doesn't span lines
+fn block_lines(buffer: &Buffer, cursor: &Cursor) -> Range<usize> {
+    match selection_bounds(cursor) {
+        Some((start, end)) if end.line > start.line => target_lines(cursor),
+        _ => {
+            let count = buffer.line_count();
+            let last_is_empty = count > 1 && buffer.line_len(count - 1) == 0;
+            0..count - last_is_empty as usize
+        }
+    }
+}
+
+/// Start and end of the word at or just before a position
+fn word_at(buffer: &Buffer, position: Position) -> Option<(Position, Position)> {
+    let chars: Vec<char> = line_text(buffer, position.line).chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let column = position.column.min(chars.len());
+    let start = column - chars[..column].iter().rev().take_while(|c| is_word(c)).count();
+    let end = column + chars[column..].iter().take_while(|c| is_word(c)).count();
+    (start < end).then(|| (Position::new(position.line, start), Position::new(position.line, end)))
+}
+
+fn line_text(buffer: &Buffer, line: usize) -> String {
+    buffer.line(line).trim_end_matches('\n').to_string()
+}
+
+fn line_texts(buffer: &Buffer, lines: Range<usize>) -> Vec<String> {
+    lines.map(|line| line_text(buffer, line)).collect()
+}
+
+/// Replace the text of `lines`, keeping the line break after the last one
+fn replace_lines(buffer: &mut Buffer, lines: Range<usize>, texts: &[String]) {
+    let start = offset(buffer, Position::new(lines.start, 0));
+    let last = lines.end - 1;
+    let end = offset(buffer, Position::new(last, buffer.line_len(last)));
+    let text = texts.join("\n");
+    if buffer.text_range(start..end) == text {
+        return;
+    }
+    if start < end {
+        buffer.delete_range(start..end);
+    }
+    if !text.is_empty() {
+        buffer.insert_text(start, &text);
+    }
+}
+
+fn offset(buffer: &Buffer, position: Position) -> usize {
+    buffer.position_to_offset(position.line, position.column)
+}
+
+fn position(buffer: &Buffer, offset: usize) -> Position {
+    let (line, column) = buffer.offset_to_position(offset);
+    Position::new(line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_on(text: &str, selection: Option<Range<Position>>, position: Position, command: LineCommand) -> (Buffer, Cursor) {
+        let mut buffer = Buffer::from_text(text);
+        let mut cursor = Cursor::new();
+        cursor.selection = selection;
+        cursor.position = position;
+        run(command, &mut buffer, &mut cursor);
+        (buffer, cursor)
+    }
+
+    #[test]
+    fn test_move_and_duplicate() {
+        let selection = Some(Position::new(1, 1)..Position::new(2, 1));
+        let (mut buffer, cursor) = run_on("a\nb\nc\nd", selection, Position::new(2, 1), LineCommand::MoveDown);
+        assert_eq!(buffer.text(), "a\nd\nb\nc");
+        assert_eq!(cursor.selection, Some(Position::new(2, 1)..Position::new(3, 1)));
+
+        // The whole command is one undo step
+        buffer.undo();
+        assert_eq!(buffer.text(), "a\nb\nc\nd");
+
+        let (buffer, cursor) = run_on("a\nb", None, Position::new(1, 0), LineCommand::MoveUp);
+        assert_eq!(buffer.text(), "b\na");
+        assert_eq!(cursor.position, Position::new(0, 0));
+
+        let (buffer, cursor) = run_on("  ab\n", None, Position::new(0, 3), LineCommand::Duplicate);
+        assert_eq!(buffer.text(), "  ab\n  ab\n");
+        assert_eq!(cursor.position, Position::new(1, 3));
+
+        let (buffer, _) = run_on("a\nb", None, Position::new(1, 0), LineCommand::Delete);
+        assert_eq!(buffer.text(), "a");
+
+        let (buffer, cursor) = run_on("f(a,\n    b)", None, Position::new(0, 0), LineCommand::Join);
+        assert_eq!(buffer.text(), "f(a, b)");
+        assert_eq!(cursor.position, Position::new(0, 4));
+
+        let (buffer, cursor) = run_on("    x", None, Position::new(0, 2), LineCommand::InsertBelow);
+        assert_eq!(buffer.text(), "    x\n    ");
+        assert_eq!(cursor.position, Position::new(1, 4));
+    }
+
+    #[test]
+    fn test_sort_lines() {
+        let sort = |text: &str, natural, case_insensitive, unique| {
+            let options = SortOptions { natural, case_insensitive, unique };
+            run_on(text, None, Position::zero(), LineCommand::Sort(options)).0.text()
+        };
+        assert_eq!(sort("a10\nB\na2\n", false, false, false), "B\na10\na2\n");
+        assert_eq!(sort("a10\nB\na2\n", true, false, false), "B\na2\na10\n");
+        assert_eq!(sort("a10\nB\na2\n", true, true, false), "a2\na10\nB\n");
+        assert_eq!(sort("b\na\nB\na", false, true, true), "a\nb");
+
+        let (buffer, _) = run_on("a \nb\t\n", None, Position::zero(), LineCommand::TrimTrailingWhitespace);
+        assert_eq!(buffer.text(), "a\nb\n");
+    }
+
+    #[test]
+    fn test_change_case() {
+        assert_eq!(convert_case("parseHTTPRequest_v2", Case::Snake), "parse_http_request_v2");
+        assert_eq!(convert_case("  user-id", Case::Camel), "  userId");
+        assert_eq!(convert_case("Some Title", Case::Kebab), "some-title");
+        assert_eq!(convert_case("hello wORLD", Case::Title), "Hello World");
+
+        // Without a selection the word at the cursor changes and gets selected
+        let (buffer, cursor) = run_on("let fooBar = 1;", None, Position::new(0, 6), LineCommand::ChangeCase(Case::Snake));
+        assert_eq!(buffer.text(), "let foo_bar = 1;");
+        assert_eq!(cursor.selection, Some(Position::new(0, 4)..Position::new(0, 11)));
+    }
+}
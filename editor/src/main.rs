@@ -1,45 +1,109 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use winit::{
     event::{Event, WindowEvent},
-    event_loop::EventLoop,
+    event_loop::EventLoopBuilder,
     window::WindowBuilder,
 };
 
+mod brackets;
 mod buffer;
+mod cli;
+mod comment;
 mod config;
 mod cursor;
 mod file;
+mod file_ops;
 mod file_tree;
+mod folding;
+mod history;
+mod indent;
 mod input;
+mod instance;
+mod language;
+mod line_ops;
+mod motion;
+mod project_search;
 mod renderer;
 mod search;
+mod selection;
+mod symbol_index;
+mod symbols;
 mod syntax;
 mod tabs;
 mod text_renderer;
+mod theme;
 mod ui;
+mod workspace;
 
 use renderer::Renderer;
+use buffer::Buffer;
+use cli::{Args, OpenRequest, USAGE};
 use config::Config;
 use file::FileManager;
-use file_tree::FileTree;
+use file_ops::{FileAction, FileOp, FileOps};
+use file_tree::{FileTree, PromptKind, SortMode};
+use history::SavedHistories;
+use indent::IndentStyle;
 use input::InputHandler;
-use search::SearchState;
-use syntax::SyntaxHighlighter;
+use instance::{RemoteOpen, WaitList};
+use language::LanguageRegistry;
+use line_ops::LineCommand;
+use motion::{Motion, WordRules};
+use search::{SearchOption, SearchScope, SearchState};
+use symbol_index::{IndexedSymbol, SymbolIndex};
+use symbols::SymbolKind;
 use tabs::{Tab, TabManager};
+use theme::ThemeManager;
+use ui::command_palette::{CommandPalette, PaletteCommand, PaletteItem};
+use ui::outline::OutlinePanel;
+use ui::project_search::ProjectSearchPanel;
+use ui::text_input::InputResponse;
+use workspace::{FileList, Workspace};
+use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
 fn main() -> Result<()> {
+    // Parse the command line
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("nexus: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if args.version {
+        println!("nexus {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
     // Initialize logger
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    // Hand files to the running window, unless a new one or another
+    // workspace is asked for
+    let current_dir = env::current_dir()?;
+    let new_window = args.new_window;
+    let (mut folders, request) = args.into_request(&current_dir)?;
+    let socket = instance::socket_path();
+    if !new_window && folders.is_empty() && !request.is_empty() && instance::forward(&socket, &request)? {
+        return Ok(());
+    }
+
     log::info!("🚀 Starting Nexus Editor v{}", env!("CARGO_PKG_VERSION"));
 
     // Load configuration
-    let _config = Config::load()?;
+    let mut config = Config::load()?;
     log::info!("⚙️  Configuration loaded");
 
-    // Create event loop
-    let event_loop = EventLoop::new()?;
+    // Create event loop, woken by requests from later `nexus` invocations
+    let event_loop = EventLoopBuilder::<RemoteOpen>::with_user_event().build()?;
 
     // Create window
     let window = WindowBuilder::new()
@@ -49,10 +113,21 @@ fn main() -> Result<()> {
 
     log::info!("🪟 Window created");
 
+    let proxy = event_loop.create_proxy();
+    if let Err(e) = instance::listen(socket, move |open| proxy.send_event(open).is_ok()) {
+        log::warn!("Other nexus processes can't open files here: {}", e);
+    }
+
     // Initialize GPU renderer
     let mut renderer = pollster::block_on(Renderer::new(&window))?;
     log::info!("🎨 GPU renderer initialized");
 
+    // Load themes and apply the configured one
+    let mut theme_manager = ThemeManager::new(&config.ui.theme);
+    renderer.set_theme(theme_manager.active().clone());
+    renderer.rainbow_brackets = config.editor.rainbow_brackets;
+    let word_rules = WordRules::new(&config.editor.word_separators, config.editor.subword_navigation);
+
     // Create tab manager
     let mut tab_manager = TabManager::new();
     tab_manager.active_tab_mut().buffer.insert_text(0, "// Welcome to Nexus!\n// The AI-native code editor that doesn't suck.\n\nfn main() {\n    println!(\"Hello, World!\");\n}\n");
@@ -66,35 +141,82 @@ fn main() -> Result<()> {
     let mut file_manager = FileManager::new();
     log::info!("📁 File manager initialized");
 
-    // Create syntax highlighter (default to Rust)
-    let mut syntax_highlighter = SyntaxHighlighter::new("rs").ok();
-    let mut syntax_tokens = if let Some(ref mut highlighter) = syntax_highlighter {
-        highlighter.highlight(&tab_manager.active_tab().buffer.text())
-    } else {
-        Vec::new()
-    };
-    log::info!("🎨 Syntax highlighter initialized");
+    // Load languages and highlight the welcome text as Rust
+    let language_registry = Arc::new(LanguageRegistry::new());
+    tab_manager.active_tab_mut().set_language(language_registry.get("rust"), &language_registry);
+    log::info!("🎨 {} languages loaded", language_registry.languages().len());
 
     // Create search state
     let mut search_state = SearchState::new();
     log::info!("🔍 Search state initialized");
 
-    // Create file tree explorer
-    let current_dir = env::current_dir()?;
-    let mut file_tree = FileTree::new(current_dir).unwrap_or_else(|e| {
-        log::warn!("Failed to create file tree: {}. Using empty tree.", e);
-        FileTree {
-            nodes: Vec::new(),
-            selected_index: 0,
-            is_visible: false,
-            root_path: env::current_dir().unwrap_or_default(),
-        }
-    });
+    // Create command palette
+    let mut command_palette = CommandPalette::new();
+
+    // Restore search, replace and command histories from the last session
+    let history_path = SavedHistories::path();
+    if let Some(path) = &history_path {
+        let saved = SavedHistories::load(path);
+        search_state.query_history = saved.search;
+        search_state.replace_history = saved.replace;
+        command_palette.history = saved.commands;
+    }
+
+    // Create outline panel (hidden until toggled)
+    let mut outline_panel = OutlinePanel::new();
+
+    // Create project search panel (hidden until Ctrl+Shift+F)
+    let mut project_search = ProjectSearchPanel::new();
+
+    // Create file tree explorer over the workspace: the folders given on
+    // the command line, or the current directory or the workspace file in it
+    let workspace = if folders.len() > 1 {
+        Workspace::folders(folders)
+    } else {
+        let folder = folders.pop().unwrap_or(current_dir);
+        Workspace::open(&folder).unwrap_or_else(|e| {
+            log::warn!("Failed to open workspace: {}", e);
+            Workspace::folder(folder)
+        })
+    };
+    let mut file_tree = FileTree::new(workspace);
+    let mut file_list = FileList::default();
+    file_list.refresh(&file_tree.workspace);
     log::info!("🌳 File tree initialized");
 
+    // File operations from the tree, undoable until Nexus closes
+    let mut file_ops = FileOps::new();
+
+    // Index the workspace's symbols in the background
+    let symbol_index = SymbolIndex::spawn(&file_tree.workspace.roots, language_registry.clone());
+
     // Track buffer version to detect modifications
     let mut last_buffer_version = tab_manager.active_tab().buffer.version();
 
+    // Track keyboard modifiers for shortcuts
+    let mut modifiers = winit::keyboard::ModifiersState::empty();
+
+    // Last known mouse position, for clicks
+    let mut mouse_position = (0.0f32, 0.0f32);
+
+    // File tree entry being dragged to another directory
+    let mut dragged_file: Option<PathBuf> = None;
+
+    // Open what the command line asks for in place of the welcome tab
+    let mut waits = WaitList::default();
+    if !request.is_empty() {
+        let opened = open_request(&request, &mut tab_manager, &language_registry);
+        if !opened.is_empty() {
+            tab_manager.close_tab(0);
+            let tab = tab_manager.active_tab();
+            renderer.scroll_to_line(tab, tab.cursor.position.line);
+            last_buffer_version = tab.buffer.version();
+        }
+        if request.wait {
+            waits.add(opened, None);
+        }
+    }
+
     // Event loop
     log::info!("🔄 Entering event loop");
 
@@ -103,11 +225,71 @@ fn main() -> Result<()> {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => {
                     log::info!("👋 Closing Nexus");
+                    save_histories(history_path.as_deref(), &search_state, &command_palette);
                     control_flow.exit();
                 }
                 WindowEvent::Resized(physical_size) => {
                     renderer.resize(physical_size);
                 }
+                WindowEvent::ModifiersChanged(new_modifiers) => {
+                    modifiers = new_modifiers.state();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    mouse_position = (position.x as f32, position.y as f32);
+                }
+                WindowEvent::MouseInput {
+                    state: winit::event::ElementState::Pressed,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } if renderer.status_language_hit(mouse_position.0, mouse_position.1) => {
+                    // Clicking the language in the status bar opens the language picker
+                    command_palette.open(language_picker_items(&language_registry));
+                }
+                WindowEvent::MouseInput {
+                    state: winit::event::ElementState::Pressed,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } => {
+                    // Clicking a find option toggles it, and clicking a find field puts the caret there
+                    if let Some(option) = renderer.find_option_at(mouse_position.0, mouse_position.1) {
+                        search_state.toggle_option(option, &tab_manager.active_tab().buffer);
+                    } else if let Some((is_replacement, position)) = renderer.find_field_at(mouse_position.0, mouse_position.1) {
+                        search_state.editing_replacement = is_replacement;
+                        search_state.focused_input().move_to(position, modifiers.shift_key());
+                    // Clicking a fold marker in the gutter folds or unfolds its range
+                    } else if let Some(line) = renderer.gutter_line_at(mouse_position.0, mouse_position.1) {
+                        let active_tab = tab_manager.active_tab_mut();
+                        if active_tab.folds.is_foldable(line) || active_tab.folds.is_folded(line) {
+                            active_tab.folds.toggle_at(line);
+                            active_tab.skip_folds(active_tab.cursor.position.line);
+                        }
+                    } else if let Some((line, column)) = renderer.outline_target_at(mouse_position.0, mouse_position.1) {
+                        // Clicking a symbol in the outline jumps to it
+                        tab_manager.active_tab_mut().go_to(line, column);
+                        renderer.scroll_to_line(tab_manager.active_tab(), line);
+                    } else if let Some(row) = renderer.file_tree_row_at(mouse_position.0, mouse_position.1) {
+                        // Pressing on a file tree row selects it, and starts dragging it
+                        file_tree.selected_index = row;
+                        dragged_file = file_tree.selected_entry().map(Path::to_path_buf);
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: winit::event::ElementState::Released,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } => {
+                    // Dropping a dragged entry on a row moves it into that row's directory
+                    let Some(from) = dragged_file.take() else {
+                        return;
+                    };
+                    if let (Some(row), Some(name)) = (renderer.file_tree_row_at(mouse_position.0, mouse_position.1), from.file_name()) {
+                        file_tree.selected_index = row;
+                        let to = file_tree.selected_dir().join(name);
+                        if !to.starts_with(&from) {
+                            run_file_action(FileAction::Move { from, to }, &mut file_ops, &mut file_tree, &mut tab_manager, &language_registry);
+                        }
+                    }
+                }
                 WindowEvent::MouseWheel { delta, .. } => {
                     // Handle scrolling
                     use winit::event::MouseScrollDelta;
@@ -139,16 +321,24 @@ fn main() -> Result<()> {
                         // Update cursor blink (assume 16ms frame time)
                         active_tab.cursor.update_blink(0.016);
 
+                        // Re-highlight the tab if its buffer changed
+                        active_tab.refresh_syntax();
+
+                        // Take in background search results and follow edits
+                        if search_state.is_active {
+                            search_state.poll(&active_tab.buffer);
+                        }
+                        project_search.poll();
+                        file_tree.poll();
+                        if file_list.poll() {
+                            command_palette.set_loaded(file_items(file_list.files(), &file_tree.workspace));
+                        }
+
                         // Check if buffer has been modified
                         if active_tab.buffer.version() != last_buffer_version {
                             active_tab.is_modified = true;
                             last_buffer_version = active_tab.buffer.version();
 
-                            // Re-highlight syntax
-                            if let Some(ref mut highlighter) = syntax_highlighter {
-                                syntax_tokens = highlighter.highlight(&active_tab.buffer.text());
-                            }
-
                             // Update window title to show modification status
                             let title = format!(
                                 "Nexus - {} ({}/{})",
@@ -161,16 +351,13 @@ fn main() -> Result<()> {
                     } // Drop mutable borrow here
 
                     // Now borrow immutably for rendering
-                    let active_tab = tab_manager.active_tab();
                     match renderer.render(
-                        &active_tab.buffer,
-                        &active_tab.cursor,
-                        &syntax_tokens,
-                        active_tab.file_path.as_ref().and_then(|p| p.file_name()?.to_str()),
-                        active_tab.is_modified,
-                        &file_tree,
                         &tab_manager,
+                        &file_tree,
+                        &outline_panel,
+                        &project_search,
                         &search_state,
+                        &command_palette,
                     ) {
                         Ok(_) => {}
                         Err(e) => {
@@ -181,26 +368,572 @@ fn main() -> Result<()> {
                 WindowEvent::KeyboardInput { event, .. } => {
                     use winit::event::ElementState;
 
+                    // Command palette captures all keys while open
+                    if command_palette.is_active {
+                        if event.state == ElementState::Pressed {
+                            if let winit::keyboard::PhysicalKey::Code(key_code) = event.physical_key {
+                                use winit::keyboard::KeyCode;
+                                match key_code {
+                                    KeyCode::Escape => command_palette.close(),
+                                    KeyCode::ArrowUp => command_palette.move_selection_up(),
+                                    KeyCode::ArrowDown => command_palette.move_selection_down(),
+                                    _ if command_palette.handle_key(key_code, modifiers, &mut input_handler) => {}
+                                    KeyCode::Enter => {
+                                        let command = command_palette.accept();
+                                        match command {
+                                            Some(PaletteCommand::SetTheme(name)) if theme_manager.set_active(&name) => {
+                                                renderer.set_theme(theme_manager.active().clone());
+                                                config.ui.theme = name;
+                                                if let Err(e) = config.save() {
+                                                    log::error!("❌ Failed to save config: {}", e);
+                                                }
+                                            }
+                                            Some(PaletteCommand::ReloadThemes) => {
+                                                theme_manager.reload();
+                                                renderer.set_theme(theme_manager.active().clone());
+                                            }
+                                            Some(PaletteCommand::ChangeLanguage) => {
+                                                command_palette.open(language_picker_items(&language_registry));
+                                            }
+                                            Some(PaletteCommand::SetLanguage(name)) => {
+                                                let language = name.and_then(|name| language_registry.get(&name));
+                                                let active_tab = tab_manager.active_tab_mut();
+                                                active_tab.set_language(language, &language_registry);
+                                                log::info!("🎨 Language: {}", active_tab.language_name());
+                                            }
+                                            Some(command @ (PaletteCommand::Fold
+                                            | PaletteCommand::Unfold
+                                            | PaletteCommand::FoldAll
+                                            | PaletteCommand::UnfoldAll
+                                            | PaletteCommand::FoldLevel(_))) => {
+                                                run_fold_command(tab_manager.active_tab_mut(), &command);
+                                            }
+                                            Some(PaletteCommand::ExpandSelection) => {
+                                                tab_manager.active_tab_mut().expand_selection();
+                                            }
+                                            Some(PaletteCommand::ShrinkSelection) => {
+                                                tab_manager.active_tab_mut().shrink_selection();
+                                            }
+                                            Some(PaletteCommand::SelectTextObject(capture)) => {
+                                                tab_manager.active_tab_mut().select_text_object(&capture);
+                                            }
+                                            Some(PaletteCommand::ToggleOutline) => outline_panel.toggle_visibility(),
+                                            Some(PaletteCommand::GoToSymbolInFile) => {
+                                                command_palette.open(symbol_picker_items(tab_manager.active_tab()));
+                                            }
+                                            Some(PaletteCommand::GoToPosition(line, column)) => {
+                                                tab_manager.active_tab_mut().go_to(line, column);
+                                                renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                            }
+                                            Some(PaletteCommand::GoToMatchingBracket)
+                                                if tab_manager.active_tab_mut().jump_to_matching_bracket() =>
+                                            {
+                                                let line = tab_manager.active_tab().cursor.position.line;
+                                                renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                            }
+                                            Some(PaletteCommand::Reindent) => tab_manager.active_tab_mut().reindent(),
+                                            Some(PaletteCommand::Line(command)) => {
+                                                let tab = tab_manager.active_tab_mut();
+                                                line_ops::run(command, &mut tab.buffer, &mut tab.cursor);
+                                            }
+                                            Some(PaletteCommand::ToggleComment) => tab_manager.active_tab_mut().toggle_comment(),
+                                            Some(PaletteCommand::ToggleBlockComment) => {
+                                                tab_manager.active_tab_mut().toggle_block_comment()
+                                            }
+                                            Some(PaletteCommand::SetIndentStyle(style)) => {
+                                                tab_manager.active_tab_mut().indent_style = style;
+                                                log::info!("📏 Indentation: {}", style);
+                                            }
+                                            Some(PaletteCommand::ToggleRainbowBrackets) => {
+                                                config.editor.rainbow_brackets = !config.editor.rainbow_brackets;
+                                                renderer.rainbow_brackets = config.editor.rainbow_brackets;
+                                                if let Err(e) = config.save() {
+                                                    log::error!("❌ Failed to save config: {}", e);
+                                                }
+                                            }
+                                            Some(PaletteCommand::FindInFiles(replace)) => project_search.open(replace),
+                                            Some(PaletteCommand::GoToSymbolInWorkspace) => {
+                                                if !symbol_index.is_ready() {
+                                                    log::info!("🔎 Workspace is still being indexed");
+                                                }
+                                                command_palette.open(location_items(&symbol_index.all_symbols(), &file_tree.workspace));
+                                            }
+                                            Some(PaletteCommand::GoToFile) => {
+                                                command_palette.open_loading(file_items(file_list.files(), &file_tree.workspace));
+                                                file_list.refresh(&file_tree.workspace);
+                                            }
+                                            Some(PaletteCommand::SetSearchScope(scope)) => {
+                                                if !search_state.is_active {
+                                                    search_state.activate(None, &tab_manager.active_tab().buffer);
+                                                }
+                                                let visible = renderer.visible_lines();
+                                                set_search_scope(&mut search_state, tab_manager.active_tab_mut(), scope, visible);
+                                            }
+                                            Some(PaletteCommand::ToggleHiddenFiles) => {
+                                                file_tree.set_show_hidden(!file_tree.show_hidden);
+                                            }
+                                            Some(PaletteCommand::FilterFileTree) => {
+                                                file_tree.is_visible = true;
+                                                file_tree.open_filter();
+                                            }
+                                            Some(PaletteCommand::ExpandFileTree(depth)) => {
+                                                file_tree.is_visible = true;
+                                                file_tree.expand_to_depth(depth);
+                                            }
+                                            Some(PaletteCommand::SortFileTree(sort_mode)) => {
+                                                file_tree.is_visible = true;
+                                                file_tree.set_sort_mode(sort_mode);
+                                            }
+                                            Some(PaletteCommand::RevealActiveFile) => match tab_manager.active_tab().file_path.clone() {
+                                                Some(path) => {
+                                                    file_tree.is_visible = true;
+                                                    if !file_tree.reveal(&path) {
+                                                        log::info!("🌳 {} is not in the file tree", path.display());
+                                                    }
+                                                }
+                                                None => log::info!("🌳 The active tab has no file"),
+                                            },
+                                            Some(PaletteCommand::CopyActiveFilePath(relative)) => {
+                                                if let Some(path) = &tab_manager.active_tab().file_path {
+                                                    input_handler.set_clipboard_text(&path_text(path, &file_tree.workspace, relative));
+                                                }
+                                            }
+                                            Some(PaletteCommand::FindWordUnderCursor) => {
+                                                find_word_under_cursor(&mut search_state, tab_manager.active_tab_mut());
+                                                renderer.scroll_to_line(tab_manager.active_tab(), tab_manager.active_tab().cursor.position.line);
+                                            }
+                                            Some(PaletteCommand::OpenLocation(path, line, column)) => {
+                                                match open_location(&mut tab_manager, &language_registry, &path, line, column) {
+                                                    Ok(()) => {
+                                                        last_buffer_version = tab_manager.active_tab().buffer.version();
+                                                        renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                                    }
+                                                    Err(e) => log::error!("❌ Failed to open {}: {}", path.display(), e),
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        return;
+                    }
+
+                    // The project search panel captures keys while focused
+                    if project_search.is_focused {
+                        if event.state == ElementState::Pressed {
+                            if let winit::keyboard::PhysicalKey::Code(key_code) = event.physical_key {
+                                use winit::keyboard::KeyCode;
+                                let selecting = project_search.selected.is_some();
+                                match key_code {
+                                    KeyCode::Escape => project_search.close(),
+                                    KeyCode::Tab => project_search.next_field(),
+                                    KeyCode::ArrowUp | KeyCode::ArrowDown if !selecting && project_search.recall(
+                                        &mut search_state.query_history,
+                                        &mut search_state.replace_history,
+                                        key_code == KeyCode::ArrowUp,
+                                    ) => {}
+                                    KeyCode::ArrowUp => project_search.move_selection(-1),
+                                    KeyCode::ArrowDown => project_search.move_selection(1),
+                                    KeyCode::ArrowLeft if selecting => project_search.set_collapsed(true),
+                                    KeyCode::ArrowRight if selecting => project_search.set_collapsed(false),
+                                    KeyCode::Space if selecting => project_search.toggle_selected(),
+                                    _ if !selecting && project_search.handle_key(key_code, modifiers, &mut input_handler) => {}
+                                    KeyCode::KeyC | KeyCode::KeyW | KeyCode::KeyR | KeyCode::KeyP if modifiers.alt_key() => {
+                                        // Alt+C/W/R/P - Toggle case sensitivity, whole words, regex, preserve case
+                                        let option = match key_code {
+                                            KeyCode::KeyC => &mut project_search.case_sensitive,
+                                            KeyCode::KeyW => &mut project_search.whole_word,
+                                            KeyCode::KeyR => &mut project_search.use_regex,
+                                            _ => &mut project_search.preserve_case,
+                                        };
+                                        *option = !*option;
+                                        project_search.start(&file_tree.workspace.roots, open_buffers(&tab_manager));
+                                    }
+                                    KeyCode::Enter if modifiers.alt_key() && project_search.is_replace_mode => {
+                                        // Alt+Enter - Replace the included matches, one undo step per file
+                                        let files = replace_in_files(&project_search, &mut tab_manager, &language_registry);
+                                        log::info!("🔄 Replaced in {} files", files);
+                                        last_buffer_version = tab_manager.active_tab().buffer.version();
+                                        project_search.start(&file_tree.workspace.roots, open_buffers(&tab_manager));
+                                    }
+                                    KeyCode::Enter if selecting => {
+                                        // Enter - Open the selected result and go back to the editor
+                                        if let Some((path, line, column)) = project_search.selected_location() {
+                                            match open_location(&mut tab_manager, &language_registry, &path, line, column) {
+                                                Ok(()) => {
+                                                    last_buffer_version = tab_manager.active_tab().buffer.version();
+                                                    renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                                    project_search.is_focused = false;
+                                                }
+                                                Err(e) => log::error!("❌ Failed to open {}: {}", path.display(), e),
+                                            }
+                                        }
+                                    }
+                                    KeyCode::Enter => {
+                                        search_state.query_history.push(project_search.query.text());
+                                        if project_search.is_replace_mode {
+                                            search_state.replace_history.push(project_search.replace_text.text());
+                                        }
+                                        project_search.start(&file_tree.workspace.roots, open_buffers(&tab_manager));
+                                        log::info!("🔎 Searching workspace for {:?}", project_search.query.text());
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        return;
+                    }
+
+                    // The file tree takes file operation keys while visible, and
+                    // every key while a name is typed in it
+                    if file_tree.is_visible && event.state == ElementState::Pressed {
+                        if let winit::keyboard::PhysicalKey::Code(key_code) = event.physical_key {
+                            use winit::keyboard::KeyCode;
+                            let ctrl = modifiers.control_key();
+                            if let Some(prompt) = &mut file_tree.prompt {
+                                match key_code {
+                                    KeyCode::Escape => file_tree.prompt = None,
+                                    KeyCode::Enter => {
+                                        // Enter - Create or rename; a new file opens in a tab
+                                        let Some(prompt) = file_tree.prompt.take() else {
+                                            return;
+                                        };
+                                        let Some(action) = prompt.action() else {
+                                            return;
+                                        };
+                                        match run_file_action(action, &mut file_ops, &mut file_tree, &mut tab_manager, &language_registry) {
+                                            Some(FileOp::Create { path, is_dir: false }) => {
+                                                match open_location(&mut tab_manager, &language_registry, &path, 0, 0) {
+                                                    Ok(()) => last_buffer_version = tab_manager.active_tab().buffer.version(),
+                                                    Err(e) => log::error!("❌ Failed to open {}: {}", path.display(), e),
+                                                }
+                                            }
+                                            Some(_) => {}
+                                            // Keep the name to correct it
+                                            None => file_tree.prompt = Some(prompt),
+                                        }
+                                    }
+                                    _ => {
+                                        prompt.input.handle_key(key_code, modifiers, &mut input_handler);
+                                    }
+                                }
+                                return;
+                            }
+                            if let Some(filter) = &mut file_tree.filter {
+                                match key_code {
+                                    KeyCode::Escape => {
+                                        file_tree.close_filter();
+                                        return;
+                                    }
+                                    // Up / Down / Enter go through the matches and open them
+                                    KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::Enter => {}
+                                    _ => match filter.input.handle_key(key_code, modifiers, &mut input_handler) {
+                                        InputResponse::Edited => {
+                                            file_tree.filter_changed();
+                                            return;
+                                        }
+                                        InputResponse::Handled => return,
+                                        InputResponse::Ignored => {}
+                                    },
+                                }
+                            }
+
+                            let selected = file_tree.selected_entry().map(Path::to_path_buf);
+                            match key_code {
+                                KeyCode::KeyF if ctrl && !modifiers.shift_key() => {
+                                    // Ctrl+F - Filter the tree by name
+                                    file_tree.open_filter();
+                                    return;
+                                }
+                                KeyCode::KeyN if ctrl => {
+                                    // Ctrl+N / Ctrl+Shift+N - New file / folder in the selected directory
+                                    let kind = if modifiers.shift_key() { PromptKind::NewFolder } else { PromptKind::NewFile };
+                                    file_tree.start_prompt(kind);
+                                    return;
+                                }
+                                KeyCode::F2 => {
+                                    // F2 - Rename
+                                    if let Some(path) = selected {
+                                        file_tree.start_prompt(PromptKind::Rename(path));
+                                    }
+                                    return;
+                                }
+                                KeyCode::Delete if !ctrl => {
+                                    // Delete - Move to the trash
+                                    if let Some(path) = selected {
+                                        run_file_action(FileAction::Trash(path), &mut file_ops, &mut file_tree, &mut tab_manager, &language_registry);
+                                    }
+                                    return;
+                                }
+                                KeyCode::KeyD if ctrl && !modifiers.shift_key() => {
+                                    // Ctrl+D - Duplicate next to the original
+                                    if let Some(from) = selected {
+                                        let action = FileAction::Copy { to: file_ops::copy_name(&from), from };
+                                        run_file_action(action, &mut file_ops, &mut file_tree, &mut tab_manager, &language_registry);
+                                    }
+                                    return;
+                                }
+                                KeyCode::KeyC if ctrl && modifiers.alt_key() => {
+                                    // Ctrl+Alt+C / Ctrl+Alt+Shift+C - Copy the absolute / relative path
+                                    if let Some(path) = selected {
+                                        input_handler.set_clipboard_text(&path_text(&path, &file_tree.workspace, modifiers.shift_key()));
+                                    }
+                                    return;
+                                }
+                                KeyCode::KeyX | KeyCode::KeyC if ctrl => {
+                                    // Ctrl+X / Ctrl+C - Cut / copy, to paste in another directory
+                                    file_tree.clipboard = selected.map(|path| (path, key_code == KeyCode::KeyX));
+                                    return;
+                                }
+                                KeyCode::KeyV if ctrl => {
+                                    // Ctrl+V - Paste into the selected directory
+                                    if let Some(action) = file_tree.paste() {
+                                        run_file_action(action, &mut file_ops, &mut file_tree, &mut tab_manager, &language_registry);
+                                    }
+                                    return;
+                                }
+                                KeyCode::KeyZ | KeyCode::KeyY if ctrl => {
+                                    // Ctrl+Z - Undo, Ctrl+Y / Ctrl+Shift+Z - Redo the last file operation
+                                    let redo = key_code == KeyCode::KeyY || modifiers.shift_key();
+                                    let result = if redo { file_ops.redo() } else { file_ops.undo() };
+                                    match result {
+                                        Ok(Some(op)) => follow_file_op(&op, &mut file_tree, &mut tab_manager, &language_registry),
+                                        Ok(None) if redo => log::info!("↷ No file operation to redo"),
+                                        Ok(None) => log::info!("↶ No file operation to undo"),
+                                        Err(e) => log::error!("❌ {:#}", e),
+                                    }
+                                    return;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+
                     // Check for Ctrl+S (save)
                     if event.state == ElementState::Pressed {
                         if let winit::keyboard::PhysicalKey::Code(key_code) = event.physical_key {
                             use winit::keyboard::KeyCode;
 
                             // Detect Ctrl modifier
-                            let is_ctrl_s = event.logical_key == winit::keyboard::Key::Character("s".into());
-                            let is_ctrl_o = event.logical_key == winit::keyboard::Key::Character("o".into());
-                            let is_ctrl_c = event.logical_key == winit::keyboard::Key::Character("c".into());
-                            let is_ctrl_v = event.logical_key == winit::keyboard::Key::Character("v".into());
-                            let is_ctrl_x = event.logical_key == winit::keyboard::Key::Character("x".into());
-                            let is_ctrl_f = event.logical_key == winit::keyboard::Key::Character("f".into());
-                            let is_ctrl_h = event.logical_key == winit::keyboard::Key::Character("h".into());
-                            let is_ctrl_w = event.logical_key == winit::keyboard::Key::Character("w".into());
-                            let is_ctrl_t = event.logical_key == winit::keyboard::Key::Character("t".into());
-                            let is_ctrl_b = event.logical_key == winit::keyboard::Key::Character("b".into());
-                            let is_ctrl_z = event.logical_key == winit::keyboard::Key::Character("z".into());
-                            let is_ctrl_y = event.logical_key == winit::keyboard::Key::Character("y".into());
+                            let ctrl = modifiers.control_key();
+                            let is_ctrl_s = ctrl && event.logical_key == winit::keyboard::Key::Character("s".into());
+                            let is_ctrl_o = ctrl && event.logical_key == winit::keyboard::Key::Character("o".into());
+                            let is_ctrl_c = ctrl && event.logical_key == winit::keyboard::Key::Character("c".into());
+                            let is_ctrl_v = ctrl && event.logical_key == winit::keyboard::Key::Character("v".into());
+                            let is_ctrl_x = ctrl && event.logical_key == winit::keyboard::Key::Character("x".into());
+                            let is_ctrl_f = ctrl && event.logical_key == winit::keyboard::Key::Character("f".into());
+                            let is_ctrl_h = ctrl && event.logical_key == winit::keyboard::Key::Character("h".into());
+                            let is_ctrl_w = ctrl && event.logical_key == winit::keyboard::Key::Character("w".into());
+                            let is_ctrl_t = ctrl && event.logical_key == winit::keyboard::Key::Character("t".into());
+                            let is_ctrl_b = ctrl && event.logical_key == winit::keyboard::Key::Character("b".into());
+                            let is_ctrl_z = ctrl && event.logical_key == winit::keyboard::Key::Character("z".into());
+                            let is_ctrl_y = ctrl && event.logical_key == winit::keyboard::Key::Character("y".into());
+                            let is_ctrl_shift_p = ctrl && modifiers.shift_key() && key_code == KeyCode::KeyP;
+                            let is_ctrl_shift_o = ctrl && modifiers.shift_key() && key_code == KeyCode::KeyO;
 
                             match key_code {
+                                _ if search_state.is_active
+                                    && search_state.handle_key(key_code, modifiers, &mut input_handler, &tab_manager.active_tab().buffer) =>
+                                {
+                                    // Editing and clipboard keys go to the find bar while it is open
+                                    return;
+                                }
+                                KeyCode::KeyP if ctrl && !modifiers.shift_key() && !modifiers.alt_key() => {
+                                    // Ctrl+P - Go to a file of the workspace, listed again in the background
+                                    command_palette.open_loading(file_items(file_list.files(), &file_tree.workspace));
+                                    file_list.refresh(&file_tree.workspace);
+                                    return;
+                                }
+                                KeyCode::KeyP if is_ctrl_shift_p => {
+                                    // Ctrl+Shift+P - Command palette
+                                    let mut items: Vec<PaletteItem> = theme_manager
+                                        .names()
+                                        .map(|name| PaletteItem::new(
+                                            format!("Theme: {}", name),
+                                            PaletteCommand::SetTheme(name.to_string()),
+                                        ))
+                                        .collect();
+                                    items.push(PaletteItem::new("Theme: Reload Themes", PaletteCommand::ReloadThemes));
+                                    items.push(PaletteItem::new("Change Language Mode", PaletteCommand::ChangeLanguage));
+                                    items.push(PaletteItem::new("Fold", PaletteCommand::Fold));
+                                    items.push(PaletteItem::new("Unfold", PaletteCommand::Unfold));
+                                    items.push(PaletteItem::new("Fold All", PaletteCommand::FoldAll));
+                                    items.push(PaletteItem::new("Unfold All", PaletteCommand::UnfoldAll));
+                                    items.extend((1..=5).map(|level| {
+                                        PaletteItem::new(format!("Fold Level {}", level), PaletteCommand::FoldLevel(level))
+                                    }));
+                                    items.push(PaletteItem::new("Toggle Outline", PaletteCommand::ToggleOutline));
+                                    items.push(PaletteItem::new("Go to Symbol in File", PaletteCommand::GoToSymbolInFile));
+                                    items.push(PaletteItem::new("Go to Symbol in Workspace", PaletteCommand::GoToSymbolInWorkspace));
+                                    items.push(PaletteItem::new("Go to File", PaletteCommand::GoToFile));
+                                    items.push(PaletteItem::new("Toggle Hidden Files", PaletteCommand::ToggleHiddenFiles));
+                                    items.push(PaletteItem::new("File Tree: Filter", PaletteCommand::FilterFileTree));
+                                    items.push(PaletteItem::new("File Tree: Collapse All", PaletteCommand::ExpandFileTree(0)));
+                                    items.extend((1..=3).map(|depth| {
+                                        PaletteItem::new(format!("File Tree: Expand to Depth {}", depth), PaletteCommand::ExpandFileTree(depth))
+                                    }));
+                                    items.extend(SortMode::ALL.iter().map(|(label, sort_mode)| {
+                                        PaletteItem::new(*label, PaletteCommand::SortFileTree(*sort_mode))
+                                    }));
+                                    items.push(PaletteItem::new("Reveal Active File in File Tree", PaletteCommand::RevealActiveFile));
+                                    items.push(PaletteItem::new("Copy Path of Active File", PaletteCommand::CopyActiveFilePath(false)));
+                                    items.push(PaletteItem::new("Copy Relative Path of Active File", PaletteCommand::CopyActiveFilePath(true)));
+                                    items.push(PaletteItem::new("Find Word Under Cursor", PaletteCommand::FindWordUnderCursor));
+                                    items.push(PaletteItem::new("Find in Files", PaletteCommand::FindInFiles(false)));
+                                    items.push(PaletteItem::new("Replace in Files", PaletteCommand::FindInFiles(true)));
+                                    items.push(PaletteItem::new("Reindent Lines", PaletteCommand::Reindent));
+                                    items.extend(LineCommand::ALL.iter().map(|(label, command)| {
+                                        PaletteItem::new(*label, PaletteCommand::Line(*command))
+                                    }));
+                                    items.push(PaletteItem::new("Toggle Line Comment", PaletteCommand::ToggleComment));
+                                    items.push(PaletteItem::new("Toggle Block Comment", PaletteCommand::ToggleBlockComment));
+                                    items.push(PaletteItem::new("Indent Using Tabs", PaletteCommand::SetIndentStyle(IndentStyle::Tabs)));
+                                    items.extend([2, 4, 8].map(|width| {
+                                        PaletteItem::new(
+                                            format!("Indent Using {} Spaces", width),
+                                            PaletteCommand::SetIndentStyle(IndentStyle::Spaces(width)),
+                                        )
+                                    }));
+                                    items.push(PaletteItem::new("Go to Matching Bracket", PaletteCommand::GoToMatchingBracket));
+                                    items.push(PaletteItem::new("Toggle Rainbow Brackets", PaletteCommand::ToggleRainbowBrackets));
+                                    items.extend(SearchScope::ALL.iter().map(|(label, scope)| {
+                                        PaletteItem::new(*label, PaletteCommand::SetSearchScope(*scope))
+                                    }));
+                                    items.push(PaletteItem::new("Expand Selection", PaletteCommand::ExpandSelection));
+                                    items.push(PaletteItem::new("Shrink Selection", PaletteCommand::ShrinkSelection));
+                                    items.extend(selection::TEXT_OBJECTS.iter().map(|(label, capture)| {
+                                        PaletteItem::new(*label, PaletteCommand::SelectTextObject(capture.to_string()))
+                                    }));
+                                    command_palette.open_commands(items);
+                                    log::info!("🎛️  Command palette opened");
+                                    return;
+                                }
+                                KeyCode::BracketLeft if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+[ - Fold
+                                    run_fold_command(tab_manager.active_tab_mut(), &PaletteCommand::Fold);
+                                    return;
+                                }
+                                KeyCode::BracketRight if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+] - Unfold
+                                    run_fold_command(tab_manager.active_tab_mut(), &PaletteCommand::Unfold);
+                                    return;
+                                }
+                                KeyCode::KeyF | KeyCode::KeyH if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+F / Ctrl+Shift+H - Find / replace in files
+                                    project_search.open(key_code == KeyCode::KeyH);
+                                    return;
+                                }
+                                KeyCode::KeyO if is_ctrl_shift_o => {
+                                    // Ctrl+Shift+O - Go to symbol in file
+                                    command_palette.open(symbol_picker_items(tab_manager.active_tab()));
+                                    return;
+                                }
+                                KeyCode::Backslash if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+\ - Jump to the matching bracket
+                                    if tab_manager.active_tab_mut().jump_to_matching_bracket() {
+                                        let line = tab_manager.active_tab().cursor.position.line;
+                                        renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                    }
+                                    return;
+                                }
+                                KeyCode::Slash if ctrl => {
+                                    // Ctrl+/ - Toggle line comment
+                                    tab_manager.active_tab_mut().toggle_comment();
+                                    return;
+                                }
+                                KeyCode::KeyA if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+A - Toggle block comment
+                                    tab_manager.active_tab_mut().toggle_block_comment();
+                                    return;
+                                }
+                                KeyCode::ArrowUp | KeyCode::ArrowDown if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+Up/Down - Move the selected lines
+                                    let command = match key_code {
+                                        KeyCode::ArrowUp => LineCommand::MoveUp,
+                                        _ => LineCommand::MoveDown,
+                                    };
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(command, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::KeyD if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+D - Duplicate the line or selection
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(LineCommand::Duplicate, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::KeyK if ctrl && modifiers.shift_key() => {
+                                    // Ctrl+Shift+K - Delete the selected lines
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(LineCommand::Delete, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::KeyJ if ctrl => {
+                                    // Ctrl+J - Join lines
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(LineCommand::Join, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::Enter if ctrl && modifiers.shift_key() && !search_state.is_active => {
+                                    // Ctrl+Shift+Enter - Insert a line above
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(LineCommand::InsertAbove, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::Enter if ctrl && !search_state.is_active => {
+                                    // Ctrl+Enter - Insert a line below
+                                    let tab = tab_manager.active_tab_mut();
+                                    line_ops::run(LineCommand::InsertBelow, &mut tab.buffer, &mut tab.cursor);
+                                    return;
+                                }
+                                KeyCode::ArrowLeft | KeyCode::ArrowRight if ctrl => {
+                                    // Ctrl+Left/Right - Move by word, Shift extends the selection
+                                    let motion = match key_code {
+                                        KeyCode::ArrowLeft => Motion::WordLeft,
+                                        _ => Motion::WordRight,
+                                    };
+                                    let tab = tab_manager.active_tab_mut();
+                                    motion::move_cursor(motion, &tab.buffer, &mut tab.cursor, &word_rules, modifiers.shift_key());
+                                    return;
+                                }
+                                KeyCode::Backspace | KeyCode::Delete if ctrl && !search_state.is_active => {
+                                    // Ctrl+Backspace/Delete - Delete the word before/after the cursor
+                                    let motion = match key_code {
+                                        KeyCode::Backspace => Motion::WordLeft,
+                                        _ => Motion::WordRight,
+                                    };
+                                    let tab = tab_manager.active_tab_mut();
+                                    motion::delete(motion, &mut tab.buffer, &mut tab.cursor, &word_rules);
+                                    return;
+                                }
+                                KeyCode::Home | KeyCode::End if !file_tree.is_visible => {
+                                    // Home toggles between the first non-whitespace character and column 0,
+                                    // Ctrl+Home/End go to the start/end of the document
+                                    let motion = match (key_code, ctrl) {
+                                        (KeyCode::Home, false) => Motion::LineStart,
+                                        (KeyCode::Home, true) => Motion::DocumentStart,
+                                        (_, false) => Motion::LineEnd,
+                                        (_, true) => Motion::DocumentEnd,
+                                    };
+                                    let tab = tab_manager.active_tab_mut();
+                                    motion::move_cursor(motion, &tab.buffer, &mut tab.cursor, &word_rules, modifiers.shift_key());
+                                    let line = tab_manager.active_tab().cursor.position.line;
+                                    renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                    return;
+                                }
+                                KeyCode::ArrowUp if modifiers.alt_key() => {
+                                    // Alt+Up - Expand selection to the enclosing syntax node
+                                    tab_manager.active_tab_mut().expand_selection();
+                                    return;
+                                }
+                                KeyCode::ArrowDown if modifiers.alt_key() => {
+                                    // Alt+Down - Shrink selection
+                                    tab_manager.active_tab_mut().shrink_selection();
+                                    return;
+                                }
                                 KeyCode::KeyS if is_ctrl_s => {
                                     // Ctrl+S - Save file
                                     let active_tab = tab_manager.active_tab_mut();
@@ -282,13 +1015,21 @@ fn main() -> Result<()> {
                                 }
                                 KeyCode::KeyW if is_ctrl_w => {
                                     // Ctrl+W - Close active tab
+                                    let closing = tab_manager.active_tab().id;
+                                    if tab_manager.tab_count() == 1 && waits.is_waiting_for(closing) {
+                                        // The last tab can't close, so put an empty one next to it
+                                        tab_manager.add_tab(Tab::new("Untitled".to_string()));
+                                        tab_manager.switch_to_tab(0);
+                                    }
                                     if tab_manager.close_active_tab() {
                                         log::info!("📑 Tab closed");
-                                        // Re-highlight the new active tab
-                                        if let Some(ref mut highlighter) = syntax_highlighter {
-                                            syntax_tokens = highlighter.highlight(&tab_manager.active_tab().buffer.text());
-                                        }
                                         last_buffer_version = tab_manager.active_tab().buffer.version();
+                                        if waits.tab_closed(closing) {
+                                            // Started with --wait, and its tabs are closed
+                                            log::info!("👋 Closing Nexus");
+                                            save_histories(history_path.as_deref(), &search_state, &command_palette);
+                                            control_flow.exit();
+                                        }
                                     }
                                     return;
                                 }
@@ -296,10 +1037,6 @@ fn main() -> Result<()> {
                                     // Ctrl+T - New tab
                                     tab_manager.add_tab(Tab::new("Untitled".to_string()));
                                     log::info!("📑 New tab created");
-                                    // Re-highlight the new tab
-                                    if let Some(ref mut highlighter) = syntax_highlighter {
-                                        syntax_tokens = highlighter.highlight(&tab_manager.active_tab().buffer.text());
-                                    }
                                     last_buffer_version = tab_manager.active_tab().buffer.version();
                                     return;
                                 }
@@ -308,7 +1045,10 @@ fn main() -> Result<()> {
                                     file_tree.toggle_visibility();
                                     if file_tree.is_visible {
                                         log::info!("🌳 File tree opened");
-                                        log::info!("📁 Current directory: {}", file_tree.root_path.display());
+                                        log::info!("📁 Workspace: {}", file_tree.workspace.name());
+                                        for root in &file_tree.workspace.roots {
+                                            log::info!("   {}", root.path.display());
+                                        }
                                         log::info!("   {} files/folders found", file_tree.nodes.len());
                                         if let Some(node) = file_tree.get_selected_node() {
                                             log::info!("   Selected: {}", node.name);
@@ -321,7 +1061,9 @@ fn main() -> Result<()> {
                                 KeyCode::KeyF if is_ctrl_f => {
                                     // Ctrl+F - Open find dialog
                                     if !search_state.is_active {
-                                        search_state.activate();
+                                        let active_tab = tab_manager.active_tab();
+                                        search_state.activate(active_tab.search_prefill(), &active_tab.buffer);
+                                        lock_search_to_selection(&mut search_state, tab_manager.active_tab_mut());
                                         log::info!("🔍 Find dialog opened");
                                     }
                                     return;
@@ -329,7 +1071,9 @@ fn main() -> Result<()> {
                                 KeyCode::KeyH if is_ctrl_h => {
                                     // Ctrl+H - Open find/replace dialog
                                     if !search_state.is_active {
-                                        search_state.activate();
+                                        let active_tab = tab_manager.active_tab();
+                                        search_state.activate(active_tab.search_prefill(), &active_tab.buffer);
+                                        lock_search_to_selection(&mut search_state, tab_manager.active_tab_mut());
                                     }
                                     search_state.is_replace_mode = true;
                                     log::info!("🔄 Find/Replace dialog opened");
@@ -343,6 +1087,12 @@ fn main() -> Result<()> {
                                         return;
                                     }
                                 }
+                                KeyCode::F3 if ctrl => {
+                                    // Ctrl+F3 - Find the next occurrence of the word under the cursor
+                                    find_word_under_cursor(&mut search_state, tab_manager.active_tab_mut());
+                                    renderer.scroll_to_line(tab_manager.active_tab(), tab_manager.active_tab().cursor.position.line);
+                                    return;
+                                }
                                 KeyCode::F3 => {
                                     // F3 - Find next
                                     if search_state.is_active && !search_state.matches.is_empty() {
@@ -351,6 +1101,7 @@ fn main() -> Result<()> {
                                             let active_tab = tab_manager.active_tab_mut();
                                             active_tab.cursor.position.line = match_item.start_line;
                                             active_tab.cursor.position.column = match_item.start_column;
+                                            active_tab.folds.reveal(match_item.start_line);
                                             log::info!("🔍 Found match {}/{}",
                                                 search_state.current_match_index.unwrap_or(0) + 1,
                                                 search_state.match_count());
@@ -358,6 +1109,41 @@ fn main() -> Result<()> {
                                         return;
                                     }
                                 }
+                                KeyCode::F12 => {
+                                    // F12 - Go to definition of the word under the cursor
+                                    let active_tab = tab_manager.active_tab();
+                                    let Some(word) = active_tab.word_at_cursor() else {
+                                        return;
+                                    };
+
+                                    // Prefer the open document, the index only sees saved files
+                                    let local = symbols::flatten(&active_tab.symbols)
+                                        .into_iter()
+                                        .find(|(_, symbol)| symbol.name == word && symbol.kind != SymbolKind::Impl)
+                                        .map(|(_, symbol)| (symbol.line, symbol.column));
+                                    if let Some((line, column)) = local {
+                                        tab_manager.active_tab_mut().go_to(line, column);
+                                        renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                        return;
+                                    }
+
+                                    let definitions = symbol_index.definitions(&word);
+                                    match definitions.as_slice() {
+                                        [] => log::info!("🔎 No definition found for {}", word),
+                                        [definition] => {
+                                            let (line, column) = (definition.line, definition.column);
+                                            match open_location(&mut tab_manager, &language_registry, &definition.path, line, column) {
+                                                Ok(()) => {
+                                                    last_buffer_version = tab_manager.active_tab().buffer.version();
+                                                    renderer.scroll_to_line(tab_manager.active_tab(), line);
+                                                }
+                                                Err(e) => log::error!("❌ Failed to open {}: {}", definition.path.display(), e),
+                                            }
+                                        }
+                                        _ => command_palette.open(location_items(&definitions, &file_tree.workspace)),
+                                    }
+                                    return;
+                                }
                                 KeyCode::PageUp => {
                                     // Page Up - Scroll up one page
                                     renderer.scroll_offset -= 400.0; // ~22 lines
@@ -403,9 +1189,8 @@ fn main() -> Result<()> {
                                         return;
                                     }
                                     KeyCode::Enter => {
-                                        if let Err(e) = file_tree.toggle_selected_expand() {
-                                            log::error!("❌ Failed to expand: {}", e);
-                                        } else if let Some(node) = file_tree.get_selected_node() {
+                                        file_tree.toggle_selected_expand();
+                                        if let Some(node) = file_tree.get_selected_node() {
                                             if node.is_dir {
                                                 log::info!("📁 Toggled: {}", node.name);
                                             } else {
@@ -413,27 +1198,10 @@ fn main() -> Result<()> {
                                                 log::info!("📂 Opening file: {}", node.path.display());
                                                 match std::fs::read_to_string(&node.path) {
                                                     Ok(content) => {
-                                                        // Create new tab with file content
-                                                        let tab = Tab::from_file(node.path.clone(), content);
+                                                        // Create new tab with file content; its language is detected from the path
+                                                        let tab = Tab::from_file(node.path.clone(), content, &language_registry);
+                                                        log::info!("✅ Opened: {} ({})", node.name, tab.language_name());
                                                         tab_manager.add_tab(tab);
-                                                        log::info!("✅ Opened: {}", node.name);
-
-                                                        // Detect file type and create syntax highlighter
-                                                        if let Some(ext) = node.path.extension() {
-                                                            if let Some(ext_str) = ext.to_str() {
-                                                                syntax_highlighter = SyntaxHighlighter::new(ext_str).ok();
-                                                                if syntax_highlighter.is_some() {
-                                                                    log::info!("🎨 Syntax highlighting: {}", ext_str);
-                                                                }
-                                                            }
-                                                        }
-
-                                                        // Highlight the new tab
-                                                        syntax_tokens = if let Some(ref mut highlighter) = syntax_highlighter {
-                                                            highlighter.highlight(&tab_manager.active_tab().buffer.text())
-                                                        } else {
-                                                            Vec::new()
-                                                        };
 
                                                         // Update buffer version
                                                         last_buffer_version = tab_manager.active_tab().buffer.version();
@@ -459,7 +1227,32 @@ fn main() -> Result<()> {
 
                     let active_tab = tab_manager.active_tab_mut();
                     if !search_state.is_active {
-                        input_handler.handle_key_event(&event, &mut active_tab.buffer, &mut active_tab.cursor);
+                        let previous_line = active_tab.cursor.position.line;
+                        // Keys that depend on the language: pairs and indentation
+                        let handled = event.state == ElementState::Pressed
+                            && match event.physical_key {
+                                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Backspace) => {
+                                    // Backspace inside an empty pair deletes both halves
+                                    active_tab.delete_pair()
+                                }
+                                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Enter) => {
+                                    active_tab.newline();
+                                    true
+                                }
+                                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Tab) => {
+                                    if modifiers.shift_key() {
+                                        active_tab.outdent();
+                                    } else {
+                                        active_tab.indent();
+                                    }
+                                    true
+                                }
+                                _ => false,
+                            };
+                        if !handled {
+                            input_handler.handle_key_event(&event, &mut active_tab.buffer, &mut active_tab.cursor);
+                        }
+                        active_tab.skip_folds(previous_line);
                         log::debug!("Key event: {:?}", event);
                     } else {
                         // Handle search input (backspace, etc.)
@@ -467,19 +1260,59 @@ fn main() -> Result<()> {
                             if let winit::keyboard::PhysicalKey::Code(key_code) = event.physical_key {
                                 use winit::keyboard::KeyCode;
                                 match key_code {
-                                    KeyCode::Backspace => {
-                                        if !search_state.query.is_empty() {
-                                            search_state.query.pop();
-                                            search_state.find_all_matches(&active_tab.buffer);
+                                    KeyCode::KeyC | KeyCode::KeyW | KeyCode::KeyR | KeyCode::KeyP if modifiers.alt_key() => {
+                                        // Alt+C/W/R/P - Toggle case sensitivity, whole words, regex, preserve case
+                                        let option = match key_code {
+                                            KeyCode::KeyC => SearchOption::CaseSensitive,
+                                            KeyCode::KeyW => SearchOption::WholeWord,
+                                            KeyCode::KeyR => SearchOption::Regex,
+                                            _ => SearchOption::PreserveCase,
+                                        };
+                                        search_state.toggle_option(option, &active_tab.buffer);
+                                    }
+                                    KeyCode::KeyL if modifiers.alt_key() => {
+                                        // Alt+L - Toggle find in selection
+                                        let scope = if search_state.scope == SearchScope::Selection {
+                                            SearchScope::Document
+                                        } else {
+                                            SearchScope::Selection
+                                        };
+                                        set_search_scope(&mut search_state, active_tab, scope, 0..0);
+                                    }
+                                    KeyCode::Tab if search_state.is_replace_mode => {
+                                        // Tab - Switch between the query and the replacement
+                                        search_state.editing_replacement = !search_state.editing_replacement;
+                                    }
+                                    KeyCode::ArrowUp | KeyCode::ArrowDown => {
+                                        // Up / Down - Older / newer queries or replacements
+                                        search_state.recall(key_code == KeyCode::ArrowUp, &active_tab.buffer);
+                                    }
+                                    KeyCode::Enter if search_state.editing_replacement && modifiers.alt_key() => {
+                                        // Alt+Enter - Replace all
+                                        search_state.remember();
+                                        let count = search_state.replace_all(&mut active_tab.buffer);
+                                        log::info!("🔄 Replaced {} matches", count);
+                                    }
+                                    KeyCode::Enter if search_state.editing_replacement => {
+                                        // Enter - Replace the current match and go to the next
+                                        search_state.remember();
+                                        if search_state.replace_current(&mut active_tab.buffer, &mut active_tab.cursor) {
+                                            if let Some(match_item) = search_state.current_match() {
+                                                active_tab.cursor.position.line = match_item.start_line;
+                                                active_tab.cursor.position.column = match_item.start_column;
+                                                active_tab.folds.reveal(match_item.start_line);
+                                            }
                                         }
                                     }
                                     KeyCode::Enter => {
                                         // Enter - Find next
+                                        search_state.remember();
                                         if !search_state.matches.is_empty() {
                                             search_state.find_next();
                                             if let Some(match_item) = search_state.current_match() {
                                                 active_tab.cursor.position.line = match_item.start_line;
                                                 active_tab.cursor.position.column = match_item.start_column;
+                                                active_tab.folds.reveal(match_item.start_line);
                                             }
                                         }
                                     }
@@ -494,20 +1327,47 @@ fn main() -> Result<()> {
                     use winit::event::Ime;
                     if let Ime::Commit(text) = ime_event {
                         let active_tab = tab_manager.active_tab_mut();
-                        if search_state.is_active {
-                            // Update search query
-                            search_state.query.push_str(&text);
-                            search_state.find_all_matches(&active_tab.buffer);
-                            log::debug!("Search query: {:?} ({} matches)", search_state.query, search_state.match_count());
+                        if command_palette.is_active {
+                            command_palette.insert(&text);
+                        } else if project_search.is_focused {
+                            // Letters with Alt toggle options, and a selected result takes Space
+                            if !modifiers.alt_key() && project_search.selected.is_none() {
+                                project_search.insert(&text);
+                            }
+                        } else if let Some(prompt) = file_tree.prompt.as_mut().filter(|_| file_tree.is_visible) {
+                            prompt.input.insert(&text);
+                        } else if let Some(filter) = file_tree.filter.as_mut().filter(|_| file_tree.is_visible) {
+                            filter.input.insert(&text);
+                            file_tree.filter_changed();
+                        } else if search_state.is_active && modifiers.alt_key() {
+                            // Alt+letter toggles a search option
+                        } else if search_state.is_active {
+                            // Update the search query or replacement
+                            search_state.type_text(&text, &active_tab.buffer);
+                            log::debug!("Search query: {:?} ({} matches)", search_state.query.text(), search_state.match_count());
+                        } else if file_tree.is_visible {
+                            // Typing a name jumps to the entry
+                            file_tree.type_ahead(&text, Instant::now());
                         } else {
-                            // Normal text input
-                            input_handler.handle_text_input(&text, &mut active_tab.buffer, &mut active_tab.cursor);
+                            // Normal text input; brackets and quotes may close, wrap or type over
+                            if !active_tab.type_pair(&text) {
+                                input_handler.handle_text_input(&text, &mut active_tab.buffer, &mut active_tab.cursor);
+                            }
                             log::debug!("Text input: {:?}", text);
                         }
                     }
                 }
                 _ => {}
             },
+            Event::UserEvent(RemoteOpen { request, caller }) => {
+                // Another nexus process handed over files to open
+                let opened = open_request(&request, &mut tab_manager, &language_registry);
+                let tab = tab_manager.active_tab();
+                renderer.scroll_to_line(tab, tab.cursor.position.line);
+                last_buffer_version = tab.buffer.version();
+                waits.add(opened, caller);
+                window.focus_window();
+            }
             Event::AboutToWait => {
                 window.request_redraw();
             }
@@ -517,3 +1377,284 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Palette items for picking the active tab's language
+fn language_picker_items(languages: &LanguageRegistry) -> Vec<PaletteItem> {
+    let mut items = vec![PaletteItem::new("Plain Text", PaletteCommand::SetLanguage(None))];
+    items.extend(languages.languages().iter().map(|language| {
+        PaletteItem::new(
+            format!("{} ({})", language.display_name(), language.name()),
+            PaletteCommand::SetLanguage(Some(language.name().to_string())),
+        )
+    }));
+    items
+}
+
+/// Palette items for jumping to a symbol of the tab, indented by nesting
+fn symbol_picker_items(tab: &Tab) -> Vec<PaletteItem> {
+    symbols::flatten(&tab.symbols)
+        .into_iter()
+        .map(|(depth, symbol)| {
+            PaletteItem::new(
+                format!("{}{}", "  ".repeat(depth), symbol.label()),
+                PaletteCommand::GoToPosition(symbol.line, symbol.column),
+            )
+        })
+        .collect()
+}
+
+/// Palette items for jumping to workspace symbols, labelled with their
+/// location relative to the workspace root
+fn location_items(symbols: &[IndexedSymbol], workspace: &Workspace) -> Vec<PaletteItem> {
+    symbols
+        .iter()
+        .map(|symbol| {
+            PaletteItem::new(
+                format!("{} — {}:{}", symbol.label(), workspace.relative(&symbol.path).display(), symbol.line + 1),
+                PaletteCommand::OpenLocation(symbol.path.clone(), symbol.line, symbol.column),
+            )
+        })
+        .collect()
+}
+
+/// Palette items for opening the listed files of the workspace
+fn file_items(files: &[PathBuf], workspace: &Workspace) -> Vec<PaletteItem> {
+    files
+        .iter()
+        .map(|path| PaletteItem::new(workspace.relative(path).display().to_string(), PaletteCommand::OpenLocation(path.clone(), 0, 0)))
+        .collect()
+}
+
+/// Open the files, stdin text and diff of a command line request in tabs,
+/// leaving the last one active. Returns the ids of the tabs.
+fn open_request(request: &OpenRequest, tab_manager: &mut TabManager, languages: &Arc<LanguageRegistry>) -> Vec<usize> {
+    let mut opened = Vec::new();
+    for file in &request.files {
+        let index = if file.path.exists() {
+            match tab_for_path(tab_manager, languages, &file.path) {
+                Ok(index) => index,
+                Err(e) => {
+                    log::error!("❌ Failed to open {}: {}", file.path.display(), e);
+                    continue;
+                }
+            }
+        } else {
+            // A new file, created when saved
+            tab_manager.add_tab(Tab::from_file(file.path.clone(), String::new(), languages));
+            tab_manager.tab_count() - 1
+        };
+        tab_manager.switch_to_tab(index);
+        let tab = tab_manager.active_tab_mut();
+        if let Some(line) = file.line {
+            tab.go_to(line.saturating_sub(1), file.column.unwrap_or(1).saturating_sub(1));
+        }
+        opened.push(tab.id);
+    }
+
+    if let Some(text) = &request.stdin {
+        // Loaded text is where undo stops, not an edit of its own
+        let mut tab = Tab::new("Untitled".to_string());
+        tab.buffer = Buffer::from_text(text);
+        tab.set_language(languages.detect(None, text.lines().next().unwrap_or("")), languages);
+        opened.push(tab.id);
+        tab_manager.add_tab(tab);
+    }
+
+    if let Some((a, b)) = &request.diff {
+        match diff_tab(a, b) {
+            Ok(tab) => {
+                opened.push(tab.id);
+                tab_manager.add_tab(tab);
+            }
+            Err(e) => log::error!("❌ Failed to compare files: {}", e),
+        }
+    }
+    opened
+}
+
+/// A tab showing the differences between two files as a unified diff
+fn diff_tab(a: &Path, b: &Path) -> Result<Tab> {
+    let old = std::fs::read_to_string(a).with_context(|| format!("Failed to read {}", a.display()))?;
+    let new = std::fs::read_to_string(b).with_context(|| format!("Failed to read {}", b.display()))?;
+    let name = |path: &Path| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+
+    let diff = similar::TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(&a.display().to_string(), &b.display().to_string())
+        .to_string();
+    let mut tab = Tab::new(format!("{} ↔ {}", name(a), name(b)));
+    tab.buffer = Buffer::from_text(if diff.is_empty() { "Files are identical\n" } else { &diff });
+    Ok(tab)
+}
+
+/// Switch to the tab showing `path`, opening the file if there is none,
+/// and move its cursor to a line and column
+fn open_location(tab_manager: &mut TabManager, languages: &Arc<LanguageRegistry>, path: &Path, line: usize, column: usize) -> Result<()> {
+    let index = tab_for_path(tab_manager, languages, path)?;
+    tab_manager.switch_to_tab(index);
+    tab_manager.active_tab_mut().go_to(line, column);
+    Ok(())
+}
+
+/// Index of the tab showing `path`, opening the file in a new tab if there
+/// is none; the active tab stays the same
+fn tab_for_path(tab_manager: &mut TabManager, languages: &Arc<LanguageRegistry>, path: &Path) -> Result<usize> {
+    if let Some(index) = tab_manager.tabs().iter().position(|tab| tab.file_path.as_deref() == Some(path)) {
+        return Ok(index);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let active = tab_manager.active_index();
+    tab_manager.add_tab(Tab::from_file(path.to_path_buf(), content, languages));
+    tab_manager.switch_to_tab(active);
+    Ok(tab_manager.tab_count() - 1)
+}
+
+/// Text of the tabs showing files, which project search reads instead of the disk
+fn open_buffers(tab_manager: &TabManager) -> HashMap<PathBuf, ropey::Rope> {
+    tab_manager
+        .tabs()
+        .iter()
+        .filter_map(|tab| Some((tab.file_path.clone()?, tab.buffer.rope().clone())))
+        .collect()
+}
+
+/// Apply the included replacements of the project search, each file as one
+/// undo step in its tab. Files are opened in tabs if they aren't, and left
+/// unsaved for review; files that changed since the search are skipped.
+/// Returns how many files were changed.
+fn replace_in_files(project_search: &ProjectSearchPanel, tab_manager: &mut TabManager, languages: &Arc<LanguageRegistry>) -> usize {
+    let mut changed = 0;
+    for file in project_search.results.iter().filter(|file| file.matches.iter().any(|m| m.included)) {
+        let index = match tab_for_path(tab_manager, languages, &file.path) {
+            Ok(index) => index,
+            Err(e) => {
+                log::error!("❌ Failed to open {}: {}", file.path.display(), e);
+                continue;
+            }
+        };
+        let tab = &mut tab_manager.tabs_mut()[index];
+        match project_search.replacements(file, tab.buffer.rope()) {
+            Some(replacements) => {
+                // Replace from end to beginning to maintain offsets
+                tab.buffer.transaction(|buffer| {
+                    for (range, replacement) in replacements.iter().rev() {
+                        search::replace_range(buffer, range.clone(), replacement);
+                    }
+                });
+                tab.is_modified = true;
+                changed += 1;
+            }
+            None => log::warn!("⚠️  {} changed since the search, skipped", file.path.display()),
+        }
+    }
+    changed
+}
+
+/// Limit the search to part of the tab, keeping the scope it had when
+/// there is no selection or function for the new one
+fn set_search_scope(search_state: &mut SearchState, tab: &mut Tab, scope: SearchScope, visible: std::ops::Range<usize>) {
+    match tab.search_scope_range(scope, visible) {
+        Some(range) => search_state.set_scope(scope, Some(range), &tab.buffer),
+        None if scope == SearchScope::Document => search_state.set_scope(scope, None, &tab.buffer),
+        None => log::info!("🔍 Nothing to search in for {:?} scope", scope),
+    }
+}
+
+/// Search only the selection when find opens with several lines selected
+fn lock_search_to_selection(search_state: &mut SearchState, tab: &mut Tab) {
+    if tab.cursor.selection.as_ref().is_some_and(|s| s.start.line != s.end.line) {
+        set_search_scope(search_state, tab, SearchScope::Selection, 0..0);
+    }
+}
+
+/// Open find with the word under the cursor and go to its next occurrence
+fn find_word_under_cursor(search_state: &mut SearchState, tab: &mut Tab) {
+    let Some(word) = tab.word_at_cursor() else {
+        return;
+    };
+    search_state.activate(Some(word), &tab.buffer);
+    let position = tab.cursor.position;
+    search_state.find_after(tab.buffer.position_to_offset(position.line, position.column));
+    if let Some(match_item) = search_state.current_match() {
+        tab.cursor.position.line = match_item.start_line;
+        tab.cursor.position.column = match_item.start_column;
+        tab.cursor.selection = None;
+        tab.folds.reveal(match_item.start_line);
+    }
+}
+
+/// Run a file action from the file tree, with the tree and the tabs
+/// following it. Failures are logged and leave nothing to undo.
+fn run_file_action(
+    action: FileAction,
+    file_ops: &mut FileOps,
+    file_tree: &mut FileTree,
+    tab_manager: &mut TabManager,
+    languages: &Arc<LanguageRegistry>,
+) -> Option<FileOp> {
+    match file_ops.run(action) {
+        Ok(op) => {
+            follow_file_op(&op, file_tree, tab_manager, languages);
+            Some(op)
+        }
+        Err(e) => {
+            log::error!("❌ {:#}", e);
+            None
+        }
+    }
+}
+
+/// Bring the file tree and the tabs in line with a file operation: tabs
+/// of moved files take their new paths, and the tree shows the result
+fn follow_file_op(op: &FileOp, file_tree: &mut FileTree, tab_manager: &mut TabManager, languages: &Arc<LanguageRegistry>) {
+    log::info!("🗂️  {:?}", op);
+    if let FileOp::Move { from, to } = op {
+        tab_manager.retarget(from, to, languages);
+        file_tree.moved(from, to);
+    }
+    let paths: Vec<PathBuf> = op.paths().into_iter().map(Path::to_path_buf).collect();
+    file_tree.apply_changes(&paths);
+    if let Some(path) = op.result() {
+        file_tree.reveal(path);
+    }
+}
+
+/// Save the search, replace and command histories for the next session
+fn save_histories(path: Option<&Path>, search_state: &SearchState, command_palette: &CommandPalette) {
+    let Some(path) = path else { return };
+    let saved = SavedHistories {
+        search: search_state.query_history.clone(),
+        replace: search_state.replace_history.clone(),
+        commands: command_palette.history.clone(),
+    };
+    if let Err(e) = saved.save(path) {
+        log::error!("❌ Failed to save history: {}", e);
+    }
+}
+
+/// A path to copy, relative to the workspace root with `relative`
+fn path_text(path: &Path, workspace: &Workspace, relative: bool) -> String {
+    if relative {
+        workspace.relative(path).display().to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Run a fold command on the tab, keeping the cursor out of folded text
+fn run_fold_command(tab: &mut Tab, command: &PaletteCommand) {
+    let line = tab.cursor.position.line;
+    match command {
+        PaletteCommand::Fold => {
+            tab.folds.fold_at(line);
+        }
+        PaletteCommand::Unfold => {
+            tab.folds.unfold_at(line);
+        }
+        PaletteCommand::FoldAll => tab.folds.fold_all(),
+        PaletteCommand::UnfoldAll => tab.folds.unfold_all(),
+        PaletteCommand::FoldLevel(level) => tab.folds.fold_level(*level),
+        _ => return,
+    }
+    tab.skip_folds(line);
+}
@@ -0,0 +1,232 @@
+use crate::buffer::Buffer;
+use crate::cursor::{Cursor, Position};
+
+/// Punctuation that separates words by default
+pub const DEFAULT_WORD_SEPARATORS: &str = "`~!@#$%^&*()-=+[{]}\\|;:'\",.<>/?";
+
+/// What counts as a word for word motion and deletion
+#[derive(Debug, Clone)]
+pub struct WordRules {
+    /// Characters forming words of their own, apart from letters, digits
+    /// and other characters
+    pub separators: String,
+    /// Also stop inside words at camelCase humps and after underscores
+    pub subwords: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Separator,
+    Word,
+}
+
+impl WordRules {
+    pub fn new(separators: &str, subwords: bool) -> Self {
+        Self { separators: separators.to_string(), subwords }
+    }
+
+    fn class(&self, c: char) -> CharClass {
+        if c.is_whitespace() {
+            CharClass::Whitespace
+        } else if self.separators.contains(c) {
+            CharClass::Separator
+        } else {
+            CharClass::Word
+        }
+    }
+
+    /// True when `chars[i]` starts a subword: `bar` in `foo_bar`, `Bar` in
+    /// `fooBar` and `Server` in `HTTPServer`
+    fn subword_start(&self, chars: &[char], i: usize) -> bool {
+        let (previous, c) = (chars[i - 1], chars[i]);
+        self.subwords
+            && (previous == '_' && c != '_'
+                || c.is_uppercase() && (previous.is_lowercase() || previous.is_ascii_digit())
+                || c.is_uppercase() && previous.is_uppercase() && chars.get(i + 1).is_some_and(|n| n.is_lowercase()))
+    }
+
+    /// True when `chars[i - 1]` and `chars[i]` belong to the same word of `class`
+    fn continues(&self, chars: &[char], i: usize, class: CharClass) -> bool {
+        self.class(chars[i - 1]) == class
+            && self.class(chars[i]) == class
+            && !(class == CharClass::Word && self.subword_start(chars, i))
+    }
+
+    /// Column of the next word end right of `column`, `None` at the end of the line
+    pub fn next_stop(&self, chars: &[char], column: usize) -> Option<usize> {
+        if column >= chars.len() {
+            return None;
+        }
+        let mut i = column;
+        while i < chars.len() && self.class(chars[i]) == CharClass::Whitespace {
+            i += 1;
+        }
+        if i == chars.len() {
+            return Some(i);
+        }
+        let class = self.class(chars[i]);
+        i += 1;
+        while i < chars.len() && self.continues(chars, i, class) {
+            i += 1;
+        }
+        Some(i)
+    }
+
+    /// Column of the previous word start left of `column`, `None` at the start of the line
+    pub fn previous_stop(&self, chars: &[char], column: usize) -> Option<usize> {
+        let mut i = column.min(chars.len());
+        if i == 0 {
+            return None;
+        }
+        while i > 0 && self.class(chars[i - 1]) == CharClass::Whitespace {
+            i -= 1;
+        }
+        if i == 0 {
+            return Some(0);
+        }
+        let class = self.class(chars[i - 1]);
+        i -= 1;
+        while i > 0 && self.continues(chars, i, class) {
+            i -= 1;
+        }
+        Some(i)
+    }
+}
+
+impl Default for WordRules {
+    fn default() -> Self {
+        Self::new(DEFAULT_WORD_SEPARATORS, false)
+    }
+}
+
+/// Cursor movements beyond single characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    WordLeft,
+    WordRight,
+    /// The first non-whitespace character, or column 0 when already there
+    LineStart,
+    LineEnd,
+    DocumentStart,
+    DocumentEnd,
+}
+
+/// Where `motion` takes the cursor from `position`. Word motions continue
+/// on the neighbouring line at the start or end of a line.
+pub fn target(motion: Motion, buffer: &Buffer, rules: &WordRules, position: Position) -> Position {
+    let chars = |line: usize| -> Vec<char> { buffer.line(line).trim_end_matches('\n').chars().collect() };
+    let last_line = buffer.line_count().saturating_sub(1);
+    match motion {
+        Motion::WordLeft => match rules.previous_stop(&chars(position.line), position.column) {
+            Some(column) => Position::new(position.line, column),
+            None if position.line > 0 => Position::new(position.line - 1, buffer.line_len(position.line - 1)),
+            None => position,
+        },
+        Motion::WordRight => match rules.next_stop(&chars(position.line), position.column) {
+            Some(column) => Position::new(position.line, column),
+            None if position.line < last_line => Position::new(position.line + 1, 0),
+            None => position,
+        },
+        Motion::LineStart => {
+            let first = chars(position.line).iter().take_while(|c| c.is_whitespace()).count();
+            Position::new(position.line, if position.column == first { 0 } else { first })
+        }
+        Motion::LineEnd => Position::new(position.line, buffer.line_len(position.line)),
+        Motion::DocumentStart => Position::zero(),
+        Motion::DocumentEnd => Position::new(last_line, buffer.line_len(last_line)),
+    }
+}
+
+/// Move the cursor, extending the selection from where it started with
+/// `extend` (Shift held) and dropping it otherwise
+pub fn move_cursor(motion: Motion, buffer: &Buffer, cursor: &mut Cursor, rules: &WordRules, extend: bool) {
+    let position = target(motion, buffer, rules, cursor.position);
+    if extend {
+        let anchor = cursor.selection.as_ref().map_or(cursor.position, |s| s.start);
+        cursor.selection = Some(anchor..position);
+    } else {
+        cursor.clear_selection();
+    }
+    cursor.move_to(position);
+}
+
+/// Delete from the cursor to where `motion` takes it, or the selection if
+/// there is one, as a single undo step
+pub fn delete(motion: Motion, buffer: &mut Buffer, cursor: &mut Cursor, rules: &WordRules) {
+    let (start, end) = match cursor.selection.as_ref() {
+        Some(s) if s.start != s.end => (s.start.min(s.end), s.start.max(s.end)),
+        _ => {
+            let target = target(motion, buffer, rules, cursor.position);
+            (cursor.position.min(target), cursor.position.max(target))
+        }
+    };
+    let range = buffer.position_to_offset(start.line, start.column)..buffer.position_to_offset(end.line, end.column);
+    if !range.is_empty() {
+        buffer.transaction(|buffer| buffer.delete_range(range));
+    }
+    cursor.clear_selection();
+    cursor.move_to(start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(rules: &WordRules, text: &str) -> (Vec<usize>, Vec<usize>) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut right = Vec::new();
+        let mut column = 0;
+        while let Some(next) = rules.next_stop(&chars, column) {
+            right.push(next);
+            column = next;
+        }
+        let mut left = Vec::new();
+        while let Some(previous) = rules.previous_stop(&chars, column) {
+            left.push(previous);
+            column = previous;
+        }
+        (right, left)
+    }
+
+    #[test]
+    fn test_word_stops() {
+        let rules = WordRules::default();
+        let (right, left) = stops(&rules, "let fooBar = a.b_c;  ");
+        assert_eq!(right, [3, 10, 12, 14, 15, 18, 19, 21]);
+        assert_eq!(left, [18, 15, 14, 13, 11, 4, 0]);
+
+        let subwords = WordRules::new(DEFAULT_WORD_SEPARATORS, true);
+        let (right, left) = stops(&subwords, "fooBar HTTPServer snake_case");
+        assert_eq!(right, [3, 6, 11, 17, 24, 28]);
+        assert_eq!(left, [24, 18, 11, 7, 3, 0]);
+    }
+
+    #[test]
+    fn test_motions() {
+        let mut buffer = Buffer::from_text("    fn f() {}\nlet x");
+        let rules = WordRules::default();
+        let mut cursor = Cursor::new();
+        cursor.position = Position::new(0, 9);
+
+        move_cursor(Motion::LineStart, &buffer, &mut cursor, &rules, true);
+        assert_eq!(cursor.position, Position::new(0, 4));
+        move_cursor(Motion::LineStart, &buffer, &mut cursor, &rules, true);
+        assert_eq!(cursor.selection, Some(Position::new(0, 9)..Position::new(0, 0)));
+
+        move_cursor(Motion::DocumentEnd, &buffer, &mut cursor, &rules, false);
+        assert_eq!((cursor.position, cursor.selection.clone()), (Position::new(1, 5), None));
+        move_cursor(Motion::WordLeft, &buffer, &mut cursor, &rules, false);
+        move_cursor(Motion::WordLeft, &buffer, &mut cursor, &rules, false);
+        move_cursor(Motion::WordLeft, &buffer, &mut cursor, &rules, false);
+        assert_eq!(cursor.position, Position::new(0, 13));
+
+        // Deleting a word back from the end of `f()` takes the separators only
+        cursor.position = Position::new(0, 10);
+        delete(Motion::WordLeft, &mut buffer, &mut cursor, &rules);
+        assert_eq!(buffer.line(0), "    fn f {}\n");
+        assert_eq!(cursor.position, Position::new(0, 8));
+        delete(Motion::WordRight, &mut buffer, &mut cursor, &rules);
+        assert_eq!(buffer.line(0), "    fn f\n");
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::Result;
+use ignore::overrides::OverrideBuilder;
+use ignore::{WalkBuilder, WalkState};
+use regex::Regex;
+use ropey::Rope;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+
+use crate::search::{self, Pattern};
+use crate::workspace::WorkspaceRoot;
+
+/// Matches found at most across the workspace
+pub const MAX_MATCHES: usize = 10_000;
+
+/// Larger files are skipped
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Lines shown around each match
+const CONTEXT_LINES: usize = 1;
+
+/// A file containing a NUL byte in its first this many bytes is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// One match in a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMatch {
+    pub line: usize,
+    /// Byte column of the match within `line`
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// Byte range of the match in the file
+    pub range: Range<usize>,
+    /// The lines the match is on, for showing it and previewing a replacement
+    pub text: String,
+    /// Lines around the match, with their numbers
+    pub before: Vec<(usize, String)>,
+    pub after: Vec<(usize, String)>,
+    /// Replace this match when replacing across files
+    pub included: bool,
+}
+
+impl FileMatch {
+    /// The matched text
+    pub fn matched(&self) -> &str {
+        &self.text[self.column..self.column + self.range.len()]
+    }
+
+    /// `text` with the match replaced as `regex` and the template say
+    pub fn preview(&self, regex: &Regex, template: &str, use_regex: bool, preserve_case: bool) -> Option<String> {
+        let replacement = search::expand_replacement(regex, &self.text, self.column, template, use_regex, preserve_case)?;
+        let mut preview = self.text.clone();
+        preview.replace_range(self.column..self.column + self.range.len(), &replacement);
+        Some(preview)
+    }
+}
+
+/// The matches in one file, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileResults {
+    pub path: PathBuf,
+    pub matches: Vec<FileMatch>,
+    /// Show only the file's row in the results panel
+    pub collapsed: bool,
+}
+
+/// What to search the workspace for
+pub struct ProjectQuery {
+    /// Folders to search, each leaving out its excluded files
+    pub roots: Vec<WorkspaceRoot>,
+    pub pattern: Pattern,
+    /// Globs a file must match one of, if any, e.g. `*.rs` or `src/**`
+    pub include: Vec<String>,
+    /// Globs of files to leave out
+    pub exclude: Vec<String>,
+    /// Contents of files open in tabs, searched instead of the file on disk
+    pub open: HashMap<PathBuf, Rope>,
+}
+
+/// A workspace search on a pool of background threads. Files are walked in
+/// parallel, skipping what `.gitignore` and the exclude globs leave out and
+/// binary files, and their results arrive as each is searched. Dropping it
+/// cancels the search.
+pub struct ProjectSearch {
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<FileResults>,
+    found: Arc<AtomicUsize>,
+}
+
+impl ProjectSearch {
+    pub fn spawn(query: ProjectQuery) -> Result<Self> {
+        let mut walkers = Vec::new();
+        for root in &query.roots {
+            let mut overrides = OverrideBuilder::new(&root.path);
+            for glob in &query.include {
+                overrides.add(glob)?;
+            }
+            for glob in query.exclude.iter().chain(&root.exclude) {
+                overrides.add(&format!("!{}", glob))?;
+            }
+            let walker = WalkBuilder::new(&root.path)
+                .overrides(overrides.build()?)
+                .hidden(!root.show_hidden)
+                .require_git(false)
+                .build_parallel();
+            walkers.push(walker);
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let found = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let (thread_cancel, thread_found) = (cancel.clone(), found.clone());
+        let query = Arc::new(query);
+        let spawned = std::thread::Builder::new().name("project-search".to_string()).spawn(move || {
+            for walker in walkers {
+                walker.run(|| {
+                    let (sender, cancel, found, query) = (sender.clone(), thread_cancel.clone(), thread_found.clone(), query.clone());
+                    Box::new(move |entry| {
+                        if cancel.load(Ordering::Relaxed) || found.load(Ordering::Relaxed) >= MAX_MATCHES {
+                            return WalkState::Quit;
+                        }
+                        let Ok(entry) = entry else {
+                            return WalkState::Continue;
+                        };
+                        if !entry.file_type().is_some_and(|t| t.is_file()) {
+                            return WalkState::Continue;
+                        }
+
+                        let path = entry.into_path();
+                        let Some(rope) = query.open.get(&path).cloned().or_else(|| read_text(&path)) else {
+                            return WalkState::Continue;
+                        };
+                        let matches = search_rope(&rope, &query.pattern, &cancel);
+                        if matches.is_empty() {
+                            return WalkState::Continue;
+                        }
+                        found.fetch_add(matches.len(), Ordering::Relaxed);
+                        // Nobody receives once the search was dropped
+                        match sender.send(FileResults { path, matches, collapsed: false }) {
+                            Ok(()) => WalkState::Continue,
+                            Err(_) => WalkState::Quit,
+                        }
+                    })
+                });
+            }
+        });
+        if let Err(e) = spawned {
+            log::error!("❌ Failed to start project search: {}", e);
+        }
+
+        Ok(Self { cancel, receiver, found })
+    }
+
+    /// Files searched since the last call, and whether the search is over
+    pub fn poll(&self) -> (Vec<FileResults>, bool) {
+        let mut files = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(file) => files.push(file),
+                Err(TryRecvError::Empty) => return (files, false),
+                Err(TryRecvError::Disconnected) => return (files, true),
+            }
+        }
+    }
+
+    /// True when the search stopped at `MAX_MATCHES`
+    pub fn truncated(&self) -> bool {
+        self.found.load(Ordering::Relaxed) >= MAX_MATCHES
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A file's text, or `None` for large, binary and non-UTF-8 files
+fn read_text(path: &Path) -> Option<Rope> {
+    if std::fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    if is_binary(&bytes) {
+        return None;
+    }
+    String::from_utf8(bytes).ok().map(|text| Rope::from_str(&text))
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Matches in a file, with their lines and context
+fn search_rope(rope: &Rope, pattern: &Pattern, cancel: &AtomicBool) -> Vec<FileMatch> {
+    let line_text = |line: usize| rope.line(line).to_string().trim_end_matches(['\n', '\r']).to_string();
+    let context = |lines: Range<usize>| lines.map(|line| (line, line_text(line))).collect::<Vec<_>>();
+    let last_line = rope.len_lines().saturating_sub(1);
+
+    let mut matches = Vec::new();
+    search::search_lines(rope, pattern, 0..rope.len_lines(), Some(cancel), |found| {
+        let (text, _) = search::lines_text(rope, found.start_line..found.end_line + 1);
+        matches.push(FileMatch {
+            line: found.start_line,
+            column: found.start_column,
+            end_line: found.end_line,
+            end_column: found.end_column,
+            range: found.start_offset..found.end_offset,
+            text,
+            before: context(found.start_line.saturating_sub(CONTEXT_LINES)..found.start_line),
+            after: context(found.end_line + 1..(found.end_line + 1 + CONTEXT_LINES).min(last_line + 1)),
+            included: true,
+        });
+        matches.len() < MAX_MATCHES
+    });
+    matches
+}
+
+/// Byte ranges to replace in a file and what with, for its included
+/// matches. `None` if the text no longer has the matched text at a match,
+/// as when the file changed since it was searched.
+pub fn file_replacements(
+    file: &FileResults,
+    text: &Rope,
+    regex: &Regex,
+    template: &str,
+    use_regex: bool,
+    preserve_case: bool,
+) -> Option<Vec<(Range<usize>, String)>> {
+    let mut replacements = Vec::new();
+    for found in file.matches.iter().filter(|m| m.included) {
+        // Out of range or off char boundaries also means the file changed
+        if text.get_byte_slice(found.range.clone())? != found.matched() {
+            return None;
+        }
+        let replacement = search::expand_replacement(regex, &found.text, found.column, template, use_regex, preserve_case)?;
+        replacements.push((found.range.clone(), replacement));
+    }
+    Some(replacements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_search() {
+        let root = std::env::temp_dir().join("nexus_test_project_search");
+        let _ = std::fs::remove_dir_all(&root);
+        let write = |path: &str, content: &[u8]| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(".gitignore", b"target/\n");
+        write("src/main.rs", b"fn main() {\n    let todo = 1;\n}\n");
+        write("src/lib.rs", b"// TODO: tests\n");
+        write("notes.md", b"todo\n");
+        write("target/out.rs", b"todo\n");
+        write("image.bin", b"todo\0\0");
+
+        let search = |include: &[&str], exclude: &[&str], open: HashMap<PathBuf, Rope>| {
+            let regex = search::build_regex("todo", false, false).unwrap();
+            let job = ProjectSearch::spawn(ProjectQuery {
+                roots: vec![WorkspaceRoot::new(root.clone())],
+                pattern: Pattern { regex, whole_word: false },
+                include: include.iter().map(|s| s.to_string()).collect(),
+                exclude: exclude.iter().map(|s| s.to_string()).collect(),
+                open,
+            })
+            .unwrap();
+            let mut files = Vec::new();
+            loop {
+                let (found, done) = job.poll();
+                files.extend(found);
+                if done {
+                    break;
+                }
+                std::thread::yield_now();
+            }
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            files
+        };
+        let names = |files: &[FileResults]| -> Vec<String> {
+            files.iter().map(|f| f.path.strip_prefix(&root).unwrap().display().to_string()).collect()
+        };
+
+        // Ignored and binary files are skipped
+        let files = search(&[], &[], HashMap::new());
+        assert_eq!(names(&files), ["notes.md", "src/lib.rs", "src/main.rs"]);
+        let found = &files[2].matches[0];
+        assert_eq!((found.line, found.column, found.matched()), (1, 8, "todo"));
+        assert_eq!(found.before, [(0, "fn main() {".to_string())]);
+        assert_eq!(found.after, [(2, "}".to_string())]);
+
+        assert_eq!(names(&search(&["*.rs"], &["lib.rs"], HashMap::new())), ["src/main.rs"]);
+
+        // Open tabs are searched instead of the file on disk
+        let open = HashMap::from([(root.join("notes.md"), Rope::from_str("nothing\n"))]);
+        assert_eq!(names(&search(&["*.md"], &[], open)), Vec::<String>::new());
+
+        // Replacements skip excluded matches and files that changed
+        let mut file = files[2].clone();
+        let regex = search::build_regex("todo", false, false).unwrap();
+        let text = Rope::from_str("fn main() {\n    let todo = 1;\n}\n");
+        let replacements = file_replacements(&file, &text, &regex, "done", false, true).unwrap();
+        assert_eq!(replacements, [(20..24, "done".to_string())]);
+        assert_eq!(found.preview(&regex, "done", false, true).as_deref(), Some("    let done = 1;\n"));
+        file.matches[0].included = false;
+        assert_eq!(file_replacements(&file, &text, &regex, "done", false, true), Some(Vec::new()));
+        file.matches[0].included = true;
+        assert!(file_replacements(&file, &Rope::from_str("changed"), &regex, "done", false, true).is_none());
+        let shifted = Rope::from_str(&format!("x{}todo", "é".repeat(10)));
+        assert!(file_replacements(&file, &shifted, &regex, "done", false, true).is_none());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
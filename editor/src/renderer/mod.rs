@@ -3,15 +3,19 @@ use wgpu::{
     Device, Queue, Surface, SurfaceConfiguration, RenderPipeline,
     BindGroup, Buffer as WgpuBuffer, util::DeviceExt,
 };
+use std::ops::Range;
 use winit::window::Window;
 
-use crate::buffer::Buffer;
 use crate::text_renderer::{TextRenderer, GlyphInstance};
-use crate::cursor::Cursor;
-use crate::syntax::Token;
 use crate::file_tree::FileTree;
-use crate::tabs::TabManager;
-use crate::search::SearchState;
+use crate::symbols;
+use crate::tabs::{Tab, TabManager};
+use crate::search::{SearchOption, SearchState};
+use crate::theme::{Style, Theme};
+use crate::ui::command_palette::CommandPalette;
+use crate::ui::outline::OutlinePanel;
+use crate::ui::project_search::{Field, ProjectSearchPanel};
+use crate::ui::text_input::TextInput;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -66,7 +70,29 @@ pub struct Renderer {
     text_instance_buffer: WgpuBuffer,
     rect_instance_buffer: WgpuBuffer,
     uniform_buffer: WgpuBuffer,
+    theme: Theme,
     pub scroll_offset: f32, // Vertical scroll offset in pixels
+    /// Color brackets by nesting depth using the theme's `rainbow.*` styles
+    pub rainbow_brackets: bool,
+    /// Horizontal extent of the language label in the status bar
+    status_language_span: (f32, f32),
+    /// Buffer line shown on each row of the editor in the last frame
+    display_rows: Vec<usize>,
+    /// Top-left corner of the editor text and the width of its gutter
+    editor_origin: (f32, f32),
+    gutter_width_px: f32,
+    /// Line and column each outline row jumps to, and where the rows start
+    outline_rows: Vec<(usize, usize)>,
+    outline_origin: (f32, f32),
+    /// Rows of the file tree in the last frame (none when hidden), and the
+    /// y they start at
+    file_tree_rows: usize,
+    file_tree_top: f32,
+    /// Find bar option buttons and their x, y, width and height
+    find_buttons: Vec<(SearchOption, [f32; 4])>,
+    /// Find bar fields (`true` for the replacement), their bounds and the
+    /// x of each char boundary of their text
+    find_fields: Vec<(bool, [f32; 4], Vec<f32>)>,
 }
 
 impl Renderer {
@@ -395,10 +421,102 @@ impl Renderer {
             text_instance_buffer,
             rect_instance_buffer,
             uniform_buffer,
+            theme: Theme::default(),
             scroll_offset: 0.0,
+            rainbow_brackets: false,
+            status_language_span: (0.0, 0.0),
+            display_rows: Vec::new(),
+            editor_origin: (0.0, 0.0),
+            gutter_width_px: 0.0,
+            outline_rows: Vec::new(),
+            outline_origin: (0.0, 0.0),
+            file_tree_rows: 0,
+            file_tree_top: 0.0,
+            find_buttons: Vec::new(),
+            find_fields: Vec::new(),
         })
     }
 
+    /// Switch the theme used for all subsequent frames
+    pub fn set_theme(&mut self, theme: Theme) {
+        log::info!("🎨 Theme: {}", theme.name);
+        self.theme = theme;
+    }
+
+    /// True if a click at (x, y) lands on the status bar language label
+    pub fn status_language_hit(&self, x: f32, y: f32) -> bool {
+        let status_bar_y = self.size.height as f32 - 24.0;
+        y >= status_bar_y && x >= self.status_language_span.0 && x < self.status_language_span.1
+    }
+
+    /// Buffer line whose gutter (line number and fold marker) is at `x`, `y`
+    pub fn gutter_line_at(&self, x: f32, y: f32) -> Option<usize> {
+        let (origin_x, origin_y) = self.editor_origin;
+        if x < origin_x || x >= origin_x + self.gutter_width_px || y < origin_y {
+            return None;
+        }
+        let row = ((y - origin_y + self.scroll_offset) / 18.0) as usize;
+        self.display_rows.get(row).copied()
+    }
+
+    /// Line and column of the outline entry at `x`, `y`
+    pub fn outline_target_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let (origin_x, origin_y) = self.outline_origin;
+        if self.outline_rows.is_empty() || x < origin_x || y < origin_y {
+            return None;
+        }
+        let row = ((y - origin_y) / OUTLINE_ROW_HEIGHT) as usize;
+        self.outline_rows.get(row).copied()
+    }
+
+    /// Index of the file tree row at `x`, `y`
+    pub fn file_tree_row_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !(0.0..FILE_TREE_WIDTH).contains(&x) || y < self.file_tree_top {
+            return None;
+        }
+        let row = ((y - self.file_tree_top) / OUTLINE_ROW_HEIGHT) as usize;
+        (row < self.file_tree_rows).then_some(row)
+    }
+
+    /// Find bar option button at `x`, `y`
+    pub fn find_option_at(&self, x: f32, y: f32) -> Option<SearchOption> {
+        self.find_buttons.iter().find(|(_, bounds)| contains(bounds, x, y)).map(|(option, _)| *option)
+    }
+
+    /// Find bar field at `x`, `y` (`true` for the replacement) and the char
+    /// boundary nearest to `x`
+    pub fn find_field_at(&self, x: f32, y: f32) -> Option<(bool, usize)> {
+        let (is_replacement, _, offsets) = self.find_fields.iter().find(|(_, bounds, _)| contains(bounds, x, y))?;
+        let nearest = offsets
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - x).abs().total_cmp(&(*b - x).abs()))
+            .map_or(0, |(position, _)| position);
+        Some((*is_replacement, nearest))
+    }
+
+    /// Buffer lines on screen in the last frame
+    pub fn visible_lines(&self) -> Range<usize> {
+        let visible_height = self.size.height as f32 - self.editor_origin.1 - 24.0;
+        let first = (self.scroll_offset / 18.0) as usize;
+        let last = ((self.scroll_offset + visible_height) / 18.0) as usize;
+        match (self.display_rows.get(first), self.display_rows.get(last.min(self.display_rows.len().saturating_sub(1)))) {
+            (Some(&start), Some(&end)) => start..end + 1,
+            _ => 0..0,
+        }
+    }
+
+    /// Scroll so that a buffer line of `tab` is on screen
+    pub fn scroll_to_line(&mut self, tab: &Tab, line: usize) {
+        // Count rows from the tab's folds, the last frame may show another tab
+        let row = (0..line).filter(|&l| !tab.folds.is_hidden(l)).count();
+        let y = row as f32 * 18.0;
+        let visible_height = self.size.height as f32 - self.editor_origin.1 - 24.0;
+        if y < self.scroll_offset || y + 18.0 > self.scroll_offset + visible_height {
+            self.scroll_offset = (y - visible_height / 3.0).max(0.0);
+        }
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
@@ -416,54 +534,110 @@ impl Renderer {
 
     pub fn render(
         &mut self,
-        buffer: &Buffer,
-        cursor: &Cursor,
-        syntax_tokens: &[Token],
-        filename: Option<&str>,
-        is_modified: bool,
-        file_tree: &FileTree,
         tab_manager: &TabManager,
+        file_tree: &FileTree,
+        outline: &OutlinePanel,
+        project_search: &ProjectSearchPanel,
         search_state: &SearchState,
+        command_palette: &CommandPalette,
     ) -> Result<()> {
+        let tab = tab_manager.active_tab();
+        let buffer = &tab.buffer;
+        let cursor = &tab.cursor;
+        let syntax_tokens = &tab.syntax_tokens;
+        let folds = &tab.folds;
+        let filename = tab.file_path.as_ref().and_then(|p| p.file_name()?.to_str());
+        let is_modified = tab.is_modified;
+        let language = tab.language_name();
+
         // Get current frame
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        // Create color lookup function from syntax tokens
-        let color_fn = |offset: usize| {
-            for token in syntax_tokens {
-                if offset >= token.start && offset < token.end {
-                    return token.token_type.color();
-                }
-            }
-            [1.0, 1.0, 1.0, 1.0] // Default white
-        };
+        let theme = &self.theme;
 
         // Calculate layout offsets
         let tab_bar_height = 30.0;
-        let file_tree_width = if file_tree.is_visible { 250.0 } else { 0.0 };
+        let file_tree_width = if file_tree.is_visible { FILE_TREE_WIDTH } else { 0.0 };
+        let breadcrumb_height = 20.0;
         let text_offset_x = file_tree_width + 4.0; // Add padding
-        let text_offset_y = tab_bar_height + 4.0;
+        let text_offset_y = tab_bar_height + breadcrumb_height + 4.0;
 
-        // Prepare text with line numbers
+        // Prepare text with line numbers and fold markers, skipping folded lines
         let line_count = buffer.line_count();
-        let gutter_width = (line_count.to_string().len() + 2) as usize;
+        let digits = line_count.to_string().len();
+        let gutter_width = digits + 2; // Line number, fold marker, space
         let mut text_with_lines = String::new();
-        for (idx, line) in buffer.text().lines().enumerate() {
-            text_with_lines.push_str(&format!("{:width$} {}\n", idx + 1, line, width = gutter_width - 1));
+        // Buffer byte offset, gutter bytes and text bytes of each displayed line
+        let mut line_starts = Vec::new();
+        let mut display_rows = Vec::new();
+        let mut offset = 0;
+        for (idx, line) in buffer.text().split_inclusive('\n').enumerate() {
+            let start = offset;
+            offset += line.len();
+            if folds.is_hidden(idx) {
+                continue;
+            }
+
+            let is_folded = folds.is_folded(idx);
+            let marker = if is_folded {
+                '▸'
+            } else if folds.is_foldable(idx) {
+                '▾'
+            } else {
+                ' '
+            };
+            let gutter = format!("{:>digits$}{} ", idx + 1, marker, digits = digits);
+            let display = line.trim_end_matches('\n').trim_end_matches('\r');
+
+            text_with_lines.push_str(&gutter);
+            text_with_lines.push_str(display);
+            if is_folded {
+                text_with_lines.push_str(" ⋯");
+            }
+            text_with_lines.push('\n');
+            line_starts.push((start, gutter.len(), display.len()));
+            display_rows.push(idx);
         }
 
+        // Map (display line, byte in line) back to the buffer to find the token style
+        let style_fn = |line: usize, byte: usize| -> Style {
+            let (start, gutter_len, text_len) = match line_starts.get(line) {
+                Some(&line_start) => line_start,
+                None => return Style::plain(theme.ui.foreground),
+            };
+            // Gutter and the placeholder after a folded line
+            if byte < gutter_len || byte >= gutter_len + text_len {
+                return Style::plain(theme.ui.line_number);
+            }
+            let offset = start + byte - gutter_len;
+            if self.rainbow_brackets {
+                if let Ok(index) = tab.brackets.binary_search_by_key(&offset, |b| b.offset) {
+                    if let Some(style) = theme.rainbow(tab.brackets[index].depth) {
+                        return style;
+                    }
+                }
+            }
+            for token in syntax_tokens {
+                if offset >= token.start && offset < token.end {
+                    return theme.style(&token.scope);
+                }
+            }
+            Style::plain(theme.ui.foreground)
+        };
+
         // Render text to get glyph instances with offset
-        let mut instances = self.text_renderer.render_text(
+        let rendered = self.text_renderer.render_text(
             &self.device,
             &self.queue,
             &text_with_lines,
             14.0, // font size
             18.0, // line height
-            Some(&color_fn),
+            Some(&style_fn),
         )?;
+        let mut instances = rendered.glyphs;
 
         // Apply offset to all text instances (including scroll)
         for instance in instances.iter_mut() {
@@ -487,23 +661,53 @@ impl Renderer {
         };
 
         let status_text = format!(
-            " {} | Line {}, Col {} | {} lines",
+            " {} | Line {}, Col {} | {} lines | {}",
             file_display,
             cursor.position.line + 1,
             cursor.position.column + 1,
-            line_count
+            line_count,
+            tab.indent_style
         );
 
         // Render status bar text
         let status_bar_y = (self.size.height as f32) - 24.0 + 4.0; // Add small padding
-        let status_instances = self.text_renderer.render_text_at_position(
+        let mut status_instances = self.text_renderer.render_text_at_position(
             &self.device,
             &self.queue,
             &status_text,
             12.0, // Smaller font for status bar
             4.0, // x position (left padding)
             status_bar_y,
-            [0.7, 0.7, 0.7, 1.0], // Light gray text
+            theme.ui.status_bar_foreground,
+        )?;
+
+        // Language on the right of the status bar; clicking it opens the picker
+        let language_width = language.chars().count() as f32 * 7.0 + 16.0; // Approx width + padding
+        let language_x = (self.size.width as f32 - language_width).max(0.0);
+        self.status_language_span = (language_x, language_x + language_width);
+        status_instances.extend(self.text_renderer.render_text_at_position(
+            &self.device,
+            &self.queue,
+            language,
+            12.0,
+            language_x + 8.0,
+            status_bar_y,
+            theme.ui.status_bar_foreground,
+        )?);
+
+        // Breadcrumbs: the file, then the symbols around the cursor
+        let breadcrumbs = std::iter::once(filename.unwrap_or("Untitled").to_string())
+            .chain(tab.breadcrumbs().iter().map(|symbol| symbol.label()))
+            .collect::<Vec<_>>()
+            .join(" › ");
+        let breadcrumb_instances = self.text_renderer.render_text_at_position(
+            &self.device,
+            &self.queue,
+            &breadcrumbs,
+            12.0,
+            text_offset_x + 4.0,
+            tab_bar_height + 3.0,
+            theme.ui.line_number,
         )?;
 
         // Render tab bar
@@ -516,9 +720,9 @@ impl Renderer {
             let tab_width = (tab_title.len() as f32 * 8.0) + 20.0; // Approx width + padding
 
             let color = if is_active {
-                [0.9, 0.9, 0.9, 1.0] // White for active
+                theme.ui.tab_active_foreground
             } else {
-                [0.6, 0.6, 0.6, 1.0] // Gray for inactive
+                theme.ui.tab_inactive_foreground
             };
 
             let tab_text_instances = self.text_renderer.render_text_at_position(
@@ -537,9 +741,26 @@ impl Renderer {
 
         // Render file tree (if visible)
         let mut file_tree_instances = Vec::new();
-        let file_tree_width = 250.0;
+        let mut file_tree_input_rects = Vec::new();
+        let file_tree_width = FILE_TREE_WIDTH;
         if file_tree.is_visible {
-            let mut tree_text = String::from("📁 Files\n\n");
+            // The name being typed for a new entry or a rename, or else the
+            // filter, goes on the line under the title
+            let mut tree_text = String::from("📁 Files\n");
+            let field = match (&file_tree.prompt, &file_tree.filter) {
+                (Some(prompt), _) => Some((prompt.label(), &prompt.input)),
+                (None, Some(filter)) => Some(("Filter", &filter.input)),
+                (None, None) => None,
+            };
+            if let Some((label, input)) = field {
+                let label = format!("{}: ", label);
+                let line = format!("{}{}", label, input.text());
+                let offsets = self.text_renderer.char_offsets(&line, 11.0);
+                let y = tab_bar_height + 8.0 + OUTLINE_ROW_HEIGHT;
+                file_tree_input_rects = input_rects(input, &offsets[label.chars().count()..], 4.0, y, OUTLINE_ROW_HEIGHT, theme);
+                tree_text.push_str(&line);
+            }
+            tree_text.push('\n');
             for (idx, node) in file_tree.nodes.iter().enumerate() {
                 let indent = "  ".repeat(node.depth);
                 let marker = if idx == file_tree.selected_index { "▶ " } else { "  " };
@@ -550,6 +771,9 @@ impl Renderer {
                 };
                 tree_text.push_str(&format!("{}{}{} {}\n", marker, indent, icon, node.name));
             }
+            if file_tree.nodes.is_empty() && file_tree.filter.is_some() {
+                tree_text.push_str("  No matching files\n");
+            }
 
             file_tree_instances = self.text_renderer.render_text_at_position(
                 &self.device,
@@ -558,39 +782,275 @@ impl Renderer {
                 11.0,
                 4.0,
                 tab_bar_height + 8.0,
-                [0.9, 0.9, 0.9, 1.0],
+                theme.ui.sidebar_foreground,
+            )?;
+            self.file_tree_rows = file_tree.nodes.len();
+            self.file_tree_top = tab_bar_height + 8.0 + 2.0 * OUTLINE_ROW_HEIGHT;
+        } else {
+            self.file_tree_rows = 0;
+        }
+
+        // Render outline panel (if visible)
+        let mut outline_instances = Vec::new();
+        let outline_width = 250.0;
+        let outline_x = (self.size.width as f32 - outline_width).max(0.0);
+        let outline_symbols = symbols::flatten(&tab.symbols);
+        let current_symbol = tab.breadcrumbs().last().copied();
+        let mut current_outline_row = None;
+        if outline.is_visible {
+            let mut outline_text = String::from("Outline\n\n");
+            for (row, label) in OutlinePanel::rows(&tab.symbols).iter().enumerate() {
+                outline_text.push_str(label);
+                outline_text.push('\n');
+                if current_symbol.is_some_and(|s| std::ptr::eq(s, outline_symbols[row].1)) {
+                    current_outline_row = Some(row);
+                }
+            }
+            if outline_symbols.is_empty() {
+                outline_text.push_str("No symbols\n");
+            }
+
+            outline_instances = self.text_renderer.render_text_at_position(
+                &self.device,
+                &self.queue,
+                &outline_text,
+                11.0,
+                outline_x + 8.0,
+                tab_bar_height + 8.0,
+                theme.ui.sidebar_foreground,
+            )?;
+
+            self.outline_rows = outline_symbols.iter().map(|(_, s)| (s.line, s.column)).collect();
+            self.outline_origin = (outline_x, tab_bar_height + 8.0 + 2.0 * OUTLINE_ROW_HEIGHT);
+        } else {
+            self.outline_rows.clear();
+        }
+
+        // Render project search panel (if visible): fields, then the results
+        // scrolled to keep the selected row in view
+        let mut project_search_instances = Vec::new();
+        let project_search_x = if file_tree.is_visible { file_tree_width + 1.0 } else { 0.0 };
+        let project_search_y = self.size.height as f32 - 24.0 - PROJECT_SEARCH_HEIGHT;
+        let mut project_search_selected_line = None;
+        let mut project_search_input_rects = Vec::new();
+        if project_search.is_visible {
+            let marker = |field: Field| {
+                let editing = project_search.is_focused && project_search.selected.is_none() && project_search.field == field;
+                if editing { ">" } else { " " }
+            };
+            let options: String = [
+                (project_search.case_sensitive, " [Aa]"),
+                (project_search.whole_word, " [ab]"),
+                (project_search.use_regex, " [.*]"),
+                (project_search.preserve_case && project_search.is_replace_mode, " [AB]"),
+            ]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, label)| *label)
+            .collect();
+            let count = project_search.match_count();
+            let status = match project_search.error {
+                Some(ref error) => error.clone(),
+                None if project_search.is_searching() => format!("{} matches…", count),
+                None if project_search.truncated => format!("{}+ matches", count),
+                None => format!("{} matches in {} files", count, project_search.results.len()),
+            };
+
+            let field_text =
+                |field: Field, label: &str| format!("{}{}: {}", marker(field), label, project_search.field_input(field).text());
+            let mut header = vec![format!("{}{}   ({})", field_text(Field::Query, "Search"), options, status)];
+            if project_search.is_replace_mode {
+                header.push(field_text(Field::Replace, "Replace"));
+            }
+            let include = field_text(Field::Include, "Include");
+            header.push(format!("{}   {}", include, field_text(Field::Exclude, "Exclude")));
+            let header_lines = header.len();
+
+            // Caret and selection of the field being edited: its line, and
+            // the byte of the line its text starts at
+            if project_search.is_focused && project_search.selected.is_none() {
+                let (line, start) = match project_search.field {
+                    Field::Query => (0, ">Search: ".len()),
+                    Field::Replace => (1, ">Replace: ".len()),
+                    Field::Include => (header_lines - 1, ">Include: ".len()),
+                    Field::Exclude => (header_lines - 1, include.len() + "   >Exclude: ".len()),
+                };
+                let offsets = self.text_renderer.char_offsets(&header[line], 11.0);
+                let first = header[line][..start].chars().count();
+                project_search_input_rects = input_rects(
+                    project_search.field_input(project_search.field),
+                    &offsets[first..],
+                    project_search_x + 8.0,
+                    project_search_y + 4.0 + line as f32 * OUTLINE_ROW_HEIGHT,
+                    OUTLINE_ROW_HEIGHT,
+                    theme,
+                );
+            }
+            let mut text = header.join("\n");
+            text.push('\n');
+
+            let lines = project_search.lines(&file_tree.workspace);
+            let visible = (PROJECT_SEARCH_HEIGHT / OUTLINE_ROW_HEIGHT) as usize - header_lines - 1;
+            let selected_line = project_search.selected.and_then(|row| lines.iter().position(|(_, r)| *r == Some(row)));
+            let first = selected_line.map_or(0, |line| (line + 1).saturating_sub(visible));
+            for (line, _) in lines.iter().skip(first).take(visible) {
+                text.push_str(line);
+                text.push('\n');
+            }
+            project_search_selected_line = selected_line.map(|line| header_lines + line - first);
+
+            project_search_instances = self.text_renderer.render_text_at_position(
+                &self.device,
+                &self.queue,
+                &text,
+                11.0,
+                project_search_x + 8.0,
+                project_search_y + 4.0,
+                theme.ui.sidebar_foreground,
             )?;
         }
 
-        // Render search UI (if active)
+        // Render the find bar (if active): a row for the query and one for
+        // the replacement, each a field followed by its option buttons
         let mut search_ui_instances = Vec::new();
+        let mut find_rects = Vec::new();
+        self.find_buttons.clear();
+        self.find_fields.clear();
         if search_state.is_active {
-            let search_text = if search_state.is_replace_mode {
-                format!("Find & Replace: {} ({} matches)", search_state.query, search_state.match_count())
-            } else {
-                format!("Find: {} ({} matches) - Press ESC to close", search_state.query, search_state.match_count())
-            };
+            let font_size = 12.0;
+            let bar_x = if file_tree.is_visible { file_tree_width + 10.0 } else { 10.0 };
+            let field_x = bar_x + 8.0;
+            let mut rows = vec![(
+                false,
+                &search_state.query,
+                "Find",
+                vec![SearchOption::CaseSensitive, SearchOption::WholeWord, SearchOption::Regex],
+            )];
+            if search_state.is_replace_mode {
+                rows.push((true, &search_state.replace_text, "Replace", vec![SearchOption::PreserveCase]));
+            }
+            let offsets: Vec<Vec<f32>> =
+                rows.iter().map(|(_, input, _, _)| self.text_renderer.char_offsets(input.text(), font_size)).collect();
+            let field_width = offsets
+                .iter()
+                .map(|offsets| offsets.last().copied().unwrap_or_default() + 12.0)
+                .fold(FIND_FIELD_WIDTH, f32::max);
+
+            let mut bar_end = field_x + field_width;
+            for (row, ((is_replacement, input, placeholder, options), offsets)) in rows.iter().zip(offsets).enumerate() {
+                let y = tab_bar_height + row as f32 * FIND_ROW_HEIGHT + 4.0;
+                let text_x = field_x + 4.0;
+                find_rects.push(RectInstance {
+                    position: [field_x, y],
+                    size: [field_width, 18.0],
+                    color: theme.ui.background,
+                });
+                let (text, color) = if input.is_empty() {
+                    (*placeholder, theme.ui.line_number)
+                } else {
+                    (input.text(), theme.ui.overlay_foreground)
+                };
+                search_ui_instances.extend(self.text_renderer.render_text_at_position(
+                    &self.device,
+                    &self.queue,
+                    text,
+                    font_size,
+                    text_x,
+                    y + 2.0,
+                    color,
+                )?);
+                if search_state.editing_replacement == *is_replacement {
+                    find_rects.extend(input_rects(input, &offsets, text_x, y + 1.0, 16.0, theme));
+                }
+                self.find_fields.push((*is_replacement, [field_x, y, field_width, 18.0], offsets.iter().map(|o| text_x + o).collect()));
+
+                let mut x = field_x + field_width + 6.0;
+                for &option in options {
+                    if search_state.option(option) {
+                        find_rects.push(RectInstance {
+                            position: [x, y],
+                            size: [FIND_BUTTON_WIDTH, 18.0],
+                            color: theme.ui.overlay_selection,
+                        });
+                    }
+                    let label_width = self.text_renderer.char_offsets(option.label(), font_size).last().copied().unwrap_or_default();
+                    search_ui_instances.extend(self.text_renderer.render_text_at_position(
+                        &self.device,
+                        &self.queue,
+                        option.label(),
+                        font_size,
+                        x + (FIND_BUTTON_WIDTH - label_width) / 2.0,
+                        y + 2.0,
+                        theme.ui.overlay_foreground,
+                    )?);
+                    self.find_buttons.push((option, [x, y, FIND_BUTTON_WIDTH, 18.0]));
+                    x += FIND_BUTTON_WIDTH + 2.0;
+                }
+
+                // "3 of 12" and the scope after the query's buttons
+                if row == 0 {
+                    let status = format!("{}{}", search_state.counter(), search_state.scope.label());
+                    let status_width = self.text_renderer.char_offsets(&status, font_size).last().copied().unwrap_or_default();
+                    search_ui_instances.extend(self.text_renderer.render_text_at_position(
+                        &self.device,
+                        &self.queue,
+                        &status,
+                        font_size,
+                        x + 8.0,
+                        y + 2.0,
+                        theme.ui.overlay_foreground,
+                    )?);
+                    x += 8.0 + status_width;
+                }
+                bar_end = bar_end.max(x);
+            }
+
+            find_rects.insert(
+                0,
+                RectInstance {
+                    position: [bar_x, tab_bar_height],
+                    size: [bar_end - bar_x + 8.0, rows.len() as f32 * FIND_ROW_HEIGHT + 4.0],
+                    color: theme.ui.overlay_background,
+                },
+            );
+        }
 
-            let search_y = tab_bar_height + 4.0;
-            let search_x = if file_tree.is_visible { file_tree_width + 10.0 } else { 10.0 };
+        // Render command palette (if active)
+        let palette_width = 500.0;
+        let palette_x = ((self.size.width as f32 - palette_width) / 2.0).max(0.0);
+        let palette_y = tab_bar_height + 4.0;
+        let palette_visible_items = 12;
+        let mut palette_instances = Vec::new();
+        let mut palette_input_rects = Vec::new();
+        if command_palette.is_active {
+            let offsets = self.text_renderer.char_offsets(&format!("> {}", command_palette.query.text()), 12.0);
+            palette_input_rects = input_rects(&command_palette.query, &offsets[2..], palette_x + 8.0, palette_y + 4.0, 14.4, theme);
+            let mut palette_text = format!("> {}\n", command_palette.query.text());
+            for item in command_palette.visible_items().take(palette_visible_items) {
+                palette_text.push_str(&format!("  {}\n", item.label));
+            }
 
-            search_ui_instances = self.text_renderer.render_text_at_position(
+            palette_instances = self.text_renderer.render_text_at_position(
                 &self.device,
                 &self.queue,
-                &search_text,
+                &palette_text,
                 12.0,
-                search_x,
-                search_y,
-                [1.0, 0.9, 0.3, 1.0], // Yellow highlight
+                palette_x + 8.0,
+                palette_y + 4.0,
+                theme.ui.overlay_foreground,
             )?;
         }
 
         // Combine text instances (main text + status bar + tab bar + file tree + search)
         let mut all_instances = instances;
+        all_instances.extend(breadcrumb_instances);
         all_instances.extend(status_instances);
         all_instances.extend(tab_bar_instances);
         all_instances.extend(file_tree_instances);
+        all_instances.extend(outline_instances);
+        all_instances.extend(project_search_instances);
         all_instances.extend(search_ui_instances);
+        all_instances.extend(palette_instances);
 
         // Update text instance buffer
         if !all_instances.is_empty() {
@@ -608,7 +1068,7 @@ impl Renderer {
         rect_instances.push(RectInstance {
             position: [0.0, 0.0],
             size: [self.size.width as f32, tab_bar_height],
-            color: [0.15, 0.15, 0.15, 1.0], // Dark gray
+            color: theme.ui.tab_bar_background,
         });
 
         // Add file tree panel background (if visible)
@@ -616,41 +1076,130 @@ impl Renderer {
             rect_instances.push(RectInstance {
                 position: [0.0, tab_bar_height],
                 size: [file_tree_width, self.size.height as f32 - tab_bar_height],
-                color: [0.10, 0.10, 0.10, 1.0], // Darker gray
+                color: theme.ui.sidebar_background,
             });
 
             // Add vertical separator line
             rect_instances.push(RectInstance {
                 position: [file_tree_width, tab_bar_height],
                 size: [1.0, self.size.height as f32 - tab_bar_height],
-                color: [0.3, 0.3, 0.3, 1.0], // Light gray separator
+                color: theme.ui.separator,
             });
+            rect_instances.extend(file_tree_input_rects);
         }
 
-        // Add search box background (if active)
-        if search_state.is_active {
-            let search_x = if file_tree.is_visible { file_tree_width + 10.0 } else { 10.0 };
-            let search_width = 400.0;
-            let search_box_y = tab_bar_height;
-            let search_box_height = 30.0;
+        // Add outline panel background, separator and current symbol (if visible)
+        if outline.is_visible {
+            rect_instances.push(RectInstance {
+                position: [outline_x, tab_bar_height],
+                size: [outline_width, self.size.height as f32 - tab_bar_height],
+                color: theme.ui.sidebar_background,
+            });
+            rect_instances.push(RectInstance {
+                position: [outline_x, tab_bar_height],
+                size: [1.0, self.size.height as f32 - tab_bar_height],
+                color: theme.ui.separator,
+            });
+            if let Some(row) = current_outline_row {
+                rect_instances.push(RectInstance {
+                    position: [outline_x + 1.0, self.outline_origin.1 + row as f32 * OUTLINE_ROW_HEIGHT],
+                    size: [outline_width - 1.0, OUTLINE_ROW_HEIGHT],
+                    color: theme.ui.overlay_selection,
+                });
+            }
+        }
 
+        // Add project search panel background, separator and selected row (if visible)
+        if project_search.is_visible {
+            let width = self.size.width as f32 - project_search_x;
+            rect_instances.push(RectInstance {
+                position: [project_search_x, project_search_y],
+                size: [width, PROJECT_SEARCH_HEIGHT],
+                color: theme.ui.sidebar_background,
+            });
             rect_instances.push(RectInstance {
-                position: [search_x, search_box_y],
-                size: [search_width, search_box_height],
-                color: [0.2, 0.2, 0.2, 0.95], // Semi-transparent dark background
+                position: [project_search_x, project_search_y],
+                size: [width, 1.0],
+                color: theme.ui.separator,
+            });
+            if let Some(line) = project_search_selected_line {
+                rect_instances.push(RectInstance {
+                    position: [project_search_x, project_search_y + 4.0 + line as f32 * OUTLINE_ROW_HEIGHT],
+                    size: [width, OUTLINE_ROW_HEIGHT],
+                    color: theme.ui.overlay_selection,
+                });
+            }
+            rect_instances.extend(project_search_input_rects);
+        }
+
+        // Add the find bar: background, fields, toggled options, caret and selection
+        rect_instances.extend(find_rects);
+
+        // Add command palette background and selected row (if active)
+        if command_palette.is_active {
+            let row_height = 14.4; // 12pt font * 1.2 line height
+            let rows = command_palette.visible_items().take(palette_visible_items).count() + 1;
+            rect_instances.push(RectInstance {
+                position: [palette_x, palette_y],
+                size: [palette_width, rows as f32 * row_height + 8.0],
+                color: theme.ui.overlay_background,
+            });
+            if command_palette.selected_index < palette_visible_items {
+                rect_instances.push(RectInstance {
+                    position: [palette_x, palette_y + 4.0 + (command_palette.selected_index + 1) as f32 * row_height],
+                    size: [palette_width, row_height],
+                    color: theme.ui.overlay_selection,
+                });
+            }
+            rect_instances.extend(palette_input_rects);
+        }
+
+        // Add underlines for styled text
+        for underline in &rendered.underlines {
+            rect_instances.push(RectInstance {
+                position: [
+                    underline.position[0] + text_offset_x,
+                    underline.position[1] + text_offset_y - self.scroll_offset,
+                ],
+                size: [underline.width, 1.0],
+                color: underline.color,
             });
         }
 
+        // Display row of a buffer line; folded lines map to their fold's header
+        let row_of = |line: usize| {
+            let line = folds.prev_visible(line);
+            display_rows.partition_point(|&row_line| row_line < line)
+        };
+
+        // Highlight the bracket at the cursor and its match
+        if let Some((bracket, partner)) = tab.matching_bracket() {
+            for offset in [bracket, partner] {
+                let (line, column) = buffer.offset_to_position(offset);
+                if folds.is_hidden(line) {
+                    continue;
+                }
+                rect_instances.push(RectInstance {
+                    position: [
+                        text_offset_x + gutter_width as f32 * 8.0 + column as f32 * 8.0,
+                        text_offset_y + (row_of(line) as f32 * 18.0) - self.scroll_offset,
+                    ],
+                    size: [8.0, 18.0],
+                    color: theme.ui.bracket_match,
+                });
+            }
+        }
+
         // Add cursor rectangle if should draw
         if cursor.should_draw() {
             // Account for line numbers gutter and scroll
             let gutter_width_px = gutter_width as f32 * 8.0;
             let cursor_x = text_offset_x + gutter_width_px + (cursor.position.column as f32 * 8.0);
-            let cursor_y = text_offset_y + (cursor.position.line as f32 * 18.0) - self.scroll_offset;
+            let cursor_y = text_offset_y + (row_of(cursor.position.line) as f32 * 18.0) - self.scroll_offset;
             rect_instances.push(RectInstance {
                 position: [cursor_x, cursor_y],
                 size: [2.0, 18.0], // 2px wide cursor
-                color: [1.0, 1.0, 1.0, 1.0], // White cursor
+                color: theme.ui.cursor,
             });
         }
 
@@ -659,21 +1208,23 @@ impl Renderer {
             // Account for line numbers gutter and scroll
             let gutter_width_px = gutter_width as f32 * 8.0;
             let start_x = text_offset_x + gutter_width_px + (selection.start.column as f32 * 8.0);
-            let start_y = text_offset_y + (selection.start.line as f32 * 18.0) - self.scroll_offset;
+            let start_y = text_offset_y + (row_of(selection.start.line) as f32 * 18.0) - self.scroll_offset;
             let end_x = text_offset_x + gutter_width_px + (selection.end.column as f32 * 8.0);
-            let _end_y = text_offset_y + (selection.end.line as f32 * 18.0) - self.scroll_offset;
 
             if selection.start.line == selection.end.line {
                 // Single line selection
                 rect_instances.push(RectInstance {
                     position: [start_x, start_y],
                     size: [end_x - start_x, 18.0],
-                    color: [0.3, 0.5, 0.8, 0.3], // Semi-transparent blue
+                    color: theme.ui.selection,
                 });
             } else {
                 // Multi-line selection (improved)
                 for line in selection.start.line..=selection.end.line {
-                    let y = text_offset_y + (line as f32 * 18.0) - self.scroll_offset;
+                    if folds.is_hidden(line) {
+                        continue;
+                    }
+                    let y = text_offset_y + (row_of(line) as f32 * 18.0) - self.scroll_offset;
                     let line_start_x = text_offset_x + gutter_width_px;
                     let max_line_width = self.size.width as f32 - line_start_x;
 
@@ -681,25 +1232,29 @@ impl Renderer {
                         rect_instances.push(RectInstance {
                             position: [start_x, y],
                             size: [max_line_width - (start_x - line_start_x), 18.0],
-                            color: [0.3, 0.5, 0.8, 0.3],
+                            color: theme.ui.selection,
                         });
                     } else if line == selection.end.line {
                         rect_instances.push(RectInstance {
                             position: [line_start_x, y],
                             size: [end_x - line_start_x, 18.0],
-                            color: [0.3, 0.5, 0.8, 0.3],
+                            color: theme.ui.selection,
                         });
                     } else {
                         rect_instances.push(RectInstance {
                             position: [line_start_x, y],
                             size: [max_line_width, 18.0],
-                            color: [0.3, 0.5, 0.8, 0.3],
+                            color: theme.ui.selection,
                         });
                     }
                 }
             }
         }
 
+        self.editor_origin = (text_offset_x, text_offset_y);
+        self.gutter_width_px = gutter_width as f32 * 8.0;
+        self.display_rows = display_rows;
+
         // Add status bar background
         let status_bar_height = 24.0;
         let window_height = self.size.height as f32;
@@ -708,7 +1263,7 @@ impl Renderer {
         rect_instances.push(RectInstance {
             position: [0.0, status_bar_y],
             size: [self.size.width as f32, status_bar_height],
-            color: [0.08, 0.08, 0.08, 1.0], // Darker background for status bar
+            color: theme.ui.status_bar_background,
         });
 
         // Update rect instance buffer
@@ -720,6 +1275,8 @@ impl Renderer {
             );
         }
 
+        let background = theme.ui.background;
+
         // Create command encoder
         let mut encoder = self
             .device
@@ -736,10 +1293,10 @@ impl Renderer {
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.12,
-                            g: 0.12,
-                            b: 0.12,
-                            a: 1.0,
+                            r: background[0] as f64,
+                            g: background[1] as f64,
+                            b: background[2] as f64,
+                            a: background[3] as f64,
                         }),
                         store: wgpu::StoreOp::Store,
                     },
@@ -776,6 +1333,50 @@ impl Renderer {
     }
 }
 
+/// Height of a row in the outline panel (11pt font * 1.2 line height)
+const OUTLINE_ROW_HEIGHT: f32 = 13.2;
+
+/// Width of the file tree panel
+const FILE_TREE_WIDTH: f32 = 250.0;
+
+/// Height of the project search panel above the status bar
+const PROJECT_SEARCH_HEIGHT: f32 = 240.0;
+
+/// Height of a row of the find bar
+const FIND_ROW_HEIGHT: f32 = 24.0;
+
+/// Narrowest width of the find bar's fields
+const FIND_FIELD_WIDTH: f32 = 220.0;
+
+/// Width of a find option button
+const FIND_BUTTON_WIDTH: f32 = 22.0;
+
+/// True if `x`, `y` is inside `bounds` (x, y, width, height)
+fn contains(bounds: &[f32; 4], x: f32, y: f32) -> bool {
+    let [left, top, width, height] = *bounds;
+    x >= left && x < left + width && y >= top && y < top + height
+}
+
+/// The selection and caret of a focused text input whose text is drawn at
+/// `x`, given the x of each char boundary of its text
+fn input_rects(input: &TextInput, offsets: &[f32], x: f32, y: f32, height: f32, theme: &Theme) -> Vec<RectInstance> {
+    let at = |position: usize| x + offsets.get(position).or(offsets.last()).copied().unwrap_or_default();
+    let mut rects = Vec::new();
+    if let Some(selection) = input.selection() {
+        rects.push(RectInstance {
+            position: [at(selection.start), y],
+            size: [at(selection.end) - at(selection.start), height],
+            color: theme.ui.selection,
+        });
+    }
+    rects.push(RectInstance {
+        position: [at(input.cursor()), y],
+        size: [1.0, height],
+        color: theme.ui.cursor,
+    });
+    rects
+}
+
 // Helper function to create orthographic projection matrix
 fn create_ortho_matrix(width: f32, height: f32) -> [[f32; 4]; 4] {
     [
@@ -0,0 +1,963 @@
+mod stream;
+
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::buffer::{Buffer, Edit};
+use crate::cursor::{Cursor, Position};
+use crate::history::History;
+use crate::input::InputHandler;
+use crate::ui::text_input::{InputResponse, TextInput};
+use stream::{SearchJob, OVERLAP_LINES};
+
+pub use stream::{lines_text, search_lines, Pattern};
+
+/// Matches found at most; the rest of the document isn't searched
+pub const MAX_MATCHES: usize = 10_000;
+
+/// Buffers larger than this many bytes are searched on a background thread
+const BACKGROUND_THRESHOLD: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// Part of the buffer searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchScope {
+    #[default]
+    Document,
+    /// The selection when the scope was set
+    Selection,
+    /// The function around the cursor
+    Function,
+    /// The lines on screen
+    Visible,
+}
+
+impl SearchScope {
+    /// Command palette labels
+    pub const ALL: &'static [(&'static str, SearchScope)] = &[
+        ("Search Scope: Document", SearchScope::Document),
+        ("Search Scope: Selection", SearchScope::Selection),
+        ("Search Scope: Current Function", SearchScope::Function),
+        ("Search Scope: Visible Area", SearchScope::Visible),
+    ];
+
+    /// Label shown in the find bar
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchScope::Document => "",
+            SearchScope::Selection => " [in selection]",
+            SearchScope::Function => " [in function]",
+            SearchScope::Visible => " [in view]",
+        }
+    }
+}
+
+/// Find options, toggled with Alt+C/W/R/P or their buttons in the find bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOption {
+    CaseSensitive,
+    WholeWord,
+    Regex,
+    PreserveCase,
+}
+
+impl SearchOption {
+    /// Button label in the find bar
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchOption::CaseSensitive => "Aa",
+            SearchOption::WholeWord => "ab",
+            SearchOption::Regex => ".*",
+            SearchOption::PreserveCase => "AB",
+        }
+    }
+}
+
+/// The query and options matches were found for
+#[derive(Debug, Clone, PartialEq)]
+struct SearchKey {
+    query: String,
+    case_sensitive: bool,
+    use_regex: bool,
+    whole_word: bool,
+}
+
+pub struct SearchState {
+    pub query: TextInput,
+    pub replace_text: TextInput,
+    pub is_active: bool,
+    pub is_replace_mode: bool,
+    /// Typing goes to the replacement instead of the query
+    pub editing_replacement: bool,
+    pub matches: Vec<SearchMatch>,
+    pub current_match_index: Option<usize>,
+    pub case_sensitive: bool,
+    pub use_regex: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// Give replacements the case of the text they replace
+    pub preserve_case: bool,
+    /// Why the query isn't a valid pattern, if it isn't
+    pub error: Option<String>,
+    /// The search stopped at `MAX_MATCHES`
+    pub truncated: bool,
+    /// Where to search; set with `set_scope`
+    pub scope: SearchScope,
+    /// Char range the scope covers, moved along with edits, and the buffer
+    /// version it is for
+    scope_range: Option<(Range<usize>, usize)>,
+    /// The query compiled by the last `find_all_matches`
+    pattern: Option<Pattern>,
+    /// What `matches` were found for, and the buffer version they are for
+    searched: Option<(SearchKey, usize)>,
+    /// The background search still delivering matches
+    job: Option<SearchJob>,
+    /// Past queries and replacements, shared with the project search
+    pub query_history: History,
+    pub replace_history: History,
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self {
+            query: TextInput::new(),
+            replace_text: TextInput::new(),
+            is_active: false,
+            is_replace_mode: false,
+            editing_replacement: false,
+            matches: Vec::new(),
+            current_match_index: None,
+            case_sensitive: false,
+            use_regex: false,
+            whole_word: false,
+            preserve_case: false,
+            error: None,
+            truncated: false,
+            scope: SearchScope::Document,
+            scope_range: None,
+            pattern: None,
+            searched: None,
+            job: None,
+            query_history: History::default(),
+            replace_history: History::default(),
+        }
+    }
+
+    /// Open the search with `prefill`, e.g. the word at the cursor, or else
+    /// the last query, and the last replacement. The query starts out
+    /// selected, so typing replaces it.
+    pub fn activate(&mut self, prefill: Option<String>, buffer: &Buffer) {
+        self.is_active = true;
+        self.query.set_text(prefill.or_else(|| self.query_history.last().map(String::from)).unwrap_or_default());
+        self.query.select_all();
+        self.replace_text.set_text(self.replace_history.last().unwrap_or_default());
+        self.editing_replacement = false;
+        self.clear_matches();
+        self.scope = SearchScope::Document;
+        self.scope_range = None;
+        self.find_all_matches(buffer);
+    }
+
+    /// Add the query and, in replace mode, the replacement to the histories
+    pub fn remember(&mut self) {
+        self.query_history.push(self.query.text());
+        if self.is_replace_mode {
+            self.replace_history.push(self.replace_text.text());
+        }
+    }
+
+    /// The field typing goes to
+    pub fn focused_input(&mut self) -> &mut TextInput {
+        if self.editing_replacement {
+            &mut self.replace_text
+        } else {
+            &mut self.query
+        }
+    }
+
+    /// Type into the focused field
+    pub fn type_text(&mut self, text: &str, buffer: &Buffer) {
+        self.focused_input().insert(text);
+        self.edited(buffer);
+    }
+
+    /// Edit the focused field by a key, searching again if the query
+    /// changed; false if the field doesn't use the key
+    pub fn handle_key(&mut self, key_code: KeyCode, modifiers: ModifiersState, clipboard: &mut InputHandler, buffer: &Buffer) -> bool {
+        match self.focused_input().handle_key(key_code, modifiers, clipboard) {
+            InputResponse::Edited => self.edited(buffer),
+            InputResponse::Handled => {}
+            InputResponse::Ignored => return false,
+        }
+        true
+    }
+
+    /// The focused field was edited: stop browsing its history, and search
+    /// for a changed query
+    fn edited(&mut self, buffer: &Buffer) {
+        if self.editing_replacement {
+            self.replace_history.reset();
+        } else {
+            self.query_history.reset();
+            self.find_all_matches(buffer);
+        }
+    }
+
+    /// Show an older (Up) or newer (Down) entry of the focused field's history
+    pub fn recall(&mut self, older: bool, buffer: &Buffer) -> bool {
+        let (history, input) = if self.editing_replacement {
+            (&mut self.replace_history, &mut self.replace_text)
+        } else {
+            (&mut self.query_history, &mut self.query)
+        };
+        if !input.recall(history, older) {
+            return false;
+        }
+        if !self.editing_replacement {
+            self.find_all_matches(buffer);
+        }
+        true
+    }
+
+    /// Whether an option is on
+    pub fn option(&self, option: SearchOption) -> bool {
+        match option {
+            SearchOption::CaseSensitive => self.case_sensitive,
+            SearchOption::WholeWord => self.whole_word,
+            SearchOption::Regex => self.use_regex,
+            SearchOption::PreserveCase => self.preserve_case,
+        }
+    }
+
+    /// Turn an option on or off and search again
+    pub fn toggle_option(&mut self, option: SearchOption, buffer: &Buffer) {
+        let enabled = match option {
+            SearchOption::CaseSensitive => &mut self.case_sensitive,
+            SearchOption::WholeWord => &mut self.whole_word,
+            SearchOption::Regex => &mut self.use_regex,
+            SearchOption::PreserveCase => &mut self.preserve_case,
+        };
+        *enabled = !*enabled;
+        self.find_all_matches(buffer);
+    }
+
+    /// The find bar's counter: `3 of 12`, `No results` or why the query is
+    /// invalid
+    pub fn counter(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        if self.query.is_empty() {
+            return String::new();
+        }
+        let count = self.match_count();
+        let current = self.current_match_index.map_or("?".to_string(), |index| (index + 1).to_string());
+        if self.is_searching() {
+            format!("{} of {}…", current, count)
+        } else if count == 0 {
+            "No results".to_string()
+        } else if self.truncated {
+            format!("{} of {}+", current, count)
+        } else {
+            format!("{} of {}", current, count)
+        }
+    }
+
+    pub fn deactivate(&mut self) {
+        self.remember();
+        self.is_active = false;
+        self.is_replace_mode = false;
+        self.editing_replacement = false;
+        self.query.clear();
+        self.replace_text.clear();
+        self.clear_matches();
+        self.scope = SearchScope::Document;
+        self.scope_range = None;
+        self.error = None;
+    }
+
+    fn clear_matches(&mut self) {
+        self.matches.clear();
+        self.current_match_index = None;
+        self.truncated = false;
+        self.pattern = None;
+        self.searched = None;
+        self.job = None;
+    }
+
+    pub fn toggle_replace_mode(&mut self) {
+        self.is_replace_mode = !self.is_replace_mode;
+    }
+
+    pub fn update_query(&mut self, query: String, buffer: &Buffer) {
+        self.query.set_text(query);
+        self.find_all_matches(buffer);
+    }
+
+    /// The query as a regex: a pattern in regex mode, otherwise the literal
+    /// text. `^` and `$` match at line breaks, and patterns may span lines.
+    pub fn build_regex(&self) -> Result<Regex, regex::Error> {
+        build_regex(self.query.text(), self.case_sensitive, self.use_regex)
+    }
+
+    /// Search only the byte `range` of the buffer, or all of it for
+    /// `SearchScope::Document` or without a range
+    pub fn set_scope(&mut self, scope: SearchScope, range: Option<Range<usize>>, buffer: &Buffer) {
+        let rope = buffer.rope();
+        let range = range.filter(|_| scope != SearchScope::Document).map(|range| {
+            let to_char = |byte: usize| rope.byte_to_char(byte.min(rope.len_bytes()));
+            (to_char(range.start)..to_char(range.end), buffer.version())
+        });
+        self.scope = if range.is_some() { scope } else { SearchScope::Document };
+        self.scope_range = range;
+
+        // The previous matches may lie outside the new scope
+        self.searched = None;
+        self.find_all_matches(buffer);
+    }
+
+    /// Move the scope along with edits made since it was set
+    fn track_scope(&mut self, buffer: &Buffer) {
+        let Some((range, version)) = self.scope_range.as_mut() else {
+            return;
+        };
+        if *version == buffer.version() {
+            return;
+        }
+        // Edits that are no longer remembered leave the scope where it was
+        for edit in buffer.edits_since(*version).unwrap_or_default() {
+            // Text typed at either edge of the scope belongs to it
+            range.start = map_position(range.start, &edit, false);
+            range.end = map_position(range.end, &edit, true);
+        }
+        let len = buffer.rope().len_chars();
+        *range = range.start.min(len)..range.end.min(len);
+        *version = buffer.version();
+    }
+
+    /// Byte range searched
+    fn bounds(&self, buffer: &Buffer) -> Range<usize> {
+        let rope = buffer.rope();
+        match &self.scope_range {
+            Some((range, _)) => rope.char_to_byte(range.start)..rope.char_to_byte(range.end),
+            None => 0..rope.len_bytes(),
+        }
+    }
+
+    fn key(&self) -> SearchKey {
+        SearchKey {
+            query: self.query.text().to_string(),
+            case_sensitive: self.case_sensitive,
+            use_regex: self.use_regex,
+            whole_word: self.whole_word,
+        }
+    }
+
+    /// Search the buffer for the query. A literal query that grew only
+    /// rechecks the previous matches; large buffers are searched in the
+    /// background, with matches arriving through `poll`.
+    pub fn find_all_matches(&mut self, buffer: &Buffer) {
+        self.track_scope(buffer);
+        let complete = self.job.is_none() && !self.truncated;
+        let previous = self.searched.take();
+        let previous_matches = std::mem::take(&mut self.matches);
+        self.clear_matches();
+        self.error = None;
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        let regex = match self.build_regex() {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.error = Some(regex_error(&e));
+                return;
+            }
+        };
+        let pattern = Pattern { regex, whole_word: self.whole_word };
+        let key = self.key();
+        let rope = buffer.rope();
+        let bounds = self.bounds(buffer);
+
+        let narrows = previous.is_some_and(|(previous, version)| {
+            complete
+                && version == buffer.version()
+                && !previous.use_regex
+                && !previous.whole_word
+                && SearchKey { query: previous.query.clone(), ..key.clone() } == previous
+                && key.query.starts_with(&previous.query)
+        });
+        if narrows {
+            // Every match of the longer text starts where the shorter text
+            // matched. Matches don't overlap, so that can be anywhere inside
+            // a previous match, not only at its start.
+            let lines = 1 + self.query.text().matches('\n').count();
+            let mut end = 0;
+            for previous in &previous_matches {
+                let mut offset = previous.start_offset.max(end);
+                while offset < previous.end_offset {
+                    match pattern.match_at(rope, rope.byte_to_line(offset), offset, lines) {
+                        Some(found) if within(&bounds, &found) => {
+                            end = found.end_offset;
+                            offset = end;
+                            self.matches.push(found);
+                        }
+                        _ => offset = rope.char_to_byte(rope.byte_to_char(offset) + 1),
+                    }
+                }
+            }
+        } else if bounds.len() > BACKGROUND_THRESHOLD {
+            self.job = Some(SearchJob::spawn(rope.clone(), pattern.clone(), bounds, MAX_MATCHES));
+        } else {
+            let matches = &mut self.matches;
+            self.truncated = !stream::search_lines(rope, &pattern, scope_lines(rope, &bounds), None, |found| {
+                if within(&bounds, &found) {
+                    matches.push(found);
+                }
+                matches.len() < MAX_MATCHES
+            });
+        }
+        self.pattern = Some(pattern);
+        self.searched = Some((key, buffer.version()));
+
+        // Set current match to first one if any matches found
+        if !self.matches.is_empty() {
+            self.current_match_index = Some(0);
+        }
+    }
+
+    /// Take in matches from the background search and follow edits made to
+    /// the buffer since the matches were found. True when matches changed.
+    pub fn poll(&mut self, buffer: &Buffer) -> bool {
+        let Some((_, version)) = self.searched else {
+            return false;
+        };
+        self.track_scope(buffer);
+        if let Some(job) = &self.job {
+            if version != buffer.version() {
+                // The snapshot being searched is out of date
+                self.find_all_matches(buffer);
+                return true;
+            }
+            let (found, done) = job.poll();
+            let changed = !found.is_empty() || done;
+            self.matches.extend(found);
+            if self.matches.len() >= MAX_MATCHES {
+                self.matches.truncate(MAX_MATCHES);
+                self.truncated = true;
+                self.job = None;
+            } else if done {
+                self.job = None;
+            }
+            if self.current_match_index.is_none() && !self.matches.is_empty() {
+                self.current_match_index = Some(0);
+            }
+            return changed;
+        }
+        if version != buffer.version() {
+            self.update_after_edits(buffer);
+            return true;
+        }
+        false
+    }
+
+    /// True while a background search is running
+    pub fn is_searching(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// Bring the matches up to date with the buffer by searching again only
+    /// around the lines edited since they were found
+    fn update_after_edits(&mut self, buffer: &Buffer) {
+        let (Some(pattern), Some((key, version))) = (self.pattern.clone(), self.searched.clone()) else {
+            return;
+        };
+        let edits = match buffer.edits_since(version) {
+            Some(edits) if !self.truncated => edits,
+            _ => {
+                let index = self.current_match_index;
+                self.find_all_matches(buffer);
+                self.current_match_index = index.filter(|_| !self.matches.is_empty()).map(|i| i.min(self.matches.len() - 1));
+                return;
+            }
+        };
+
+        // Drop matches touching edited lines and move later ones, tracking
+        // which lines need searching again
+        let mut dirty: Option<(usize, usize)> = None;
+        for edit in &edits {
+            let mut first = edit.start_line;
+            self.matches.retain(|m| {
+                let keep = m.end_line < edit.start_line || m.start_line > edit.old_end_line;
+                if !keep {
+                    first = first.min(m.start_line);
+                }
+                keep
+            });
+            for m in self.matches.iter_mut().filter(|m| m.start_line > edit.old_end_line) {
+                m.start_line = shift_line(m.start_line, edit);
+                m.end_line = shift_line(m.end_line, edit);
+            }
+            dirty = Some(match dirty {
+                Some((start, end)) => (shift_line(start, edit).min(first), shift_line(end, edit).max(edit.new_end_line)),
+                None => (first, edit.new_end_line),
+            });
+        }
+
+        for m in &mut self.matches {
+            m.start_offset = buffer.position_to_offset(m.start_line, m.start_column);
+            m.end_offset = buffer.position_to_offset(m.end_line, m.end_column);
+        }
+
+        // Edits at the edges of the scope move it
+        let bounds = self.bounds(buffer);
+        self.matches.retain(|m| within(&bounds, m));
+
+        if let Some((start, end)) = dirty {
+            // Matches starting a little earlier may reach into the edited lines
+            let lines = start.saturating_sub(OVERLAP_LINES)..end + 1;
+            let at = self.matches.partition_point(|m| m.start_line < lines.start);
+            let after = self.matches.partition_point(|m| m.start_line < lines.end);
+            let mut rest = self.matches.split_off(after);
+            self.matches.truncate(at);
+
+            let mut last_end = self.matches.last().map_or(0, |m| m.end_offset);
+            let (rope, matches) = (buffer.rope(), &mut self.matches);
+            stream::search_lines(rope, &pattern, lines, None, |found| {
+                if found.start_offset >= last_end && within(&bounds, &found) {
+                    last_end = found.end_offset;
+                    matches.push(found);
+                }
+                true
+            });
+            rest.retain(|m| m.start_offset >= last_end);
+            self.matches.append(&mut rest);
+        }
+
+        if self.matches.len() > MAX_MATCHES {
+            self.matches.truncate(MAX_MATCHES);
+            self.truncated = true;
+        }
+        self.current_match_index = self
+            .current_match_index
+            .filter(|_| !self.matches.is_empty())
+            .map(|index| index.min(self.matches.len() - 1));
+        self.searched = Some((key, buffer.version()));
+    }
+
+    pub fn find_next(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        self.current_match_index = Some(match self.current_match_index {
+            Some(idx) => (idx + 1) % self.matches.len(),
+            None => 0,
+        });
+    }
+
+    /// Go to the first match starting after byte `offset`, wrapping around
+    pub fn find_after(&mut self, offset: usize) {
+        if !self.matches.is_empty() {
+            self.current_match_index = Some(self.matches.iter().position(|m| m.start_offset > offset).unwrap_or(0));
+        }
+    }
+
+    pub fn find_previous(&mut self) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        self.current_match_index = Some(match self.current_match_index {
+            Some(idx) => {
+                if idx == 0 {
+                    self.matches.len() - 1
+                } else {
+                    idx - 1
+                }
+            }
+            None => 0,
+        });
+    }
+
+    pub fn current_match(&self) -> Option<&SearchMatch> {
+        if let Some(idx) = self.current_match_index {
+            self.matches.get(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the current match and move on to the next one
+    pub fn replace_current(&mut self, buffer: &mut Buffer, cursor: &mut Cursor) -> bool {
+        let Some(match_item) = self.current_match().cloned() else {
+            return false;
+        };
+        let Some(replacement) = self.replacement_at(buffer, &match_item) else {
+            return false;
+        };
+
+        buffer.transaction(|buffer| {
+            replace_range(buffer, match_item.start_offset..match_item.end_offset, &replacement);
+        });
+
+        // Update cursor position
+        let end = match_item.start_offset + replacement.len();
+        let (line, column) = buffer.offset_to_position(end);
+        cursor.position = Position { line, column };
+        cursor.clear_selection();
+
+        // Update matches around the replacement, continuing after it
+        self.poll(buffer);
+        if !self.matches.is_empty() {
+            self.current_match_index = Some(self.matches.iter().position(|m| m.start_offset >= end).unwrap_or(0));
+        }
+
+        true
+    }
+
+    /// Replace every match in the scope as a single undo step, including
+    /// any not found yet by a background or capped search
+    pub fn replace_all(&mut self, buffer: &mut Buffer) -> usize {
+        let Some(pattern) = self.pattern.clone() else {
+            return 0;
+        };
+        let mut matches = Vec::new();
+        if self.job.is_some() || self.truncated {
+            self.track_scope(buffer);
+            let (rope, bounds) = (buffer.rope(), self.bounds(buffer));
+            stream::search_lines(rope, &pattern, scope_lines(rope, &bounds), None, |found| {
+                if within(&bounds, &found) {
+                    matches.push(found);
+                }
+                true
+            });
+        } else {
+            matches = self.matches.clone();
+        }
+        let replacements: Vec<(Range<usize>, String)> = matches
+            .iter()
+            .filter_map(|m| Some((m.start_offset..m.end_offset, self.replacement_at(buffer, m)?)))
+            .collect();
+
+        // Replace from end to beginning to maintain offsets
+        buffer.transaction(|buffer| {
+            for (range, replacement) in replacements.iter().rev() {
+                replace_range(buffer, range.clone(), replacement);
+            }
+        });
+
+        // Re-find matches
+        self.find_all_matches(buffer);
+
+        replacements.len()
+    }
+
+    /// Replacement for a match in the buffer: in regex mode `$1` and
+    /// `${name}` expand to captures and `\n` / `\t` to a line break / tab
+    fn replacement_at(&self, buffer: &Buffer, match_item: &SearchMatch) -> Option<String> {
+        // Only the lines of the match are needed, with the same context as the search
+        let lines = match_item.start_line..match_item.end_line + 1;
+        let (text, base) = lines_text(buffer.rope(), lines);
+        let start = match_item.start_offset.checked_sub(base)?;
+        let regex = &self.pattern.as_ref()?.regex;
+        expand_replacement(regex, &text, start, self.replace_text.text(), self.use_regex, self.preserve_case)
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The query as a regex: a pattern with `use_regex`, otherwise the
+/// literal text. `^` and `$` match at line breaks, and patterns may span lines.
+pub fn build_regex(query: &str, case_sensitive: bool, use_regex: bool) -> Result<Regex, regex::Error> {
+    let pattern = if use_regex { query.to_string() } else { regex::escape(query) };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .multi_line(true)
+        .build()
+}
+
+/// What's wrong with a pattern, in one line
+pub fn regex_error(error: &regex::Error) -> String {
+    // The last line of the message says what's wrong
+    error.to_string().lines().last().map_or_else(String::new, |line| line.trim().to_string())
+}
+
+/// Replacement for the match of `regex` at byte `start` of `text`. With
+/// `use_regex` `$1` and `${name}` expand to captures and `\n` / `\t` to a
+/// line break / tab; `preserve_case` gives it the case of the matched text.
+pub fn expand_replacement(
+    regex: &Regex,
+    text: &str,
+    start: usize,
+    template: &str,
+    use_regex: bool,
+    preserve_case: bool,
+) -> Option<String> {
+    let captures = regex
+        .captures_at(text, start)
+        .filter(|captures| captures.get(0).is_some_and(|m| m.start() == start))?;
+
+    let mut replacement = String::new();
+    if use_regex {
+        captures.expand(&unescape(template), &mut replacement);
+    } else {
+        replacement.push_str(template);
+    }
+    Some(if preserve_case {
+        self::preserve_case(&captures[0], &replacement)
+    } else {
+        replacement
+    })
+}
+
+/// Replace the byte `range` of the buffer
+pub fn replace_range(buffer: &mut Buffer, range: Range<usize>, replacement: &str) {
+    let rope = buffer.rope();
+    let (start, end) = (rope.byte_to_char(range.start), rope.byte_to_char(range.end));
+    if start < end {
+        buffer.delete_range(start..end);
+    }
+    if !replacement.is_empty() {
+        buffer.insert_text(start, replacement);
+    }
+}
+
+/// Lines a byte range of the rope is on
+fn scope_lines(rope: &ropey::Rope, bounds: &Range<usize>) -> Range<usize> {
+    rope.byte_to_line(bounds.start)..rope.byte_to_line(bounds.end) + 1
+}
+
+/// True when a match lies within the byte range searched
+fn within(bounds: &Range<usize>, found: &SearchMatch) -> bool {
+    found.start_offset >= bounds.start && found.end_offset <= bounds.end
+}
+
+/// Where char `position` ends up after `edit`. Positions inside deleted
+/// text go to its start; at an insertion point they stay before the new
+/// text, or move past it with `after_insert`.
+fn map_position(position: usize, edit: &Edit, after_insert: bool) -> usize {
+    if position < edit.start || position == edit.start && !after_insert {
+        position
+    } else if position >= edit.old_end {
+        position - edit.old_end + edit.new_end
+    } else {
+        edit.start
+    }
+}
+
+/// Where `line` ends up after `edit`; lines inside the edit go to its start
+fn shift_line(line: usize, edit: &Edit) -> usize {
+    if line > edit.old_end_line {
+        line + edit.new_end_line - edit.old_end_line
+    } else {
+        line.min(edit.start_line)
+    }
+}
+
+/// Expand `\n`, `\t` and `\\` in a replacement template
+fn unescape(template: &str) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('t')) => out.push('\t'),
+            ('\\', Some('\\')) => out.push('\\'),
+            _ => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+/// `replacement` in the case of `matched`: all upper, all lower or capitalized
+fn preserve_case(matched: &str, replacement: &str) -> String {
+    let has_upper = matched.chars().any(char::is_uppercase);
+    let has_lower = matched.chars().any(char::is_lowercase);
+    if has_upper && !has_lower && matched.chars().filter(|c| c.is_alphabetic()).count() > 1 {
+        replacement.to_uppercase()
+    } else if has_lower && !has_upper {
+        replacement.to_lowercase()
+    } else if matched.chars().next().is_some_and(char::is_uppercase) && !matched.chars().skip(1).any(char::is_uppercase) {
+        let mut chars = replacement.chars();
+        chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+    } else {
+        replacement.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(text: &str, query: &str, configure: impl FnOnce(&mut SearchState)) -> (SearchState, Buffer) {
+        let buffer = Buffer::from_text(text);
+        let mut state = SearchState::new();
+        state.query.set_text(query);
+        configure(&mut state);
+        state.find_all_matches(&buffer);
+        (state, buffer)
+    }
+
+    fn found<'a>(state: &SearchState, text: &'a str) -> Vec<&'a str> {
+        state.matches.iter().map(|m| &text[m.start_offset..m.end_offset]).collect()
+    }
+
+    #[test]
+    fn test_find_options() {
+        let text = "Straße STRASSE strasse";
+        let (state, _) = search(text, "strasse", |_| {});
+        assert_eq!(found(&state, text), ["STRASSE", "strasse"]);
+
+        let text = "foo food (foo) foo_bar";
+        let (state, _) = search(text, "foo", |s| s.whole_word = true);
+        assert_eq!(state.matches.iter().map(|m| m.start_offset).collect::<Vec<_>>(), [0, 10]);
+        let (state, _) = search(text, "(foo)", |s| s.whole_word = true);
+        assert_eq!(found(&state, text), ["(foo)"]);
+
+        let (state, _) = search("a(", "a(", |s| s.use_regex = true);
+        assert!(state.error.is_some() && state.matches.is_empty());
+
+        // Patterns can span lines, and `^` matches at every line start
+        let text = "fn a() {\n}\nfn b() {}\n";
+        let (state, _) = search(text, r"\{\n\}", |s| s.use_regex = true);
+        assert_eq!((state.matches[0].start_line, state.matches[0].end_line), (0, 1));
+        let (state, _) = search(text, "^fn", |s| s.use_regex = true);
+        assert_eq!(state.match_count(), 2);
+    }
+
+    #[test]
+    fn test_replace() {
+        let (mut state, mut buffer) = search("let width = 1;\nlet height = 2;\n", r"let (?<name>\w+) = (\d)", |s| {
+            s.use_regex = true;
+            s.replace_text.set_text("const ${name}: u8 = $2$2");
+        });
+        assert_eq!(state.replace_all(&mut buffer), 2);
+        assert_eq!(buffer.text(), "const width: u8 = 11;\nconst height: u8 = 22;\n");
+        buffer.undo();
+        assert_eq!(buffer.text(), "let width = 1;\nlet height = 2;\n");
+
+        let (mut state, mut buffer) = search("Item item ITEMS", "item", |s| {
+            s.preserve_case = true;
+            s.replace_text.set_text("entry");
+        });
+        let mut cursor = Cursor::new();
+        assert!(state.replace_current(&mut buffer, &mut cursor));
+        assert_eq!(state.current_match().map(|m| m.start_offset), Some(6));
+        state.replace_all(&mut buffer);
+        assert_eq!(buffer.text(), "Entry entry ENTRYS");
+    }
+
+    #[test]
+    fn test_incremental_search() {
+        let text: String = (0..300).map(|i| format!("item {} of {}\n", i, i % 7)).collect();
+        let (mut state, mut buffer) = search(&text, "item 1", |_| {});
+
+        // A growing query narrows the matches it already has
+        state.query.insert("2");
+        state.find_all_matches(&buffer);
+        let (fresh, _) = search(&text, "item 12", |_| {});
+        assert_eq!(state.matches, fresh.matches);
+
+        // Including where the longer text starts inside a shorter match
+        let (mut state, short) = search("aaab", "aa", |_| {});
+        state.query.insert("b");
+        state.find_all_matches(&short);
+        assert_eq!(found(&state, "aaab"), ["aab"]);
+        assert_eq!(state.matches, search("aaab", "aab", |_| {}).0.matches);
+
+        // Edits are followed by searching only around them
+        let mut state = search(&text, r"of 3\nitem", |s| s.use_regex = true).0;
+        buffer.transaction(|buffer| {
+            buffer.insert_text(buffer.position_to_offset(10, 0), "of 3\nitem\n");
+            buffer.delete_range(buffer.position_to_offset(200, 0)..buffer.position_to_offset(203, 0));
+        });
+        assert!(state.poll(&buffer));
+        let fresh = search(&buffer.text(), r"of 3\nitem", |s| s.use_regex = true).0;
+        assert_eq!(state.matches, fresh.matches);
+        assert!(!state.poll(&buffer));
+
+        let mut cursor = Cursor::new();
+        state.replace_text.set_text("-");
+        state.current_match_index = Some(5);
+        assert!(state.replace_current(&mut buffer, &mut cursor));
+        let fresh = search(&buffer.text(), r"of 3\nitem", |s| s.use_regex = true).0;
+        assert_eq!(state.matches, fresh.matches);
+        assert_eq!(state.current_match_index, Some(5));
+    }
+
+    #[test]
+    fn test_search_scope() {
+        let text = "a = 1\nfn f() {\n    a + a\n}\na\n";
+        let (mut state, mut buffer) = search(text, "a", |_| {});
+        assert_eq!(state.match_count(), 4);
+
+        let body = buffer.position_to_offset(1, 0)..buffer.position_to_offset(4, 0);
+        state.set_scope(SearchScope::Function, Some(body), &buffer);
+        assert_eq!(state.matches.iter().map(|m| m.start_line).collect::<Vec<_>>(), [2, 2]);
+
+        // The scope follows edits before and inside it
+        buffer.insert_text(0, "a\n");
+        buffer.insert_text(buffer.position_to_offset(3, 4), "a * ");
+        state.poll(&buffer);
+        assert_eq!(state.matches.iter().map(|m| m.start_line).collect::<Vec<_>>(), [3, 3, 3]);
+
+        state.replace_text.set_text("b");
+        assert_eq!(state.replace_all(&mut buffer), 3);
+        assert_eq!(buffer.text(), "a\na = 1\nfn f() {\n    b * b + b\n}\na\n");
+
+        state.set_scope(SearchScope::Document, None, &buffer);
+        assert_eq!(state.match_count(), 3);
+    }
+
+    #[test]
+    fn test_search_history() {
+        let text = "foo bar\nbar baz\n";
+        let buffer = Buffer::from_text(text);
+        let mut state = SearchState::new();
+
+        // A prefilled query is replaced by typing
+        state.activate(Some("bar".to_string()), &buffer);
+        assert_eq!((state.match_count(), state.query.selected_text()), (2, Some("bar")));
+        state.type_text("ba", &buffer);
+        assert_eq!((state.query.text(), state.match_count()), ("ba", 3));
+        state.deactivate();
+
+        // Without a prefill, find opens with the last query, also selected;
+        // Up and Down browse
+        state.activate(None, &buffer);
+        assert_eq!(state.query.text(), "ba");
+        state.type_text("baz", &buffer);
+        state.remember();
+        assert!(state.recall(true, &buffer));
+        assert!(state.recall(true, &buffer));
+        assert_eq!((state.query.text(), state.match_count()), ("ba", 3));
+        assert!(!state.recall(true, &buffer));
+        assert!(state.recall(false, &buffer));
+        assert_eq!((state.query.text(), state.match_count()), ("baz", 1));
+        assert_eq!(state.counter(), "1 of 1");
+    }
+}
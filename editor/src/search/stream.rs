@@ -0,0 +1,226 @@
+use regex::Regex;
+use ropey::Rope;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use super::SearchMatch;
+
+/// Lines copied out of the rope and searched at once
+const BLOCK_LINES: usize = 512;
+
+/// How many lines past its first a match may span. Blocks are searched
+/// with this many extra lines, so longer matches are missed.
+pub const OVERLAP_LINES: usize = 32;
+
+/// Matches sent from a background search at a time
+const BATCH_SIZE: usize = 256;
+
+/// A compiled query with the options checked after matching
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub regex: Regex,
+    pub whole_word: bool,
+}
+
+impl Pattern {
+    /// The match starting exactly at `offset`, which is on `line`, looking
+    /// at most `lines` lines ahead
+    pub fn match_at(&self, rope: &Rope, line: usize, offset: usize, lines: usize) -> Option<SearchMatch> {
+        let (text, base) = lines_text(rope, line..line + lines);
+        let start = offset.checked_sub(base).filter(|&start| start <= text.len())?;
+        let found = self.regex.find_at(&text, start)?;
+        let range = base + found.start()..base + found.end();
+        (range.start == offset && self.accepts(rope, &range)).then(|| search_match(rope, range))
+    }
+
+    fn accepts(&self, rope: &Rope, range: &Range<usize>) -> bool {
+        !self.whole_word || is_whole_word(rope, range)
+    }
+}
+
+/// Text of `lines` of the rope, which may run past its end, and the byte
+/// offset it starts at
+pub fn lines_text(rope: &Rope, lines: Range<usize>) -> (String, usize) {
+    let end = lines.end.min(rope.len_lines());
+    let start = lines.start.min(end);
+    let mut text = String::new();
+    for chunk in rope.slice(rope.line_to_char(start)..rope.line_to_char(end)).chunks() {
+        text.push_str(chunk);
+    }
+    (text, rope.line_to_byte(start))
+}
+
+/// Find the matches starting on `lines`, in order, copying only a block of
+/// lines at a time out of the rope. Blocks start at line starts, so `^` and
+/// `\b` see the same context as in the whole document. `on_match` returns
+/// false to stop early; the result is false when stopped or cancelled.
+pub fn search_lines(
+    rope: &Rope,
+    pattern: &Pattern,
+    lines: Range<usize>,
+    cancel: Option<&AtomicBool>,
+    mut on_match: impl FnMut(SearchMatch) -> bool,
+) -> bool {
+    let lines = lines.start..lines.end.min(rope.len_lines());
+    // Matches may run on into the next block; it mustn't find a match inside them
+    let mut last_end = 0;
+    let mut line = lines.start;
+    while line < lines.end {
+        if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return false;
+        }
+
+        let owned_end = (line + BLOCK_LINES).min(lines.end);
+        let owned_end_byte = if owned_end == rope.len_lines() { usize::MAX } else { rope.line_to_byte(owned_end) };
+        let (text, base) = lines_text(rope, line..owned_end + OVERLAP_LINES);
+        for found in pattern.regex.find_iter(&text) {
+            let range = base + found.start()..base + found.end();
+            if range.start >= owned_end_byte {
+                break;
+            }
+            if range.start < last_end || !pattern.accepts(rope, &range) {
+                continue;
+            }
+            last_end = range.end;
+            if !on_match(search_match(rope, range)) {
+                return false;
+            }
+        }
+        line = owned_end;
+    }
+    true
+}
+
+/// A search of a whole document on a background thread. Dropping it
+/// cancels the search.
+pub struct SearchJob {
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<Vec<SearchMatch>>,
+}
+
+impl SearchJob {
+    /// Search the byte range `bounds` of a snapshot of the rope for up to
+    /// `limit` matches
+    pub fn spawn(rope: Rope, pattern: Pattern, bounds: Range<usize>, limit: usize) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let thread_cancel = cancel.clone();
+        let spawned = thread::Builder::new().name("search".to_string()).spawn(move || {
+            let mut batch = Vec::new();
+            let mut count = 0;
+            let lines = rope.byte_to_line(bounds.start)..rope.byte_to_line(bounds.end) + 1;
+            search_lines(&rope, &pattern, lines, Some(&thread_cancel), |found| {
+                if found.start_offset < bounds.start || found.end_offset > bounds.end {
+                    return true;
+                }
+                batch.push(found);
+                count += 1;
+                if batch.len() >= BATCH_SIZE {
+                    // The job was dropped if nobody receives
+                    if sender.send(std::mem::take(&mut batch)).is_err() {
+                        return false;
+                    }
+                }
+                count < limit
+            });
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+        });
+        if let Err(e) = spawned {
+            log::error!("❌ Failed to start search thread: {}", e);
+        }
+
+        Self { cancel, receiver }
+    }
+
+    /// Matches found since the last call, and whether the search is over
+    pub fn poll(&self) -> (Vec<SearchMatch>, bool) {
+        let mut found = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => found.extend(batch),
+                Err(TryRecvError::Empty) => return (found, false),
+                Err(TryRecvError::Disconnected) => return (found, true),
+            }
+        }
+    }
+}
+
+impl Drop for SearchJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn search_match(rope: &Rope, range: Range<usize>) -> SearchMatch {
+    let position = |offset: usize| {
+        let line = rope.byte_to_line(offset);
+        (line, offset - rope.line_to_byte(line))
+    };
+    let (start_line, start_column) = position(range.start);
+    let (end_line, end_column) = position(range.end);
+    SearchMatch {
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+        start_offset: range.start,
+        end_offset: range.end,
+    }
+}
+
+/// True when a match neither starts nor ends inside a word
+fn is_whole_word(rope: &Rope, range: &Range<usize>) -> bool {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let char_at = |byte: usize| (byte < rope.len_bytes()).then(|| rope.char(rope.byte_to_char(byte)));
+    let char_before = |byte: usize| (byte > 0).then(|| rope.char(rope.byte_to_char(byte) - 1));
+
+    let starts_inside = is_word(char_before(range.start)) && is_word(char_at(range.start));
+    let ends_inside = range.end > range.start && is_word(char_before(range.end)) && is_word(char_at(range.end));
+    !starts_inside && !ends_inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_blocks() {
+        // Matches across block boundaries are found once, as in the whole text
+        let text: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let rope = Rope::from_str(&text);
+        let regex = Regex::new(r"(?m)1\nline \d*$").unwrap();
+        let expected: Vec<usize> = regex.find_iter(&text).map(|m| m.start()).collect();
+
+        let pattern = Pattern { regex, whole_word: false };
+        let mut found = Vec::new();
+        assert!(search_lines(&rope, &pattern, 0..rope.len_lines(), None, |m| {
+            found.push(m.start_offset);
+            true
+        }));
+        assert_eq!(found, expected);
+
+        // A background search delivers the same matches in batches
+        let job = SearchJob::spawn(rope.clone(), pattern.clone(), 0..rope.len_bytes(), usize::MAX);
+        let mut background = Vec::new();
+        loop {
+            let (batch, done) = job.poll();
+            background.extend(batch.iter().map(|m| m.start_offset));
+            if done {
+                break;
+            }
+            thread::yield_now();
+        }
+        assert_eq!(background, expected);
+
+        let pattern = Pattern { regex: Regex::new("1").unwrap(), whole_word: true };
+        let found = pattern.match_at(&rope, 1, rope.line_to_byte(1) + 5, 1).unwrap();
+        assert_eq!((found.start_line, found.start_column, found.end_column), (1, 5, 6));
+        assert!(pattern.match_at(&rope, 10, rope.line_to_byte(10) + 5, 1).is_none());
+    }
+}
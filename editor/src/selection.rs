@@ -0,0 +1,146 @@
+use std::ops::Range;
+use tree_sitter::{Node, Query, QueryCursor, StreamingIterator, Tree};
+
+/// Text objects offered by the command palette: label and `textobjects.scm` capture
+pub const TEXT_OBJECTS: &[(&str, &str)] = &[
+    ("Select Function", "function.outer"),
+    ("Select Function Body", "function.inner"),
+    ("Select Class", "class.outer"),
+    ("Select Class Body", "class.inner"),
+    ("Select Argument", "parameter.inner"),
+    ("Select Comment", "comment.outer"),
+];
+
+/// Byte range of the smallest syntax node that contains `range` and is
+/// larger than it: identifier → expression → statement → block → function
+pub fn expand(tree: &Tree, range: Range<usize>) -> Option<Range<usize>> {
+    let mut node = tree
+        .root_node()
+        .named_descendant_for_byte_range(range.start, range.end)?;
+
+    loop {
+        let node_range = node.byte_range();
+        if node_range.start <= range.start && node_range.end >= range.end && node_range != range {
+            return Some(node_range);
+        }
+        node = node.parent()?;
+    }
+}
+
+/// Byte range of the first named node inside `range`, the reverse of `expand`
+pub fn shrink(tree: &Tree, range: Range<usize>) -> Option<Range<usize>> {
+    if range.is_empty() {
+        return None;
+    }
+
+    let node = tree
+        .root_node()
+        .named_descendant_for_byte_range(range.start, range.end)?;
+    if node.byte_range() != range {
+        // Already smaller than the selection
+        return (node.start_byte() >= range.start && node.end_byte() <= range.end).then(|| node.byte_range());
+    }
+
+    let mut cursor = node.walk();
+    let child = node.named_children(&mut cursor).next();
+    child.map(|child| child.byte_range())
+}
+
+/// Byte range of the smallest `capture` text object around `range`, e.g.
+/// `function.outer`. A selection that already is such an object selects the
+/// enclosing one, so repeating the command grows the selection.
+pub fn text_object(tree: &Tree, query: &Query, source: &str, capture: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let capture_index = query.capture_index_for_name(capture)?;
+    let inner = capture.ends_with(".inner");
+
+    let mut nodes = Vec::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+    while let Some(m) = matches.next() {
+        nodes.extend(m.captures.iter().filter(|c| c.index == capture_index).map(|c| c.node));
+    }
+    nodes
+        .into_iter()
+        .map(|node| if inner { inside_brackets(node, source) } else { node.byte_range() })
+        .filter(|r| r.start <= range.start && r.end >= range.end && *r != range)
+        .min_by_key(|r| r.end - r.start)
+}
+
+/// Content of a `{ ... }`, `( ... )` or `[ ... ]` node without the brackets
+/// and surrounding whitespace; other nodes are returned whole
+fn inside_brackets(node: Node, source: &str) -> Range<usize> {
+    let (first, last) = match (node.child(0), node.child(node.child_count().saturating_sub(1))) {
+        (Some(first), Some(last)) if node.child_count() >= 2 => (first, last),
+        _ => return node.byte_range(),
+    };
+    if !matches!(first.kind(), "{" | "(" | "[") || !matches!(last.kind(), "}" | ")" | "]") {
+        return node.byte_range();
+    }
+
+    let content = &source[first.end_byte()..last.start_byte()];
+    let start = first.end_byte() + (content.len() - content.trim_start().len());
+    let end = last.start_byte() - (content.len() - content.trim_end().len());
+    start..end.max(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+
+    fn parse(language: &str, source: &str) -> (Tree, std::sync::Arc<crate::language::Language>) {
+        let registry = LanguageRegistry::builtin();
+        let language = registry.get(language).unwrap();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&language.grammar).unwrap();
+        (parser.parse(source, None).unwrap(), language)
+    }
+
+    #[test]
+    fn test_expand_and_shrink() {
+        let source = "fn main() {\n    let x = a + b;\n}\n";
+        let (tree, _) = parse("rust", source);
+        let at = |text: &str| {
+            let start = source.find(text).unwrap();
+            start..start + text.len()
+        };
+
+        let cursor = source.find("a +").unwrap();
+        let mut range = cursor..cursor;
+        let mut steps = Vec::new();
+        while let Some(next) = expand(&tree, range.clone()) {
+            steps.push(&source[next.clone()]);
+            range = next;
+        }
+        assert_eq!(steps[..4], ["a", "a + b", "let x = a + b;", "{\n    let x = a + b;\n}"]);
+        assert_eq!(range, 0..source.len());
+
+        assert_eq!(shrink(&tree, at("a + b")), Some(cursor..cursor + 1));
+        assert_eq!(shrink(&tree, cursor..cursor + 1), None);
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let source = "class A:\n    def f(self, x):\n        return x\n";
+        let (tree, language) = parse("python", source);
+        let query = language.query("textobjects").unwrap();
+        let cursor = source.find("return").unwrap();
+        let object = |capture, range: Range<usize>| text_object(&tree, query, source, capture, range).map(|r| &source[r]);
+
+        assert_eq!(object("function.inner", cursor..cursor), Some("return x"));
+        assert_eq!(object("function.outer", cursor..cursor), Some("def f(self, x):\n        return x"));
+        let param = source.find("x)").unwrap();
+        assert_eq!(object("parameter.inner", param..param), Some("x"));
+        assert_eq!(object("class.outer", cursor..cursor), Some(source.trim_end()));
+
+        // Braces are not part of a Rust function body
+        let source = "fn f() {\n    g(1, 2);\n}\n";
+        let (tree, language) = parse("rust", source);
+        let query = language.query("textobjects").unwrap();
+        let cursor = source.find('2').unwrap();
+        let range = text_object(&tree, query, source, "function.inner", cursor..cursor).unwrap();
+        assert_eq!(&source[range], "g(1, 2);");
+        let range = text_object(&tree, query, source, "parameter.inner", cursor..cursor).unwrap();
+        assert_eq!(&source[range], "2");
+    }
+}
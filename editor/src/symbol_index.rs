@@ -0,0 +1,408 @@
+use anyhow::Result;
+use notify::{RecursiveMode, Watcher};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::language::{grammar, LanguageRegistry};
+use crate::symbols::{self, Symbol, SymbolKind};
+use crate::workspace::WorkspaceRoot;
+
+/// Bump when the on-disk format changes; older indexes are rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// Larger files are skipped (generated code, bundles, ...)
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// File events are collected until the workspace has been quiet this long
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A symbol defined somewhere in the workspace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    /// Name of the enclosing symbol, e.g. the impl of a method
+    pub container: Option<String>,
+}
+
+impl IndexedSymbol {
+    pub fn label(&self) -> String {
+        format!("{} {}", self.kind.keyword(), self.name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileEntry {
+    /// Modification time in milliseconds and size when the file was indexed
+    modified: u64,
+    size: u64,
+    symbols: Vec<IndexedSymbol>,
+}
+
+/// Symbols of every indexed file. Files are indexed when their name or
+/// extension maps to a language with a `tags.scm` query.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexData {
+    version: u32,
+    files: HashMap<PathBuf, FileEntry>,
+}
+
+impl IndexData {
+    /// Load a saved index; a missing, corrupt or outdated index loads empty
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<IndexData>(&content).ok())
+            .filter(|data| data.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename, so a crash never leaves half an index behind
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string(&IndexData {
+            version: INDEX_VERSION,
+            files: self.files.clone(),
+        })?)?;
+        std::fs::rename(temp, path)?;
+        Ok(())
+    }
+
+    /// Bring the index in line with the files under `root`: index new and
+    /// changed files and forget deleted and excluded ones. Returns true if
+    /// anything changed.
+    pub fn scan(&mut self, root: &WorkspaceRoot, languages: &LanguageRegistry) -> bool {
+        let mut seen = HashSet::new();
+        let mut changed = false;
+        for path in workspace_files(root, &root.path) {
+            changed |= self.update_file(&path, languages);
+            seen.insert(path);
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| !path.starts_with(&root.path) || seen.contains(path));
+        changed || self.files.len() != before
+    }
+
+    /// Update the index after `path`, inside `root`, changed on disk: a file
+    /// is reindexed, a new directory indexed, and anything deleted dropped
+    pub fn update_path(&mut self, root: &WorkspaceRoot, path: &Path, languages: &LanguageRegistry) -> bool {
+        if path.is_dir() {
+            workspace_files(root, path).fold(false, |changed, file| self.update_file(&file, languages) | changed)
+        } else if path.is_file() {
+            self.update_file(path, languages)
+        } else {
+            let before = self.files.len();
+            self.files.retain(|indexed, _| !indexed.starts_with(path));
+            self.files.len() != before
+        }
+    }
+
+    /// Reindex one file if it changed since it was indexed
+    fn update_file(&mut self, path: &Path, languages: &LanguageRegistry) -> bool {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) if metadata.len() <= MAX_FILE_SIZE => metadata,
+            _ => return self.files.remove(path).is_some(),
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_millis() as u64);
+
+        if let Some(entry) = self.files.get(path) {
+            if entry.modified == modified && entry.size == metadata.len() {
+                return false;
+            }
+        }
+
+        match index_file(path, languages) {
+            Some(symbols) => {
+                self.files.insert(path.to_path_buf(), FileEntry {
+                    modified,
+                    size: metadata.len(),
+                    symbols,
+                });
+                true
+            }
+            None => self.files.remove(path).is_some(),
+        }
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &IndexedSymbol> {
+        self.files.values().flat_map(|entry| entry.symbols.iter())
+    }
+}
+
+/// Files under `dir`, a folder of `root`, skipping what `.gitignore` and
+/// `.ignore` files and the exclude globs leave out, and dotfiles unless the
+/// root shows them
+fn workspace_files(root: &WorkspaceRoot, dir: &Path) -> impl Iterator<Item = PathBuf> {
+    root.walker(dir)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+/// True if a walk of `root` reaches `path`: neither it nor a folder on the
+/// way is ignored, excluded or hidden. A walk that starts inside an ignored
+/// folder doesn't know it is ignored, so each folder on the way is listed;
+/// `listings` keeps them for the next path.
+fn is_walked(root: &WorkspaceRoot, path: &Path, listings: &mut HashMap<PathBuf, HashSet<PathBuf>>) -> bool {
+    let Ok(relative) = path.strip_prefix(&root.path) else {
+        return false;
+    };
+    let mut dir = root.path.clone();
+    for part in relative {
+        let child = dir.join(part);
+        let listed = listings.entry(dir.clone()).or_insert_with(|| {
+            let walker = root.walker(&dir).max_depth(Some(1)).build();
+            walker.filter_map(|entry| entry.ok()).map(|entry| entry.into_path()).collect()
+        });
+        if !listed.contains(&child) {
+            return false;
+        }
+        dir = child;
+    }
+    true
+}
+
+/// Parse a file and list its symbols; `None` if it has no language with a tags query
+fn index_file(path: &Path, languages: &LanguageRegistry) -> Option<Vec<IndexedSymbol>> {
+    let language = languages.detect(Some(path), "")?;
+    let query = language.query("tags")?;
+    let source = std::fs::read_to_string(path).ok()?;
+
+    let mut parser = tree_sitter::Parser::new();
+    grammar::set_language(&mut parser, &language.grammar).ok()?;
+    let tree = parser.parse(&source, None)?;
+
+    let mut indexed = Vec::new();
+    collect(&symbols::extract(&tree, query, &source), path, None, &mut indexed);
+    Some(indexed)
+}
+
+fn collect(symbols: &[Symbol], path: &Path, container: Option<&str>, out: &mut Vec<IndexedSymbol>) {
+    for symbol in symbols {
+        out.push(IndexedSymbol {
+            name: symbol.name.clone(),
+            kind: symbol.kind,
+            path: path.to_path_buf(),
+            line: symbol.line,
+            column: symbol.column,
+            container: container.map(str::to_string),
+        });
+        collect(&symbol.children, path, Some(&symbol.name), out);
+    }
+}
+
+/// Workspace-wide symbol index, one per root. Each is built on a background
+/// thread, saved to the cache directory and kept up to date from file
+/// system events.
+pub struct SymbolIndex {
+    roots: Vec<RootIndex>,
+}
+
+/// The symbol index of one root
+struct RootIndex {
+    data: Arc<RwLock<IndexData>>,
+    ready: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl RootIndex {
+    fn spawn(root: WorkspaceRoot, languages: Arc<LanguageRegistry>) -> Self {
+        let index = Self {
+            data: Arc::new(RwLock::new(IndexData::default())),
+            ready: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+
+        let data = index.data.clone();
+        let ready = index.ready.clone();
+        let stop = index.stop.clone();
+        let spawned = std::thread::Builder::new()
+            .name("symbol-index".into())
+            .spawn(move || {
+                if let Err(e) = run(&root, &languages, &data, &ready, &stop) {
+                    log::warn!("Symbol index stopped: {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start symbol indexer: {}", e);
+        }
+
+        index
+    }
+}
+
+impl Drop for RootIndex {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl SymbolIndex {
+    /// Start indexing each root in the background
+    pub fn spawn(roots: &[WorkspaceRoot], languages: Arc<LanguageRegistry>) -> Self {
+        let roots = roots.iter().map(|root| RootIndex::spawn(root.clone(), languages.clone())).collect();
+        Self { roots }
+    }
+
+    /// Where the index of `root` is saved (`<cache>/nexus/index/<hash>.json`)
+    pub fn index_path(root: &Path) -> Option<PathBuf> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        root.hash(&mut hasher);
+        dirs::cache_dir().map(|dir| dir.join("nexus").join("index").join(format!("{:016x}.json", hasher.finish())))
+    }
+
+    /// True once the first scan of every root finished
+    pub fn is_ready(&self) -> bool {
+        self.roots.iter().all(|root| root.ready.load(Ordering::Relaxed))
+    }
+
+    /// Symbols of every root that pass `filter`
+    fn symbols(&self, filter: impl Fn(&IndexedSymbol) -> bool) -> Vec<IndexedSymbol> {
+        self.roots.iter().flat_map(|root| root.data.read().symbols().filter(|s| filter(s)).cloned().collect::<Vec<_>>()).collect()
+    }
+
+    /// Every symbol, sorted by name
+    pub fn all_symbols(&self) -> Vec<IndexedSymbol> {
+        let mut symbols = self.symbols(|_| true);
+        symbols.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+        symbols
+    }
+
+    /// Symbols named exactly `name`, for go-to-definition
+    pub fn definitions(&self, name: &str) -> Vec<IndexedSymbol> {
+        let mut definitions = self.symbols(|symbol| symbol.name == name && symbol.kind != SymbolKind::Impl);
+        definitions.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        definitions
+    }
+}
+
+/// Indexer thread: load the saved index, rescan the workspace, then follow
+/// file events until the index is dropped
+fn run(
+    root: &WorkspaceRoot,
+    languages: &LanguageRegistry,
+    data: &RwLock<IndexData>,
+    ready: &AtomicBool,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let index_path = SymbolIndex::index_path(&root.path);
+    let save = |data: &IndexData| {
+        if let Some(ref path) = index_path {
+            if let Err(e) = data.save(path) {
+                log::warn!("Failed to save symbol index: {}", e);
+            }
+        }
+    };
+
+    // Scan into a local copy so lookups aren't blocked meanwhile
+    let mut scanned = index_path.as_deref().map(IndexData::load).unwrap_or_default();
+    if scanned.scan(root, languages) {
+        save(&scanned);
+    }
+    let count = scanned.symbols().count();
+    *data.write() = scanned;
+    ready.store(true, Ordering::Relaxed);
+    log::info!("🔎 Indexed {} symbols in {}", count, root.path.display());
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = sender.send(event.paths);
+        }
+    })?;
+    watcher.watch(&root.path, RecursiveMode::Recursive)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut changed_paths: HashSet<PathBuf> = match receiver.recv_timeout(Duration::from_millis(500)) {
+            Ok(paths) => paths.into_iter().collect(),
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        while let Ok(paths) = receiver.recv_timeout(DEBOUNCE) {
+            changed_paths.extend(paths);
+        }
+
+        let mut data = data.write();
+        let mut changed = false;
+        let mut listings = HashMap::new();
+        for path in changed_paths {
+            // Deleted paths are dropped whether they were indexed or not
+            if !path.exists() || is_walked(root, &path, &mut listings) {
+                changed |= data.update_path(root, &path, languages);
+            }
+        }
+        if changed {
+            save(&data);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_workspace() {
+        let root = std::env::temp_dir().join("nexus_test_symbol_index");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub struct Point;\nimpl Point {\n    fn norm(&self) {}\n}\n").unwrap();
+        std::fs::write(root.join("app.py"), "def norm(v):\n    pass\n").unwrap();
+        std::fs::write(root.join("target/gen.rs"), "fn generated() {}\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "fn not_code() {}\n").unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+
+        let languages = LanguageRegistry::builtin();
+        let mut data = IndexData::default();
+        let workspace_root = WorkspaceRoot::new(root.clone());
+        assert!(data.scan(&workspace_root, &languages));
+
+        let mut names: Vec<String> = data.symbols().map(|s| s.label()).collect();
+        names.sort();
+        assert_eq!(names, ["fn norm", "impl Point", "method norm", "struct Point"]);
+        let method = data.symbols().find(|s| s.kind == SymbolKind::Method).unwrap();
+        assert_eq!((method.line, method.container.as_deref()), (2, Some("Point")));
+
+        // Changes inside ignored folders are left alone
+        let mut listings = HashMap::new();
+        assert!(is_walked(&workspace_root, &root.join("src/lib.rs"), &mut listings));
+        assert!(!is_walked(&workspace_root, &root.join("target/gen.rs"), &mut listings));
+        assert!(!is_walked(&workspace_root, &root.join(".gitignore"), &mut listings));
+
+        // Unchanged files are not reparsed; the saved index round-trips
+        assert!(!data.scan(&workspace_root, &languages));
+        let saved = root.join("index.json");
+        data.save(&saved).unwrap();
+        assert_eq!(IndexData::load(&saved).symbols().count(), 4);
+
+        // Deleted files and directories drop out
+        std::fs::remove_file(root.join("app.py")).unwrap();
+        assert!(data.update_path(&workspace_root, &root.join("app.py"), &languages));
+        std::fs::remove_dir_all(root.join("src")).unwrap();
+        assert!(data.update_path(&workspace_root, &root.join("src"), &languages));
+        assert_eq!(data.symbols().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use tree_sitter::{Query, QueryCursor, StreamingIterator, Tree};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Module,
+    Class,
+    Struct,
+    Enum,
+    Interface,
+    Trait,
+    Impl,
+    Function,
+    Method,
+    Macro,
+    Constant,
+    Type,
+}
+
+impl SymbolKind {
+    /// Kind named by a `@definition.<kind>` capture
+    pub fn from_capture(name: &str) -> Option<Self> {
+        Some(match name.strip_prefix("definition.")? {
+            "module" | "namespace" => Self::Module,
+            "class" => Self::Class,
+            "struct" => Self::Struct,
+            "enum" => Self::Enum,
+            "interface" => Self::Interface,
+            "trait" => Self::Trait,
+            "impl" => Self::Impl,
+            "function" => Self::Function,
+            "method" => Self::Method,
+            "macro" => Self::Macro,
+            "constant" => Self::Constant,
+            "type" => Self::Type,
+            _ => return None,
+        })
+    }
+
+    /// Word shown before the symbol's name in the outline
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Module => "mod",
+            Self::Class => "class",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Interface => "interface",
+            Self::Trait => "trait",
+            Self::Impl => "impl",
+            Self::Function => "fn",
+            Self::Method => "method",
+            Self::Macro => "macro",
+            Self::Constant => "const",
+            Self::Type => "type",
+        }
+    }
+
+    /// Kinds whose functions are methods
+    fn has_methods(&self) -> bool {
+        matches!(self, Self::Class | Self::Interface | Self::Trait | Self::Impl)
+    }
+}
+
+/// A definition found by a language's `tags.scm`, with the definitions nested in it
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Byte range of the whole definition
+    pub range: Range<usize>,
+    /// Line and byte column of the name, where go-to-symbol moves the cursor
+    pub line: usize,
+    pub column: usize,
+    pub children: Vec<Symbol>,
+}
+
+impl Symbol {
+    /// `fn main`, `impl Display for Point`, ...
+    pub fn label(&self) -> String {
+        format!("{} {}", self.kind.keyword(), self.name)
+    }
+}
+
+/// Symbols defined in a document, as a tree following their nesting. Uses
+/// the `@name` and `@definition.<kind>` captures of a `tags.scm` query;
+/// functions inside classes, traits and impls become methods.
+pub fn extract(tree: &Tree, query: &Query, source: &str) -> Vec<Symbol> {
+    let name_index = match query.capture_index_for_name("name") {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let kinds: Vec<Option<SymbolKind>> = query
+        .capture_names()
+        .iter()
+        .map(|name| SymbolKind::from_capture(name))
+        .collect();
+
+    let mut found = Vec::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+    while let Some(match_item) = matches.next() {
+        let name = match_item.captures.iter().find(|c| c.index == name_index);
+        let definition = match_item
+            .captures
+            .iter()
+            .find_map(|c| kinds[c.index as usize].map(|kind| (c.node, kind)));
+
+        if let (Some(name), Some((node, kind))) = (name, definition) {
+            let mut text = source[name.node.byte_range()].to_string();
+            if let Some(trait_node) = node.child_by_field_name("trait") {
+                // `impl Display for Point`
+                text = format!("{} for {}", &source[trait_node.byte_range()], text);
+            }
+
+            found.push(Symbol {
+                name: text,
+                kind,
+                range: node.byte_range(),
+                line: name.node.start_position().row,
+                column: name.node.start_position().column,
+                children: Vec::new(),
+            });
+        }
+    }
+
+    // Outer definitions first, so each symbol is nested into the one before it
+    found.sort_by_key(|s| (s.range.start, std::cmp::Reverse(s.range.end)));
+    found.dedup_by(|a, b| a.range == b.range);
+
+    let mut symbols = Vec::new();
+    for symbol in found {
+        insert(&mut symbols, symbol, None);
+    }
+    symbols
+}
+
+fn insert(symbols: &mut Vec<Symbol>, mut symbol: Symbol, parent: Option<SymbolKind>) {
+    if let Some(last) = symbols.last_mut() {
+        if last.range.start <= symbol.range.start && symbol.range.end <= last.range.end {
+            let kind = last.kind;
+            insert(&mut last.children, symbol, Some(kind));
+            return;
+        }
+    }
+
+    if symbol.kind == SymbolKind::Function && parent.is_some_and(|kind| kind.has_methods()) {
+        symbol.kind = SymbolKind::Method;
+    }
+    symbols.push(symbol);
+}
+
+/// Symbols in outline order with their nesting depth
+pub fn flatten(symbols: &[Symbol]) -> Vec<(usize, &Symbol)> {
+    fn walk<'a>(symbols: &'a [Symbol], depth: usize, out: &mut Vec<(usize, &'a Symbol)>) {
+        for symbol in symbols {
+            out.push((depth, symbol));
+            walk(&symbol.children, depth + 1, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    walk(symbols, 0, &mut out);
+    out
+}
+
+/// Symbols enclosing a byte offset, outermost first: the breadcrumbs
+pub fn path_at(symbols: &[Symbol], offset: usize) -> Vec<&Symbol> {
+    let mut path = Vec::new();
+    let mut level = symbols;
+    while let Some(symbol) = level.iter().find(|s| s.range.start <= offset && offset < s.range.end) {
+        path.push(symbol);
+        level = &symbol.children;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+
+    fn symbols(language: &str, source: &str) -> Vec<Symbol> {
+        let registry = LanguageRegistry::builtin();
+        let language = registry.get(language).unwrap();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&language.grammar).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        extract(&tree, language.query("tags").unwrap(), source)
+    }
+
+    fn outline(symbols: &[Symbol]) -> Vec<String> {
+        flatten(symbols)
+            .into_iter()
+            .map(|(depth, s)| format!("{}{}", "  ".repeat(depth), s.label()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_outline() {
+        let source = "mod shapes {\n    pub struct Point;\n    impl std::fmt::Display for Point {\n        fn fmt(&self) {}\n    }\n}\nfn main() {\n    fn helper() {}\n}\n";
+        let symbols = symbols("rust", source);
+
+        assert_eq!(
+            outline(&symbols),
+            [
+                "mod shapes",
+                "  struct Point",
+                "  impl std::fmt::Display for Point",
+                "    method fmt",
+                "fn main",
+                "  fn helper",
+            ]
+        );
+        assert_eq!((symbols[1].line, symbols[1].column), (6, 3));
+
+        let offset = source.find("{}").unwrap();
+        let path: Vec<String> = path_at(&symbols, offset).iter().map(|s| s.name.clone()).collect();
+        assert_eq!(path, ["shapes", "std::fmt::Display for Point", "fmt"]);
+        assert!(path_at(&symbols, source.len()).is_empty());
+    }
+
+    #[test]
+    fn test_other_outlines() {
+        let python = "MAX = 3\nclass A:\n    def f(self):\n        pass\n";
+        assert_eq!(outline(&symbols("python", python)), ["const MAX", "class A", "  method f"]);
+
+        let typescript = "interface Shape { area(): number; }\nconst draw = () => {};\n";
+        assert_eq!(
+            outline(&symbols("typescript", typescript)),
+            ["interface Shape", "  method area", "fn draw"]
+        );
+    }
+}
@@ -1,157 +1,705 @@
 use anyhow::Result;
-use tree_sitter::{Language, Parser, Query, QueryCursor};
-
-extern "C" {
-    fn tree_sitter_rust() -> Language;
-    fn tree_sitter_javascript() -> Language;
-    fn tree_sitter_typescript() -> Language;
-    fn tree_sitter_python() -> Language;
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum TokenType {
-    Keyword,
-    Function,
-    Type,
-    String,
-    Number,
-    Comment,
-    Operator,
-    Variable,
-    Property,
-    Parameter,
-    Constant,
-    Normal,
-}
-
-impl TokenType {
-    pub fn color(&self) -> [f32; 4] {
-        match self {
-            TokenType::Keyword => [0.86, 0.20, 0.18, 1.0],      // Red
-            TokenType::Function => [0.38, 0.51, 0.71, 1.0],     // Blue
-            TokenType::Type => [0.27, 0.52, 0.53, 1.0],         // Cyan
-            TokenType::String => [0.60, 0.76, 0.29, 1.0],       // Green
-            TokenType::Number => [0.83, 0.60, 0.45, 1.0],       // Orange
-            TokenType::Comment => [0.50, 0.50, 0.50, 1.0],      // Gray
-            TokenType::Operator => [0.80, 0.80, 0.80, 1.0],     // Light gray
-            TokenType::Variable => [0.90, 0.90, 0.90, 1.0],     // White
-            TokenType::Property => [0.78, 0.67, 0.84, 1.0],     // Purple
-            TokenType::Parameter => [0.85, 0.85, 0.70, 1.0],    // Yellow-ish
-            TokenType::Constant => [0.83, 0.60, 0.45, 1.0],     // Orange
-            TokenType::Normal => [1.0, 1.0, 1.0, 1.0],          // White
-        }
-    }
-}
+use std::collections::HashMap;
+use std::sync::Arc;
+use tree_sitter::{Node, Parser, Point, Query, QueryCursor, QueryMatch, QueryPredicateArg, Range, StreamingIterator, Tree};
+
+use crate::language::{grammar, Language, LanguageRegistry};
+use crate::theme::Theme;
+
+/// Priority of patterns without a `(#set! priority N)` property
+const DEFAULT_PRIORITY: u32 = 100;
+
+/// Injected languages nest at most this deep (Markdown → JS → regex, ...)
+const MAX_INJECTION_DEPTH: usize = 4;
 
+/// A highlighted byte range of the source
 #[derive(Debug, Clone)]
 pub struct Token {
     pub start: usize,
     pub end: usize,
-    pub token_type: TokenType,
+    /// Hierarchical capture name, e.g. `function.macro` or `type.builtin`
+    pub scope: Arc<str>,
+}
+
+/// A raw query capture before overlaps are resolved
+#[derive(Debug, Clone, Copy)]
+struct HighlightCapture {
+    start: usize,
+    end: usize,
+    /// Injection depth of the layer the capture comes from; 0 is the host document
+    depth: usize,
+    priority: u32,
+    pattern_index: usize,
+    /// Index into the scope table of the current `highlight` call
+    capture_index: usize,
+}
+
+impl HighlightCapture {
+    /// Precedence for overlapping captures: injected layers win over their
+    /// host, then higher priority, then the innermost (shortest) node, then
+    /// the pattern listed first in the query
+    fn precedence(&self) -> (usize, u32, std::cmp::Reverse<usize>, std::cmp::Reverse<usize>) {
+        (
+            self.depth,
+            self.priority,
+            std::cmp::Reverse(self.end - self.start),
+            std::cmp::Reverse(self.pattern_index),
+        )
+    }
+}
+
+/// Highlight and injection queries of one language
+struct HighlightConfig {
+    language: Arc<Language>,
+    /// Capture names of `query`, shared with the tokens that use them
+    scopes: Vec<Arc<str>>,
+    /// `priority` property of each pattern in `query`
+    priorities: Vec<u32>,
+}
+
+impl HighlightConfig {
+    fn new(language: Arc<Language>) -> Self {
+        let query = language.query("highlights");
+
+        let scopes = query
+            .as_ref()
+            .map(|q| q.capture_names().iter().map(|name| Arc::from(*name)).collect())
+            .unwrap_or_default();
+        let priorities = query
+            .as_ref()
+            .map(|q| (0..q.pattern_count()).map(|i| pattern_priority(q, i)).collect())
+            .unwrap_or_default();
+
+        Self {
+            language,
+            scopes,
+            priorities,
+        }
+    }
+
+    fn query(&self) -> Option<&Query> {
+        self.language.query("highlights")
+    }
+
+    fn injections(&self) -> Option<&Query> {
+        self.language.query("injections")
+    }
+}
+
+/// A region of the document written in another language
+struct Injection {
+    language: String,
+    ranges: Vec<Range>,
+}
+
+/// State shared by all injection layers of one `highlight` call
+struct LayerContext<'a> {
+    source: &'a str,
+    languages: &'a LanguageRegistry,
+    /// Parser reused for every injected layer
+    parser: &'a mut Parser,
+    /// Configs of injected languages, `None` if the language is unknown
+    configs: &'a mut HashMap<String, Option<Arc<HighlightConfig>>>,
+    /// Scope names referenced by `HighlightCapture::capture_index`
+    scopes: Vec<Arc<str>>,
+    captures: Vec<HighlightCapture>,
+    /// Injected layers found so far, outer layers before the ones inside them
+    layers: Vec<(Arc<Language>, Vec<Range>)>,
 }
 
 pub struct SyntaxHighlighter {
     parser: Parser,
-    language: Language,
-    query: Option<Query>,
+    config: Arc<HighlightConfig>,
+    /// Registry injected languages are looked up in
+    languages: Arc<LanguageRegistry>,
+    injection_parser: Parser,
+    injection_configs: HashMap<String, Option<Arc<HighlightConfig>>>,
+    /// Tree from the last `highlight` call
+    tree: Option<Tree>,
+    /// Injected languages of the last `highlight` call and the ranges they cover
+    layers: Vec<(Arc<Language>, Vec<Range>)>,
+}
+
+impl std::fmt::Debug for SyntaxHighlighter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyntaxHighlighter")
+            .field("language", &self.config.language.name())
+            .finish()
+    }
 }
 
 impl SyntaxHighlighter {
-    pub fn new(file_extension: &str) -> Result<Self> {
+    pub fn new(language: Arc<Language>, languages: Arc<LanguageRegistry>) -> Result<Self> {
         let mut parser = Parser::new();
+        grammar::set_language(&mut parser, &language.grammar)?;
 
-        let language = unsafe {
-            match file_extension {
-                "rs" => tree_sitter_rust(),
-                "js" | "jsx" => tree_sitter_javascript(),
-                "ts" | "tsx" => tree_sitter_typescript(),
-                "py" => tree_sitter_python(),
-                _ => tree_sitter_rust(), // Default to Rust
-            }
-        };
+        Ok(Self {
+            parser,
+            config: Arc::new(HighlightConfig::new(language)),
+            languages,
+            injection_parser: Parser::new(),
+            injection_configs: HashMap::new(),
+            tree: None,
+            layers: Vec::new(),
+        })
+    }
 
-        parser.set_language(language)?;
+    pub fn language(&self) -> &Arc<Language> {
+        &self.config.language
+    }
+
+    /// Parse tree of the source last passed to `highlight`
+    pub fn tree(&self) -> Option<&Tree> {
+        self.tree.as_ref()
+    }
 
-        // Create highlighting query based on language
-        let query_source = match file_extension {
-            "rs" => include_str!("../queries/rust.scm"),
-            "js" | "jsx" => include_str!("../queries/javascript.scm"),
-            "ts" | "tsx" => include_str!("../queries/typescript.scm"),
-            "py" => include_str!("../queries/python.scm"),
-            _ => include_str!("../queries/rust.scm"),
+    /// Language at a byte offset: the innermost injected language covering
+    /// it, such as JavaScript in an HTML `<script>`, else the document's own
+    pub fn language_at(&self, offset: usize) -> &Arc<Language> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|(_, ranges)| ranges.iter().any(|r| r.start_byte <= offset && offset <= r.end_byte))
+            .map_or(&self.config.language, |(language, _)| language)
+    }
+
+    /// Parse `source_code` and return its highlighted spans, including the
+    /// spans of languages injected into it
+    pub fn highlight(&mut self, source_code: &str) -> Vec<Token> {
+        self.tree = self.parser.parse(source_code, None);
+        let tree = match self.tree {
+            Some(ref tree) => tree,
+            None => return vec![],
         };
 
-        let query = Query::new(language, query_source).ok();
+        let mut context = LayerContext {
+            source: source_code,
+            languages: &self.languages,
+            parser: &mut self.injection_parser,
+            configs: &mut self.injection_configs,
+            scopes: Vec::new(),
+            captures: Vec::new(),
+            layers: Vec::new(),
+        };
+        highlight_layer(&mut context, &self.config, tree, 0, &[]);
 
-        Ok(Self {
-            parser,
-            language,
-            query,
-        })
+        self.layers = context.layers;
+        let scopes = context.scopes;
+        resolve_overlaps(context.captures)
+            .into_iter()
+            .map(|(start, end, capture_index)| Token {
+                start,
+                end,
+                scope: scopes[capture_index].clone(),
+            })
+            .collect()
     }
 
-    pub fn highlight(&mut self, source_code: &str) -> Vec<Token> {
-        let tree = match self.parser.parse(source_code, None) {
+    pub fn get_color_at_offset(&self, offset: usize, tokens: &[Token], theme: &Theme) -> [f32; 4] {
+        for token in tokens {
+            if offset >= token.start && offset < token.end {
+                return theme.color(&token.scope);
+            }
+        }
+        theme.ui.foreground
+    }
+}
+
+/// Collect the highlights of one layer, then recurse into the languages it
+/// injects. `ranges` limits an injected layer to its part of the document.
+fn highlight_layer(
+    context: &mut LayerContext,
+    config: &HighlightConfig,
+    tree: &Tree,
+    depth: usize,
+    ranges: &[Range],
+) {
+    let source = context.source;
+
+    if let Some(query) = config.query() {
+        let scope_base = context.scopes.len();
+        context.scopes.extend(config.scopes.iter().cloned());
+
+        // `#eq?` and `#match?` are applied by the cursor; `#any-of?` is checked here
+        let mut cursor = QueryCursor::new();
+        let mut captures = cursor.captures(query, tree.root_node(), source.as_bytes());
+        while let Some((match_item, capture_index)) = captures.next() {
+            if !satisfies_general_predicates(query, match_item, source) {
+                continue;
+            }
+
+            let capture = match_item.captures[*capture_index];
+            let index = capture.index as usize;
+
+            // Captures starting with `_` are helpers for predicates, not highlights
+            if config.scopes[index].starts_with('_') {
+                continue;
+            }
+
+            let highlight = HighlightCapture {
+                start: capture.node.start_byte(),
+                end: capture.node.end_byte(),
+                depth,
+                priority: config.priorities[match_item.pattern_index],
+                pattern_index: match_item.pattern_index,
+                capture_index: scope_base + index,
+            };
+
+            if ranges.is_empty() {
+                context.captures.push(highlight);
+            } else {
+                // Nodes of an injected tree can span the gaps between its ranges
+                for range in ranges {
+                    let start = highlight.start.max(range.start_byte);
+                    let end = highlight.end.min(range.end_byte);
+                    if start < end {
+                        context.captures.push(HighlightCapture { start, end, ..highlight });
+                    }
+                }
+            }
+        }
+    }
+
+    if depth >= MAX_INJECTION_DEPTH {
+        return;
+    }
+
+    for injection in find_injections(config, tree, source, ranges) {
+        let config = match injection_config(context, &injection.language) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        if grammar::set_language(context.parser, &config.language.grammar).is_err()
+            || context.parser.set_included_ranges(&injection.ranges).is_err()
+        {
+            continue;
+        }
+        let injected_tree = match context.parser.parse(source, None) {
             Some(tree) => tree,
-            None => return vec![],
+            None => continue,
         };
 
-        let mut tokens = Vec::new();
-
-        if let Some(ref query) = self.query {
-            let mut cursor = QueryCursor::new();
-            let captures = cursor.captures(query, tree.root_node(), source_code.as_bytes());
-
-            for (match_item, capture_index) in captures {
-                let capture = match_item.captures[capture_index];
-                let capture_name = &query.capture_names()[capture.index as usize];
-
-                let token_type = match capture_name.as_str() {
-                    "keyword" => TokenType::Keyword,
-                    "function" | "function.call" => TokenType::Function,
-                    "type" | "type.builtin" => TokenType::Type,
-                    "string" => TokenType::String,
-                    "number" => TokenType::Number,
-                    "comment" => TokenType::Comment,
-                    "operator" => TokenType::Operator,
-                    "variable" => TokenType::Variable,
-                    "property" => TokenType::Property,
-                    "parameter" => TokenType::Parameter,
-                    "constant" | "constant.builtin" => TokenType::Constant,
-                    _ => TokenType::Normal,
-                };
-
-                tokens.push(Token {
-                    start: capture.node.start_byte(),
-                    end: capture.node.end_byte(),
-                    token_type,
+        context.layers.push((config.language.clone(), injection.ranges.clone()));
+        highlight_layer(context, &config, &injected_tree, depth + 1, &injection.ranges);
+    }
+}
+
+/// Look up (and cache) the config of an injected language by name
+fn injection_config(context: &mut LayerContext, name: &str) -> Option<Arc<HighlightConfig>> {
+    let languages = context.languages;
+    context
+        .configs
+        .entry(name.to_string())
+        .or_insert_with(|| {
+            languages
+                .for_injection(name)
+                .map(|language| Arc::new(HighlightConfig::new(language)))
+        })
+        .clone()
+}
+
+/// Run a layer's injection query. Supports `@injection.content`,
+/// `@injection.language` or `(#set! injection.language "...")`,
+/// `(#set! injection.combined)`, `(#set! injection.include-children)` and
+/// `(#offset! @injection.content start_row start_col end_row end_col)`.
+fn find_injections(config: &HighlightConfig, tree: &Tree, source: &str, parent_ranges: &[Range]) -> Vec<Injection> {
+    let query = match config.injections() {
+        Some(query) => query,
+        None => return Vec::new(),
+    };
+
+    let content_index = query.capture_index_for_name("injection.content");
+    let language_index = query.capture_index_for_name("injection.language");
+
+    let mut injections: Vec<Injection> = Vec::new();
+    // Combined injections of the same pattern and language share one layer
+    let mut combined: HashMap<(usize, String), usize> = HashMap::new();
+
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+    while let Some(match_item) = matches.next() {
+        if !satisfies_general_predicates(query, match_item, source) {
+            continue;
+        }
+
+        let mut language = None;
+        let mut is_combined = false;
+        let mut include_children = false;
+        for property in query.property_settings(match_item.pattern_index) {
+            match &*property.key {
+                "injection.language" => language = property.value.as_deref().map(str::to_string),
+                "injection.combined" => is_combined = true,
+                "injection.include-children" => include_children = true,
+                _ => {}
+            }
+        }
+
+        if let Some(index) = language_index {
+            if let Some(capture) = match_item.captures.iter().find(|c| c.index == index) {
+                language = capture.node.utf8_text(source.as_bytes()).ok().map(str::to_string);
+            }
+        }
+
+        let language = match language {
+            Some(language) if !language.trim().is_empty() => language.trim().to_string(),
+            _ => continue,
+        };
+
+        let offset = content_offset(query, match_item, content_index);
+        let mut ranges: Vec<Range> = match_item
+            .captures
+            .iter()
+            .filter(|c| Some(c.index) == content_index)
+            .flat_map(|c| content_ranges(c.node, include_children, offset))
+            .collect();
+        if !parent_ranges.is_empty() {
+            ranges = intersect_ranges(&ranges, parent_ranges);
+        }
+        if ranges.is_empty() {
+            continue;
+        }
+
+        if is_combined {
+            let key = (match_item.pattern_index, language.clone());
+            if let Some(&idx) = combined.get(&key) {
+                injections[idx].ranges.extend(ranges);
+                continue;
+            }
+            combined.insert(key, injections.len());
+        }
+        injections.push(Injection { language, ranges });
+    }
+
+    // Included ranges must be sorted and must not overlap
+    for injection in &mut injections {
+        injection.ranges.sort_by_key(|r| r.start_byte);
+        injection.ranges.dedup_by(|next, prev| {
+            if next.start_byte < prev.end_byte {
+                if next.end_byte > prev.end_byte {
+                    prev.end_byte = next.end_byte;
+                    prev.end_point = next.end_point;
+                }
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    injections
+}
+
+/// Column offsets of a `(#offset! @injection.content 0 1 0 -1)` predicate.
+/// Row offsets aren't supported.
+fn content_offset(query: &Query, match_item: &QueryMatch, content_index: Option<u32>) -> (isize, isize) {
+    for predicate in query.general_predicates(match_item.pattern_index) {
+        if &*predicate.operator != "offset!" {
+            continue;
+        }
+        if !matches!(predicate.args.first(), Some(QueryPredicateArg::Capture(id)) if Some(*id) == content_index) {
+            continue;
+        }
+        let numbers: Vec<isize> = predicate.args[1..]
+            .iter()
+            .filter_map(|arg| match arg {
+                QueryPredicateArg::String(value) => value.parse().ok(),
+                QueryPredicateArg::Capture(_) => None,
+            })
+            .collect();
+        if let [0, start, 0, end] = numbers[..] {
+            return (start, end);
+        }
+    }
+    (0, 0)
+}
+
+/// Ranges covered by an injected node, minus its children unless they are
+/// included, shifted by a `#offset!`
+fn content_ranges(node: Node, include_children: bool, offset: (isize, isize)) -> Vec<Range> {
+    let shift = |point: Point, delta: isize| Point {
+        row: point.row,
+        column: point.column.saturating_add_signed(delta),
+    };
+    let start_byte = node.start_byte().saturating_add_signed(offset.0);
+    let end_byte = node.end_byte().saturating_add_signed(offset.1);
+    if start_byte >= end_byte {
+        return Vec::new();
+    }
+
+    let mut ranges = Vec::new();
+    let mut range = Range {
+        start_byte,
+        end_byte,
+        start_point: shift(node.start_position(), offset.0),
+        end_point: shift(node.end_position(), offset.1),
+    };
+
+    if !include_children {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.end_byte() <= range.start_byte || child.start_byte() >= range.end_byte {
+                continue;
+            }
+            if child.start_byte() > range.start_byte {
+                ranges.push(Range {
+                    end_byte: child.start_byte(),
+                    end_point: child.start_position(),
+                    ..range
                 });
             }
+            range.start_byte = child.end_byte();
+            range.start_point = child.end_position();
         }
+    }
 
-        tokens.sort_by_key(|t| t.start);
-        tokens
+    if range.start_byte < range.end_byte {
+        ranges.push(range);
     }
+    ranges
+}
 
-    pub fn get_color_at_offset(&self, offset: usize, tokens: &[Token]) -> [f32; 4] {
-        for token in tokens {
-            if offset >= token.start && offset < token.end {
-                return token.token_type.color();
+/// Parts of `ranges` that lie inside `bounds`; both must be sorted
+fn intersect_ranges(ranges: &[Range], bounds: &[Range]) -> Vec<Range> {
+    let mut result = Vec::new();
+    for range in ranges {
+        for bound in bounds {
+            let (start, start_point) = if range.start_byte >= bound.start_byte {
+                (range.start_byte, range.start_point)
+            } else {
+                (bound.start_byte, bound.start_point)
+            };
+            let (end, end_point) = if range.end_byte <= bound.end_byte {
+                (range.end_byte, range.end_point)
+            } else {
+                (bound.end_byte, bound.end_point)
+            };
+            if start < end {
+                result.push(Range {
+                    start_byte: start,
+                    end_byte: end,
+                    start_point,
+                    end_point,
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Read a pattern's `(#set! priority N)` property
+fn pattern_priority(query: &Query, pattern_index: usize) -> u32 {
+    query
+        .property_settings(pattern_index)
+        .iter()
+        .find(|prop| &*prop.key == "priority")
+        .and_then(|prop| prop.value.as_deref())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_PRIORITY)
+}
+
+/// Check predicates the tree-sitter cursor leaves to the caller
+fn satisfies_general_predicates(query: &Query, match_item: &QueryMatch, source: &str) -> bool {
+    query
+        .general_predicates(match_item.pattern_index)
+        .iter()
+        .all(|predicate| {
+            let is_positive = match &*predicate.operator {
+                "any-of?" => true,
+                "not-any-of?" => false,
+                // Unknown predicates don't filter
+                _ => return true,
+            };
+
+            let capture_id = match predicate.args.first() {
+                Some(QueryPredicateArg::Capture(id)) => *id,
+                _ => return true,
+            };
+
+            let values = predicate.args[1..].iter().filter_map(|arg| match arg {
+                QueryPredicateArg::String(value) => Some(&**value),
+                QueryPredicateArg::Capture(_) => None,
+            });
+
+            match_item
+                .captures
+                .iter()
+                .filter(|c| c.index == capture_id)
+                .all(|c| {
+                    let text = c.node.utf8_text(source.as_bytes()).unwrap_or("");
+                    values.clone().any(|value| value == text) == is_positive
+                })
+        })
+}
+
+/// Flatten possibly overlapping captures into sorted, non-overlapping
+/// `(start, end, capture_index)` spans. Where captures overlap the one with
+/// the highest precedence wins, so the result doesn't depend on match order.
+fn resolve_overlaps(mut captures: Vec<HighlightCapture>) -> Vec<(usize, usize, usize)> {
+    captures.sort_by_key(|c| c.start);
+
+    let mut boundaries: Vec<usize> = captures.iter().flat_map(|c| [c.start, c.end]).collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+    let mut active: Vec<HighlightCapture> = Vec::new();
+    let mut next = 0;
+
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+
+        active.retain(|c| c.end > start);
+        while next < captures.len() && captures[next].start <= start {
+            if captures[next].end > start {
+                active.push(captures[next]);
             }
+            next += 1;
+        }
+
+        let winner = match active.iter().max_by_key(|c| c.precedence()) {
+            Some(winner) => winner,
+            None => continue,
+        };
+
+        // Extend the previous span when the same capture continues
+        match spans.last_mut() {
+            Some(last) if last.1 == start && last.2 == winner.capture_index => last.1 = end,
+            _ => spans.push((start, end, winner.capture_index)),
         }
-        TokenType::Normal.color()
     }
+
+    spans
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::language::LanguageRegistry;
+
+    fn highlighter(name: &str) -> SyntaxHighlighter {
+        let registry = Arc::new(LanguageRegistry::builtin());
+        SyntaxHighlighter::new(registry.get(name).unwrap(), registry).unwrap()
+    }
 
     #[test]
     fn test_rust_highlighting() {
-        let mut highlighter = SyntaxHighlighter::new("rs").unwrap();
+        let mut highlighter = highlighter("rust");
         let source = "fn main() { println!(\"Hello\"); }";
         let tokens = highlighter.highlight(source);
         assert!(!tokens.is_empty());
     }
+
+    #[test]
+    fn test_builtin_queries_compile() {
+        for name in ["rust", "javascript", "typescript", "tsx", "python"] {
+            let highlighter = highlighter(name);
+            assert!(highlighter.config.query().is_some(), "query for {} failed to compile", name);
+            assert!(highlighter.config.injections().is_some(), "injections for {} failed to compile", name);
+        }
+    }
+
+    fn scope_at<'a>(tokens: &'a [Token], source: &str, needle: &str) -> Option<&'a str> {
+        let offset = source.find(needle)?;
+        tokens
+            .iter()
+            .find(|t| t.start <= offset && offset < t.end)
+            .map(|t| &*t.scope)
+    }
+
+    #[test]
+    fn test_hierarchical_captures() {
+        let mut highlighter = highlighter("rust");
+        let source = "#[derive(Debug)]\nfn f<'a>(x: &'a str) -> u8 { println!(\"\\n\"); MAX }";
+        let tokens = highlighter.highlight(source);
+
+        assert_eq!(scope_at(&tokens, source, "derive"), Some("attribute"));
+        assert_eq!(scope_at(&tokens, source, "println"), Some("function.macro"));
+        assert_eq!(scope_at(&tokens, source, "u8"), Some("type.builtin"));
+        assert_eq!(scope_at(&tokens, source, "\\n"), Some("string.escape"));
+        assert_eq!(scope_at(&tokens, source, "a>"), Some("label"));
+        assert_eq!(scope_at(&tokens, source, "MAX"), Some("constant"));
+    }
+
+    #[test]
+    fn test_overlap_resolution() {
+        let capture = |start, end, priority, pattern_index, capture_index| HighlightCapture {
+            start,
+            end,
+            depth: 0,
+            priority,
+            pattern_index,
+            capture_index,
+        };
+
+        // Inner node wins over the outer one, regardless of input order
+        let spans = resolve_overlaps(vec![capture(2, 4, 100, 0, 1), capture(0, 10, 100, 1, 0)]);
+        assert_eq!(spans, vec![(0, 2, 0), (2, 4, 1), (4, 10, 0)]);
+
+        // Same node: earlier pattern wins, unless a later one has higher priority
+        let spans = resolve_overlaps(vec![capture(0, 3, 100, 1, 1), capture(0, 3, 100, 0, 0)]);
+        assert_eq!(spans, vec![(0, 3, 0)]);
+        let spans = resolve_overlaps(vec![capture(0, 3, 100, 0, 0), capture(0, 3, 110, 1, 1)]);
+        assert_eq!(spans, vec![(0, 3, 1)]);
+
+        // Injected layers win over their host whatever the priority
+        let injected = HighlightCapture { depth: 1, ..capture(1, 2, 100, 0, 1) };
+        let spans = resolve_overlaps(vec![capture(0, 3, 200, 0, 0), injected]);
+        assert_eq!(spans, vec![(0, 1, 0), (1, 2, 1), (2, 3, 0)]);
+    }
+
+    #[test]
+    fn test_injections() {
+        // Macro arguments are parsed as Rust, so `len` is a method call
+        let mut rust = highlighter("rust");
+        let source = "fn f() { assert!(items.len() > 0); }";
+        let tokens = rust.highlight(source);
+        assert_eq!(scope_at(&tokens, source, "len"), Some("function.method.call"));
+
+        // sqlx queries are SQL, without the quotes and escape sequences
+        let source = "fn f() { sqlx::query!(\"SELECT a\\nFROM b\", id); }";
+        rust.highlight(source);
+        let injections = find_injections(&rust.config, rust.tree().unwrap(), source, &[]);
+        let sql: Vec<_> = injections
+            .iter()
+            .filter(|injection| injection.language == "sql")
+            .flat_map(|injection| injection.ranges.iter().map(|r| &source[r.start_byte..r.end_byte]))
+            .collect();
+        assert_eq!(sql, vec!["SELECT a", "FROM b"]);
+
+        // Tagged templates use the language named by the tag, here Python
+        let mut javascript = highlighter("javascript");
+        let source = "const q = python`def f(): return None`;";
+        let tokens = javascript.highlight(source);
+        assert_eq!(scope_at(&tokens, source, "def"), Some("keyword.function"));
+        assert_eq!(scope_at(&tokens, source, "None"), Some("constant.builtin"));
+        assert_eq!(javascript.language_at(source.find("def").unwrap()).name(), "python");
+        assert_eq!(javascript.language_at(0).name(), "javascript");
+
+        // Unknown injected languages leave the host highlight alone
+        let source = "const q = nosuchlang`select 1`;";
+        let tokens = javascript.highlight(source);
+        assert_eq!(scope_at(&tokens, source, "select"), Some("string"));
+    }
+
+    #[test]
+    fn test_content_ranges_skip_children() {
+        let registry = LanguageRegistry::builtin();
+        let mut parser = Parser::new();
+        parser.set_language(&registry.get("rust").unwrap().grammar).unwrap();
+        let source = "vec![1, 2];";
+        let tree = parser.parse(source, None).unwrap();
+        let tokens = tree.root_node().descendant_for_byte_range(4, 10).unwrap();
+        assert_eq!(tokens.kind(), "token_tree");
+
+        // Brackets trimmed by the offset, child tokens excluded unless asked for
+        let text = |include_children| -> Vec<&str> {
+            content_ranges(tokens, include_children, (1, -1))
+                .iter()
+                .map(|r| &source[r.start_byte..r.end_byte])
+                .collect()
+        };
+        assert_eq!(text(false), vec![" "]);
+        assert_eq!(text(true), vec!["1, 2"]);
+    }
 }
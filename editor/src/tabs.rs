@@ -1,28 +1,72 @@
+use crate::brackets::{self, Bracket};
 use crate::buffer::Buffer;
-use crate::cursor::Cursor;
-use std::path::PathBuf;
+use crate::comment::{self, CommentTokens};
+use crate::cursor::{Cursor, Position};
+use crate::folding::{self, FoldState};
+use crate::indent::{self, IndentRules, IndentStyle};
+use crate::language::{Language, LanguageRegistry};
+use crate::search::SearchScope;
+use crate::selection;
+use crate::symbols::{self, Symbol};
+use crate::syntax::{SyntaxHighlighter, Token};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static NEXT_TAB_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug)]
 pub struct Tab {
+    /// Unique for the session, so a tab can be told apart as others close
+    pub id: usize,
     pub buffer: Buffer,
     pub cursor: Cursor,
     pub file_path: Option<PathBuf>,
     pub is_modified: bool,
     pub title: String,
+    /// Highlighter for the tab's language; `None` for plain text
+    pub highlighter: Option<SyntaxHighlighter>,
+    pub syntax_tokens: Vec<Token>,
+    pub folds: FoldState,
+    /// Outline of the document, from the language's `tags.scm`
+    pub symbols: Vec<Symbol>,
+    /// Brackets with their depth and partner, for matching and rainbow colors
+    pub brackets: Vec<Bracket>,
+    /// Width of a tab stop
+    pub tab_size: usize,
+    /// Indentation inserted by Enter, Tab and reindenting
+    pub indent_style: IndentStyle,
+    /// Buffer version `syntax_tokens` were computed for
+    highlighted_version: Option<usize>,
+    /// Selections replaced by `expand_selection`, with the range each grew
+    /// into, so `shrink_selection` can step back through them
+    selection_history: Vec<(Range<usize>, Range<usize>)>,
 }
 
 impl Tab {
     pub fn new(title: String) -> Self {
         Self {
+            id: NEXT_TAB_ID.fetch_add(1, Ordering::Relaxed),
             buffer: Buffer::new(),
             cursor: Cursor::new(),
             file_path: None,
             is_modified: false,
             title,
+            highlighter: None,
+            syntax_tokens: Vec::new(),
+            folds: FoldState::new(),
+            symbols: Vec::new(),
+            brackets: Vec::new(),
+            tab_size: 4,
+            indent_style: IndentStyle::default(),
+            highlighted_version: None,
+            selection_history: Vec::new(),
         }
     }
 
-    pub fn from_file(path: PathBuf, content: String) -> Self {
+    /// Open a file's content, detecting its language from the path and first line
+    pub fn from_file(path: PathBuf, content: String, languages: &Arc<LanguageRegistry>) -> Self {
         let mut buffer = Buffer::new();
         buffer.insert_text(0, &content);
 
@@ -32,13 +76,719 @@ impl Tab {
             .unwrap_or("Untitled")
             .to_string();
 
-        Self {
+        let language = languages.detect(Some(&path), content.lines().next().unwrap_or(""));
+        let indent_style = IndentStyle::detect(&content).unwrap_or_default();
+
+        let mut tab = Self {
+            id: NEXT_TAB_ID.fetch_add(1, Ordering::Relaxed),
             buffer,
             cursor: Cursor::new(),
             file_path: Some(path),
             is_modified: false,
             title,
+            highlighter: None,
+            syntax_tokens: Vec::new(),
+            folds: FoldState::new(),
+            symbols: Vec::new(),
+            brackets: Vec::new(),
+            tab_size: 4,
+            indent_style,
+            highlighted_version: None,
+            selection_history: Vec::new(),
+        };
+        tab.set_language(language, languages);
+        tab
+    }
+
+    pub fn language(&self) -> Option<&Arc<Language>> {
+        self.highlighter.as_ref().map(|h| h.language())
+    }
+
+    /// Name shown in the status bar
+    pub fn language_name(&self) -> &str {
+        self.language().map_or("Plain Text", |l| l.display_name())
+    }
+
+    /// Switch the tab to another language, or plain text with `None`.
+    /// `languages` resolves the languages injected into this one.
+    pub fn set_language(&mut self, language: Option<Arc<Language>>, languages: &Arc<LanguageRegistry>) {
+        self.highlighter = language.and_then(|language| match SyntaxHighlighter::new(language, languages.clone()) {
+            Ok(highlighter) => Some(highlighter),
+            Err(e) => {
+                log::warn!("Failed to create syntax highlighter: {}", e);
+                None
+            }
+        });
+        self.syntax_tokens.clear();
+        self.highlighted_version = None;
+        self.refresh_syntax();
+    }
+
+    /// Re-highlight and recompute fold ranges, symbols and brackets if the buffer
+    /// changed since the last highlight. Folded ranges move along with the edits.
+    pub fn refresh_syntax(&mut self) {
+        let version = self.buffer.version();
+        if self.highlighted_version == Some(version) {
+            return;
+        }
+
+        match self.highlighted_version.and_then(|v| self.buffer.edits_since(v)) {
+            Some(edits) => edits.iter().for_each(|edit| self.folds.apply_edit(edit)),
+            None => self.folds.unfold_all(),
+        }
+
+        let text = self.buffer.text();
+        self.syntax_tokens = match self.highlighter {
+            Some(ref mut highlighter) => highlighter.highlight(&text),
+            None => Vec::new(),
+        };
+
+        let fold_query = self
+            .highlighter
+            .as_ref()
+            .and_then(|h| Some((h.tree()?, h.language().query("folds")?)));
+        let ranges = match fold_query {
+            Some((tree, query)) => folding::query_fold_ranges(tree, query, &text),
+            None => folding::indent_fold_ranges(&text, self.tab_size),
+        };
+        self.folds.set_ranges(ranges);
+
+        self.symbols = match self.highlighter.as_ref() {
+            Some(highlighter) => match (highlighter.tree(), highlighter.language().query("tags")) {
+                (Some(tree), Some(query)) => symbols::extract(tree, query, &text),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        let tree = self.highlighter.as_ref().and_then(|h| h.tree());
+        self.brackets = brackets::find_brackets(tree, &text);
+
+        self.highlighted_version = Some(version);
+    }
+
+    /// Move the cursor out of folded text it landed in: onto the line after
+    /// the fold when it moved down from `previous_line`, otherwise onto the
+    /// fold's header
+    pub fn skip_folds(&mut self, previous_line: usize) {
+        let line = self.cursor.position.line;
+        if !self.folds.is_hidden(line) {
+            return;
+        }
+
+        let target = if line > previous_line {
+            self.folds.next_visible(line)
+        } else {
+            self.folds.prev_visible(line)
+        };
+        let target = if target < self.buffer.line_count() {
+            target
+        } else {
+            self.folds.prev_visible(line)
+        };
+
+        self.cursor.position.line = target;
+        self.cursor.position.column = self.cursor.position.column.min(self.buffer.line_len(target));
+    }
+
+    /// Grow the selection to the enclosing syntax node
+    pub fn expand_selection(&mut self) -> bool {
+        self.refresh_syntax();
+        let range = self.selection_range();
+        let expanded = match self.highlighter.as_ref().and_then(|h| h.tree()) {
+            Some(tree) => selection::expand(tree, range.clone()),
+            None => None,
+        };
+
+        match expanded {
+            Some(expanded) => {
+                self.selection_history.push((range, expanded.clone()));
+                self.select_range(expanded);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Undo the last `expand_selection`, or shrink to the first syntax node
+    /// inside the selection
+    pub fn shrink_selection(&mut self) -> bool {
+        self.refresh_syntax();
+        let range = self.selection_range();
+
+        if let Some((previous, expanded)) = self.selection_history.pop() {
+            if expanded == range {
+                if previous.is_empty() {
+                    self.cursor.clear_selection();
+                    self.cursor.position = self.offset_to_position(previous.start);
+                } else {
+                    self.select_range(previous);
+                }
+                return true;
+            }
+            self.selection_history.clear();
+        }
+
+        let shrunk = match self.highlighter.as_ref().and_then(|h| h.tree()) {
+            Some(tree) => selection::shrink(tree, range),
+            None => None,
+        };
+        match shrunk {
+            Some(shrunk) => {
+                self.select_range(shrunk);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Select the text object named by a `textobjects.scm` capture, such as
+    /// `function.outer`, around the cursor
+    pub fn select_text_object(&mut self, capture: &str) -> bool {
+        let range = self.selection_range();
+        match self.text_object(capture, range) {
+            Some(object) => {
+                self.selection_history.clear();
+                self.select_range(object);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Byte range of the smallest `capture` text object around `range`
+    fn text_object(&mut self, capture: &str, range: Range<usize>) -> Option<Range<usize>> {
+        self.refresh_syntax();
+        let highlighter = self.highlighter.as_ref()?;
+        match (highlighter.tree(), highlighter.language().query("textobjects")) {
+            (Some(tree), Some(query)) => selection::text_object(tree, query, &self.buffer.text(), capture, range),
+            _ => None,
+        }
+    }
+
+    /// Byte range a search scope covers: the selection, the function around
+    /// the cursor or the `visible` lines. `None` for the whole document, or
+    /// when there is no selection or function.
+    pub fn search_scope_range(&mut self, scope: SearchScope, visible: Range<usize>) -> Option<Range<usize>> {
+        match scope {
+            SearchScope::Document => None,
+            SearchScope::Selection => Some(self.selection_range()).filter(|range| !range.is_empty()),
+            SearchScope::Function => {
+                let cursor = self.buffer.position_to_offset(self.cursor.position.line, self.cursor.position.column);
+                self.text_object("function.outer", cursor..cursor)
+            }
+            SearchScope::Visible => {
+                Some(self.buffer.position_to_offset(visible.start, 0)..self.buffer.position_to_offset(visible.end, 0))
+            }
+        }
+    }
+
+    /// Offsets of the bracket at or before the cursor and its partner
+    pub fn matching_bracket(&self) -> Option<(usize, usize)> {
+        let position = self.cursor.position;
+        let offset = self.buffer.position_to_offset(position.line, position.column);
+        let bracket = brackets::bracket_near(&self.brackets, offset)?;
+        Some((bracket.offset, bracket.partner?))
+    }
+
+    /// Move the cursor to the bracket matching the one next to it
+    pub fn jump_to_matching_bracket(&mut self) -> bool {
+        self.refresh_syntax();
+        match self.matching_bracket() {
+            Some((_, partner)) => {
+                let position = self.offset_to_position(partner);
+                self.go_to(position.line, position.column);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Handle typing `text` where it affects a pair: wrap the selection in
+    /// brackets or quotes, type over the closing character after the
+    /// cursor, or insert the closing character along with the opening one.
+    /// Pairs are not closed inside strings and comments. Returns false if
+    /// `text` should be inserted normally.
+    pub fn type_pair(&mut self, text: &str) -> bool {
+        let mut chars = text.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return false,
+        };
+        let pairs = self.language().map_or_else(|| brackets::DEFAULT_PAIRS.to_vec(), |l| l.auto_pairs());
+        let range = self.selection_range();
+
+        // Wrap the selection
+        if !range.is_empty() {
+            let Some(&(open, close)) = pairs.iter().find(|(open, _)| *open == c) else {
+                return false;
+            };
+            self.buffer.transaction(|buffer| {
+                buffer.insert_text(range.end, &close.to_string());
+                buffer.insert_text(range.start, &open.to_string());
+            });
+            self.select_range(range.start + 1..range.end + 1);
+            return true;
+        }
+
+        let offset = range.start;
+        let next = self.char_at(offset);
+        let previous = offset.checked_sub(1).and_then(|before| self.char_at(before));
+
+        // Type over the closing character
+        if next == Some(c) && pairs.iter().any(|(_, close)| *close == c) {
+            self.cursor.position = self.offset_to_position(offset + 1);
+            return true;
+        }
+
+        let Some(&(open, close)) = pairs.iter().find(|(open, _)| *open == c) else {
+            return false;
+        };
+        // Close only before whitespace or a closer, so `(` before a word stays single
+        let before_end = next.is_none_or(|next| next.is_whitespace() || pairs.iter().any(|(_, close)| *close == next));
+        // A quote after a word is an apostrophe or a closing quote
+        let after_word = open == close && previous.is_some_and(|p| p.is_alphanumeric() || p == '_');
+        if !before_end || after_word {
+            return false;
+        }
+        self.refresh_syntax();
+        let text = self.buffer.text();
+        if let Some(tree) = self.highlighter.as_ref().and_then(|h| h.tree()) {
+            if brackets::in_string_or_comment(tree, &text, offset) {
+                return false;
+            }
+        }
+
+        self.buffer.insert_text(offset, &format!("{}{}", open, close));
+        self.cursor.clear_selection();
+        self.cursor.position = self.offset_to_position(offset + 1);
+        true
+    }
+
+    /// Backspace between an empty pair like `(|)` deletes both characters
+    pub fn delete_pair(&mut self) -> bool {
+        if self.cursor.has_selection() {
+            return false;
+        }
+        let range = self.selection_range();
+        let Some(before) = range.start.checked_sub(1) else {
+            return false;
+        };
+        let (previous, next) = (self.char_at(before), self.char_at(range.start));
+        let pairs = self.language().map_or_else(|| brackets::DEFAULT_PAIRS.to_vec(), |l| l.auto_pairs());
+        if !pairs.iter().any(|&(open, close)| previous == Some(open) && next == Some(close)) {
+            return false;
+        }
+
+        self.buffer.delete_range(before..range.start + 1);
+        self.cursor.position = self.offset_to_position(before);
+        true
+    }
+
+    fn char_at(&self, offset: usize) -> Option<char> {
+        if offset >= self.buffer.len() {
+            return None;
         }
+        self.buffer.text_range(offset..offset + 1).chars().next()
+    }
+
+    /// Byte range of the selection, or the empty range at the cursor
+    fn selection_range(&self) -> Range<usize> {
+        let to_offset = |p: Position| self.buffer.position_to_offset(p.line, p.column);
+        match self.cursor.selection {
+            Some(ref selection) => {
+                let (start, end) = (to_offset(selection.start), to_offset(selection.end));
+                start.min(end)..start.max(end)
+            }
+            None => {
+                let offset = to_offset(self.cursor.position);
+                offset..offset
+            }
+        }
+    }
+
+    /// Select a byte range, leaving the cursor at its end
+    fn select_range(&mut self, range: Range<usize>) {
+        let start = self.offset_to_position(range.start);
+        let end = self.offset_to_position(range.end);
+        self.cursor.selection = Some(start..end);
+        self.cursor.position = end;
+        self.cursor.reset_blink();
+    }
+
+    /// Symbols around the cursor, outermost first
+    pub fn breadcrumbs(&self) -> Vec<&Symbol> {
+        let position = self.cursor.position;
+        let offset = self.buffer.position_to_offset(position.line, position.column);
+        symbols::path_at(&self.symbols, offset)
+    }
+
+    /// Break the line at the cursor and indent the new line. Between a
+    /// pair like `{|}` the closer moves down too, leaving the cursor on an
+    /// indented line of its own.
+    pub fn newline(&mut self) {
+        self.buffer.begin_transaction();
+        let range = self.selection_range();
+        if !range.is_empty() {
+            self.buffer.delete_range(range.clone());
+        }
+        let offset = range.start;
+        let line = self.offset_to_position(offset).line;
+        let current = self.buffer.line(line);
+        let current_indent = indent::leading_whitespace(&current).to_string();
+
+        // Whitespace after the cursor would end up in front of the new line's text
+        let trailing = self.buffer.text_range(offset..self.buffer.len());
+        let trailing = trailing.len() - trailing.trim_start_matches([' ', '\t']).len();
+        if trailing > 0 {
+            self.buffer.delete_range(offset..offset + trailing);
+        }
+        let between_pair = match (offset.checked_sub(1).and_then(|before| self.char_at(before)), self.char_at(offset)) {
+            (Some(open), Some(close)) => brackets::DEFAULT_PAIRS.iter().any(|&pair| pair == (open, close) && open != close),
+            _ => false,
+        };
+
+        self.buffer.insert_text(offset, "\n");
+        let indent = if current.trim().is_empty() {
+            // Keep the indentation of a blank line
+            current_indent
+        } else {
+            let columns = self.indent_for(line + 1, line);
+            self.indent_style.render(columns, self.tab_size)
+        };
+
+        let mut text = indent.clone();
+        if between_pair {
+            let inner = indent::indent_columns(&indent, self.tab_size) + self.indent_style.width(self.tab_size);
+            text = self.indent_style.render(inner, self.tab_size);
+            self.buffer.insert_text(offset + 1, &format!("{}\n{}", text, indent));
+        } else {
+            self.buffer.insert_text(offset + 1, &text);
+        }
+        self.buffer.end_transaction();
+        self.cursor.clear_selection();
+        self.cursor.position = Position::new(line + 1, text.chars().count());
+        self.cursor.reset_blink();
+    }
+
+    /// Tab: indent the selected lines, or insert indentation up to the next
+    /// tab stop at the cursor
+    pub fn indent(&mut self) {
+        let width = self.indent_style.width(self.tab_size);
+        match self.selected_lines() {
+            Some(lines) => self.shift_lines(lines, |columns| (columns / width + 1) * width),
+            None => {
+                let range = self.selection_range();
+                self.buffer.begin_transaction();
+                if !range.is_empty() {
+                    self.buffer.delete_range(range.clone());
+                }
+                let position = self.offset_to_position(range.start);
+                let text = match self.indent_style {
+                    IndentStyle::Tabs => "\t".to_string(),
+                    IndentStyle::Spaces(width) => " ".repeat(width - position.column % width),
+                };
+                self.buffer.insert_text(range.start, &text);
+                self.buffer.end_transaction();
+                self.cursor.clear_selection();
+                self.cursor.position = Position::new(position.line, position.column + text.chars().count());
+            }
+        }
+    }
+
+    /// Shift+Tab: outdent the selected lines, or the cursor's line, by a level
+    pub fn outdent(&mut self) {
+        let width = self.indent_style.width(self.tab_size);
+        let line = self.cursor.position.line;
+        let lines = self.selected_lines().unwrap_or(line..line + 1);
+        self.shift_lines(lines, |columns| columns.saturating_sub(1) / width * width);
+    }
+
+    /// Recompute the indentation of the selected lines, or of every line
+    /// without a selection
+    pub fn reindent(&mut self) {
+        let lines = match self.cursor.selection {
+            Some(_) => {
+                let range = self.selection_range();
+                let start = self.offset_to_position(range.start);
+                let end = self.offset_to_position(range.end);
+                let last = if end.column == 0 && end.line > start.line { end.line - 1 } else { end.line };
+                start.line..last + 1
+            }
+            None => 0..self.buffer.line_count(),
+        };
+
+        self.refresh_syntax();
+        let text = self.buffer.text();
+        let tree = self.highlighter.as_ref().and_then(|h| h.tree());
+        let query = self.language().and_then(|l| l.query("indents"));
+        let rules = IndentRules::new(tree, query, &text, &self.brackets);
+
+        // Each line follows the new indentation of the one before it
+        let source_lines: Vec<&str> = text.split('\n').collect();
+        let mut previous = (0..lines.start)
+            .rev()
+            .find(|&l| !source_lines[l].trim().is_empty())
+            .map(|l| (l, indent::indent_columns(source_lines[l], self.tab_size) as isize));
+        let mut indents = Vec::new();
+        for line in lines.clone() {
+            let content = source_lines.get(line).copied().unwrap_or("");
+            if content.trim().is_empty() {
+                indents.push(String::new());
+                continue;
+            }
+            let columns = match previous {
+                Some((previous, columns)) => {
+                    (columns + rules.delta(previous, line) * self.indent_style.width(self.tab_size) as isize).max(0)
+                }
+                None => 0,
+            };
+            indents.push(self.indent_style.render(columns as usize, self.tab_size));
+            previous = Some((line, columns));
+        }
+
+        self.buffer.begin_transaction();
+        for (line, indent) in lines.zip(indents).rev() {
+            self.set_line_indent(line, &indent);
+        }
+        self.buffer.end_transaction();
+        let position = self.cursor.position;
+        self.cursor.clear_selection();
+        self.cursor.position.column = position.column.min(self.buffer.line_len(position.line));
+    }
+
+    /// Indentation in columns for `line`, continuing from the last non-blank
+    /// line at or before `previous`
+    fn indent_for(&mut self, line: usize, previous: usize) -> usize {
+        self.refresh_syntax();
+        let text = self.buffer.text();
+        let Some(previous) = (0..=previous).rev().find(|&l| !self.buffer.line(l).trim().is_empty()) else {
+            return 0;
+        };
+        let tree = self.highlighter.as_ref().and_then(|h| h.tree());
+        let query = self.language().and_then(|l| l.query("indents"));
+        let rules = IndentRules::new(tree, query, &text, &self.brackets);
+
+        let columns = indent::indent_columns(&self.buffer.line(previous), self.tab_size) as isize;
+        let width = self.indent_style.width(self.tab_size) as isize;
+        (columns + rules.delta(previous, line) * width).max(0) as usize
+    }
+
+    /// Lines touched by a selection spanning several lines. A selection
+    /// ending at the start of a line doesn't include that line.
+    fn selected_lines(&self) -> Option<Range<usize>> {
+        let selection = self.cursor.selection.as_ref()?;
+        let (start, end) = if selection.start <= selection.end {
+            (selection.start, selection.end)
+        } else {
+            (selection.end, selection.start)
+        };
+        if start.line == end.line {
+            return None;
+        }
+        let last = if end.column == 0 { end.line - 1 } else { end.line };
+        Some(start.line..last + 1)
+    }
+
+    /// Change the indentation of each line to `target(columns)` as one undo
+    /// step, keeping the cursor and selection on the same text
+    fn shift_lines(&mut self, lines: Range<usize>, target: impl Fn(usize) -> usize) {
+        let mut deltas = Vec::new();
+        self.buffer.begin_transaction();
+        for line in lines.clone() {
+            let text = self.buffer.line(line);
+            if text.trim().is_empty() {
+                deltas.push(0);
+                continue;
+            }
+            let columns = indent::indent_columns(&text, self.tab_size);
+            let indent = self.indent_style.render(target(columns), self.tab_size);
+            deltas.push(self.set_line_indent(line, &indent));
+        }
+        self.buffer.end_transaction();
+
+        let shift = |position: Position| {
+            let delta = match lines.clone().position(|line| line == position.line) {
+                Some(index) if position.column > 0 || deltas[index] < 0 => deltas[index],
+                _ => 0,
+            };
+            Position::new(position.line, (position.column as isize + delta).max(0) as usize)
+        };
+        if let Some(ref selection) = self.cursor.selection {
+            self.cursor.selection = Some(shift(selection.start)..shift(selection.end));
+        }
+        self.cursor.position = shift(self.cursor.position);
+    }
+
+    /// Replace the leading whitespace of a line; returns the change in length
+    fn set_line_indent(&mut self, line: usize, indent: &str) -> isize {
+        let start = self.buffer.position_to_offset(line, 0);
+        let text = self.buffer.line(line);
+        let current = indent::leading_whitespace(&text);
+        if current == indent {
+            return 0;
+        }
+        if !current.is_empty() {
+            self.buffer.delete_range(start..start + current.len());
+        }
+        if !indent.is_empty() {
+            self.buffer.insert_text(start, indent);
+        }
+        indent.len() as isize - current.len() as isize
+    }
+
+    /// Comment or uncomment the selected lines, or the cursor's line, with
+    /// the comment tokens of the language where the first line starts
+    pub fn toggle_comment(&mut self) {
+        let line = self.cursor.position.line;
+        let lines = self.selected_lines().unwrap_or(line..line + 1);
+        let start = self.buffer.rope().line_to_byte(lines.start);
+        let end = self.line_end_byte(lines.end - 1);
+        let text = self.buffer.rope().byte_slice(start..end).to_string();
+        let Some(language) = self.language_at(start + text.len() - text.trim_start().len()) else {
+            return;
+        };
+        let edits = comment::toggle_lines(&text, CommentTokens::of(&language));
+        self.apply_edits(start, &edits, |position| position.column == 0);
+    }
+
+    /// Wrap the selection, or the cursor's line, in a block comment or
+    /// unwrap it. Languages without block comments toggle line comments.
+    pub fn toggle_block_comment(&mut self) {
+        let mut range = match self.cursor.selection {
+            Some(ref selection) => {
+                let (start, end) = (self.byte_offset(selection.start), self.byte_offset(selection.end));
+                start.min(end)..start.max(end)
+            }
+            None => 0..0,
+        };
+        if range.is_empty() {
+            let line = self.cursor.position.line;
+            range = self.buffer.rope().line_to_byte(line)..self.line_end_byte(line);
+        }
+        let Some(language) = self.language_at(range.start) else {
+            return;
+        };
+        let Some((open, close)) = CommentTokens::of(&language).block else {
+            return self.toggle_comment();
+        };
+        let edits = comment::toggle_block(&self.buffer.rope().byte_slice(range.clone()).to_string(), open, close);
+        let first = self.position_at_byte(range.start);
+        self.apply_edits(range.start, &edits, |position| position == first);
+    }
+
+    /// Language at a byte offset, taking injected languages into account
+    fn language_at(&mut self, offset: usize) -> Option<Arc<Language>> {
+        self.refresh_syntax();
+        self.highlighter.as_ref().map(|h| h.language_at(offset).clone())
+    }
+
+    /// Byte offset of a position, whose column counts chars
+    fn byte_offset(&self, position: Position) -> usize {
+        let rope = self.buffer.rope();
+        if position.line >= rope.len_lines() {
+            return rope.len_bytes();
+        }
+        let line = rope.line(position.line);
+        rope.line_to_byte(position.line) + line.char_to_byte(position.column.min(line.len_chars()))
+    }
+
+    /// Position of a byte offset, with the column in chars
+    fn position_at_byte(&self, offset: usize) -> Position {
+        let rope = self.buffer.rope();
+        let line = rope.byte_to_line(offset);
+        Position::new(line, rope.byte_to_char(offset) - rope.line_to_char(line))
+    }
+
+    /// Byte offset of the end of a line, before its newline
+    fn line_end_byte(&self, line: usize) -> usize {
+        self.buffer.rope().line_to_byte(line) + self.buffer.line(line).trim_end_matches('\n').len()
+    }
+
+    /// Apply edits computed for the text starting at byte `base` as one undo
+    /// step, keeping the cursor and selection on the same text. Positions at
+    /// an insertion point move past the inserted text unless `stay_before`
+    /// says otherwise.
+    fn apply_edits(&mut self, base: usize, edits: &[comment::Edit], stay_before: impl Fn(Position) -> bool) {
+        let map = |position: Position| {
+            let offset = self.byte_offset(position);
+            match offset.checked_sub(base) {
+                Some(relative) => base + comment::map_offset(edits, relative, stay_before(position)),
+                None => offset,
+            }
+        };
+        let selection = self.cursor.selection.clone().map(|s| map(s.start)..map(s.end));
+        let cursor = map(self.cursor.position);
+
+        // Edits are in bytes, the buffer is edited in chars; later edits go
+        // first so the earlier offsets stay valid
+        self.buffer.transaction(|buffer| {
+            for edit in edits.iter().rev() {
+                let start = buffer.rope().byte_to_char(base + edit.offset);
+                if edit.delete > 0 {
+                    let end = buffer.rope().byte_to_char(base + edit.offset + edit.delete);
+                    buffer.delete_range(start..end);
+                }
+                if !edit.insert.is_empty() {
+                    buffer.insert_text(start, &edit.insert);
+                }
+            }
+        });
+        self.cursor.selection = selection.map(|s| self.position_at_byte(s.start)..self.position_at_byte(s.end));
+        self.cursor.position = self.position_at_byte(cursor);
+    }
+
+    /// Move the cursor to a line and column, unfolding the line if needed
+    pub fn go_to(&mut self, line: usize, column: usize) {
+        let line = line.min(self.buffer.line_count().saturating_sub(1));
+        self.folds.reveal(line);
+        self.cursor.clear_selection();
+        self.cursor.move_to(Position::new(line, column.min(self.buffer.line_len(line))));
+    }
+
+    /// Identifier under or just before the cursor, for go-to-definition
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let chars: Vec<char> = self.buffer.line(self.cursor.position.line).chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let column = self.cursor.position.column.min(chars.len());
+
+        let start = chars[..column].iter().rev().take_while(|c| is_word(c)).count();
+        let end = chars[column..].iter().take_while(|c| is_word(c)).count();
+        let word: String = chars[column - start..column + end].iter().collect();
+        (!word.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit())).then_some(word)
+    }
+
+    /// What find starts with: the selection if it is on one line, otherwise
+    /// the word at the cursor. `None` with several lines selected.
+    pub fn search_prefill(&self) -> Option<String> {
+        match self.cursor.selection.as_ref() {
+            Some(selection) if selection.start.line != selection.end.line => None,
+            Some(selection) if selection.start != selection.end => {
+                let (start, end) = (self.byte_offset(selection.start), self.byte_offset(selection.end));
+                Some(self.buffer.rope().byte_slice(start.min(end)..start.max(end)).to_string())
+            }
+            _ => self.word_at_cursor(),
+        }
+    }
+
+    fn offset_to_position(&self, offset: usize) -> Position {
+        let (line, column) = self.buffer.offset_to_position(offset);
+        Position::new(line, column)
+    }
+
+    /// Point the tab at the file's new path after a rename or move. The
+    /// language is detected again if the name changed.
+    pub fn set_path(&mut self, path: PathBuf, languages: &Arc<LanguageRegistry>) {
+        let renamed = self.file_path.as_deref().and_then(Path::file_name) != path.file_name();
+        self.title = path.file_name().and_then(|n| n.to_str()).unwrap_or("Untitled").to_string();
+        if renamed {
+            let first_line = self.buffer.line(0);
+            let language = languages.detect(Some(&path), first_line.trim_end());
+            if language.as_ref().map(|l| l.name()) != self.language().map(|l| l.name()) {
+                self.set_language(language, languages);
+            }
+        }
+        self.file_path = Some(path);
     }
 
     pub fn display_title(&self) -> String {
@@ -132,6 +882,21 @@ impl TabManager {
         &mut self.tabs
     }
 
+    /// Follow a file or directory moved from `from` to `to`: tabs showing
+    /// it, or files under it, take their new paths. Returns how many did.
+    pub fn retarget(&mut self, from: &Path, to: &Path, languages: &Arc<LanguageRegistry>) -> usize {
+        let mut moved = 0;
+        for tab in &mut self.tabs {
+            let Some(rest) = tab.file_path.as_deref().and_then(|path| path.strip_prefix(from).ok()) else {
+                continue;
+            };
+            let path = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+            tab.set_path(path, languages);
+            moved += 1;
+        }
+        moved
+    }
+
     pub fn active_index(&self) -> usize {
         self.active_index
     }
@@ -146,3 +911,263 @@ impl Default for TabManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tabs_highlight_independently() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut rust = Tab::from_file(PathBuf::from("main.rs"), "fn main() {}\n".into(), &languages);
+        let mut python = Tab::from_file(PathBuf::from("run"), "#!/usr/bin/env python3\nx = 1\n".into(), &languages);
+        let text = Tab::from_file(PathBuf::from("notes.txt"), "fn main() {}\n".into(), &languages);
+
+        assert_eq!(rust.language_name(), "Rust");
+        assert_eq!(python.language_name(), "Python");
+        assert_eq!(text.language_name(), "Plain Text");
+        assert!(!rust.syntax_tokens.is_empty());
+        assert!(text.syntax_tokens.is_empty());
+
+        // Edits re-highlight only the edited tab
+        let before = python.syntax_tokens.len();
+        rust.buffer.insert_text(0, "// comment\n");
+        rust.refresh_syntax();
+        python.refresh_syntax();
+        assert_eq!(python.syntax_tokens.len(), before);
+        assert!(rust.syntax_tokens.iter().any(|t| &*t.scope == "comment"));
+
+        // Overriding the language drops the old tokens
+        python.set_language(None, &languages);
+        assert!(python.syntax_tokens.is_empty());
+        python.set_language(languages.get("rust"), &languages);
+        assert_eq!(python.language_name(), "Rust");
+    }
+
+    #[test]
+    fn test_tabs_follow_moves() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut tabs = TabManager::new();
+        tabs.add_tab(Tab::from_file(PathBuf::from("/ws/src/main.rs"), "fn main() {}\n".into(), &languages));
+        tabs.add_tab(Tab::from_file(PathBuf::from("/ws/srcs.txt"), String::new(), &languages));
+
+        assert_eq!(tabs.retarget(Path::new("/ws/src"), Path::new("/ws/app"), &languages), 1);
+        assert_eq!(tabs.tabs()[1].file_path.as_deref(), Some(Path::new("/ws/app/main.rs")));
+        assert_eq!(tabs.tabs()[2].file_path.as_deref(), Some(Path::new("/ws/srcs.txt")));
+
+        // A new extension brings a new language
+        tabs.retarget(Path::new("/ws/app/main.rs"), Path::new("/ws/app/main.txt"), &languages);
+        assert_eq!((tabs.tabs()[1].title.as_str(), tabs.tabs()[1].language_name()), ("main.txt", "Plain Text"));
+    }
+
+    #[test]
+    fn test_folds_survive_edits() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let source = "fn a() {\n    1;\n    2;\n}\n\nfn b() {\n    3;\n}\n";
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), source.into(), &languages);
+
+        assert!(tab.folds.fold_at(5));
+        assert!(tab.folds.is_folded(5));
+
+        // Lines added above the fold move it down
+        tab.buffer.insert_text(0, "use x;\n\n");
+        tab.refresh_syntax();
+        assert!(tab.folds.is_folded(7));
+        assert!(tab.folds.is_hidden(8));
+
+        // The cursor skips the folded body
+        tab.cursor.position.line = 8;
+        tab.skip_folds(7);
+        assert_eq!(tab.cursor.position.line, 9);
+        tab.cursor.position.line = 8;
+        tab.skip_folds(9);
+        assert_eq!(tab.cursor.position.line, 7);
+
+        // Plain text folds by indentation
+        let text = Tab::from_file(PathBuf::from("notes.txt"), "a\n  b\nc\n".into(), &languages);
+        assert!(text.folds.is_foldable(0));
+    }
+
+    #[test]
+    fn test_structural_selection() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let source = "fn f() {\n    g(1, 2);\n}\n";
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), source.into(), &languages);
+        let selected = |tab: &Tab| tab.cursor.selection.clone().map(|s| (s.start.line, s.start.column, s.end.line, s.end.column));
+
+        tab.cursor.position = Position::new(1, 6);
+        assert!(tab.expand_selection());
+        assert_eq!(selected(&tab), Some((1, 6, 1, 7)));
+        assert!(tab.expand_selection());
+        assert_eq!(selected(&tab), Some((1, 5, 1, 11)));
+
+        // Shrinking retraces the expansion back to the cursor
+        assert!(tab.shrink_selection());
+        assert_eq!(selected(&tab), Some((1, 6, 1, 7)));
+        assert!(tab.shrink_selection());
+        assert_eq!(selected(&tab), None);
+        assert_eq!(tab.cursor.position, Position::new(1, 6));
+
+        assert!(tab.select_text_object("function.outer"));
+        assert_eq!(selected(&tab), Some((0, 0, 2, 1)));
+        assert!(!tab.select_text_object("class.outer"));
+    }
+
+    #[test]
+    fn test_auto_pairs() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut tab = Tab::from_file(PathBuf::from("app.js"), "f\n".into(), &languages);
+        let type_text = |tab: &mut Tab, text: &str| {
+            let offset = tab.buffer.position_to_offset(tab.cursor.position.line, tab.cursor.position.column);
+            if !tab.type_pair(text) {
+                tab.buffer.insert_text(offset, text);
+                tab.cursor.position = tab.offset_to_position(offset + text.len());
+            }
+        };
+
+        tab.cursor.position = Position::new(0, 1);
+        type_text(&mut tab, "(");
+        type_text(&mut tab, "'");
+        assert_eq!(tab.buffer.text(), "f('')\n");
+        type_text(&mut tab, "(");
+        type_text(&mut tab, "'");
+        type_text(&mut tab, ")");
+        assert_eq!(tab.buffer.text(), "f('(')\n");
+        assert_eq!(tab.cursor.position, Position::new(0, 6));
+
+        // Backspace removes an empty pair, typing a bracket wraps the selection
+        type_text(&mut tab, "[");
+        assert_eq!(tab.buffer.text(), "f('(')[]\n");
+        assert!(tab.delete_pair());
+        assert_eq!(tab.buffer.text(), "f('(')\n");
+        assert!(!tab.delete_pair());
+        tab.cursor.selection = Some(Position::new(0, 0)..Position::new(0, 1));
+        type_text(&mut tab, "{");
+        assert_eq!(tab.buffer.text(), "{f}('(')\n");
+        // One undo takes off both sides of the wrap
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "f('(')\n");
+        tab.buffer.redo();
+
+        // The `(` inside the string is not a bracket
+        tab.cursor.clear_selection();
+        tab.cursor.position = Position::new(0, 3);
+        assert!(tab.jump_to_matching_bracket());
+        assert_eq!(tab.cursor.position, Position::new(0, 7));
+    }
+
+    #[test]
+    fn test_auto_indent() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), "fn f() {}\n".into(), &languages);
+        assert_eq!(tab.indent_style, IndentStyle::Spaces(4));
+
+        // Enter between braces opens an indented line
+        tab.cursor.position = Position::new(0, 8);
+        tab.newline();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    \n}\n");
+        assert_eq!(tab.cursor.position, Position::new(1, 4));
+        tab.buffer.insert_text(13, "g(");
+        tab.cursor.position = Position::new(1, 6);
+        tab.newline();
+        assert_eq!(tab.buffer.line(2), "        \n");
+
+        let mut python = Tab::from_file(PathBuf::from("a.py"), "if x:\n\ty = 1\n".into(), &languages);
+        assert_eq!(python.indent_style, IndentStyle::Tabs);
+        python.cursor.position = Position::new(0, 5);
+        python.newline();
+        assert_eq!(python.buffer.text(), "if x:\n\t\n\ty = 1\n");
+
+        // Tab and Shift+Tab shift every selected line, reindent fixes them all
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), "fn f() {\na;\n  b;\n}\n".into(), &languages);
+        assert_eq!(tab.indent_style, IndentStyle::Spaces(2));
+        tab.indent_style = IndentStyle::Spaces(4);
+        tab.cursor.selection = Some(Position::new(1, 0)..Position::new(3, 0));
+        tab.indent();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    a;\n    b;\n}\n");
+        tab.outdent();
+        tab.outdent();
+        assert_eq!(tab.buffer.text(), "fn f() {\na;\nb;\n}\n");
+        tab.cursor.clear_selection();
+        tab.reindent();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    a;\n    b;\n}\n");
+
+        // Each command is one undo step, however many lines it changes
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\na;\nb;\n}\n");
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    a;\n    b;\n}\n");
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\na;\n  b;\n}\n");
+        tab.cursor.position = Position::new(0, 8);
+        tab.newline();
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\na;\n  b;\n}\n");
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), "fn f() {\n    a;\n\n    b;\n}\n".into(), &languages);
+        tab.cursor.selection = Some(Position::new(1, 4)..Position::new(3, 6));
+        tab.cursor.position = Position::new(3, 6);
+        tab.toggle_comment();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    // a;\n\n    // b;\n}\n");
+        assert_eq!(tab.cursor.position, Position::new(3, 9));
+        tab.toggle_comment();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    a;\n\n    b;\n}\n");
+        // Lines with text after multibyte chars
+        let mut accents = Tab::from_file(PathBuf::from("lib.rs"), "é;\nlet x = 1; // café\n".into(), &languages);
+        accents.cursor.selection = Some(Position::new(0, 0)..Position::new(1, 18));
+        accents.cursor.position = Position::new(1, 18);
+        accents.toggle_comment();
+        assert_eq!(accents.buffer.text(), "// é;\n// let x = 1; // café\n");
+        assert_eq!(accents.cursor.position, Position::new(1, 21));
+        accents.cursor.selection = Some(Position::new(1, 17)..Position::new(1, 21));
+        accents.toggle_block_comment();
+        assert_eq!(accents.buffer.line(1), "// let x = 1; // /* café */\n");
+        accents.cursor.clear_selection();
+        accents.toggle_comment();
+        assert_eq!(accents.buffer.line(1), "let x = 1; // /* café */\n");
+
+        // Each toggle is one undo step
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    // a;\n\n    // b;\n}\n");
+        tab.buffer.undo();
+        assert_eq!(tab.buffer.text(), "fn f() {\n    a;\n\n    b;\n}\n");
+
+        tab.cursor.selection = Some(Position::new(1, 4)..Position::new(1, 5));
+        tab.toggle_block_comment();
+        assert_eq!(tab.buffer.line(1), "    /* a */;\n");
+        assert_eq!(tab.cursor.selection, Some(Position::new(1, 4)..Position::new(1, 11)));
+
+        // Python has no block comments; a template tagged `python` uses its tokens
+        let mut python = Tab::from_file(PathBuf::from("a.py"), "x = 1\n".into(), &languages);
+        python.toggle_block_comment();
+        assert_eq!(python.buffer.text(), "# x = 1\n");
+        let mut js = Tab::from_file(PathBuf::from("a.js"), "const q = python`\nx = 1\n`;\n".into(), &languages);
+        js.cursor.position = Position::new(1, 0);
+        js.toggle_comment();
+        assert_eq!(js.buffer.line(1), "# x = 1\n");
+    }
+
+    #[test]
+    fn test_word_at_cursor() {
+        let languages = Arc::new(LanguageRegistry::builtin());
+        let mut tab = Tab::from_file(PathBuf::from("lib.rs"), "let x = do_it(42);\n".into(), &languages);
+        let mut word_at = |column| {
+            tab.cursor.position = Position::new(0, column);
+            tab.word_at_cursor()
+        };
+
+        assert_eq!(word_at(10).as_deref(), Some("do_it"));
+        assert_eq!(word_at(13).as_deref(), Some("do_it"));
+        assert_eq!(word_at(7), None);
+        assert_eq!(word_at(15), None);
+
+        // Find starts with the selection, columns counting chars
+        let mut tab = Tab::from_file(PathBuf::from("a.txt"), "éa b\n".into(), &languages);
+        tab.cursor.selection = Some(Position::new(0, 2)..Position::new(0, 1));
+        assert_eq!(tab.search_prefill().as_deref(), Some("a"));
+    }
+}
@@ -1,7 +1,56 @@
 use anyhow::Result;
 use cosmic_text::{Attrs, Buffer as TextBuffer, FontSystem, Metrics, SwashCache};
+use std::ops::Range;
 use wgpu::{Device, Queue, TextureFormat};
 
+use crate::theme::Style;
+
+/// Glyphs produced by a layout pass, plus underline segments for styled text
+#[derive(Default)]
+pub struct RenderedText {
+    pub glyphs: Vec<GlyphInstance>,
+    pub underlines: Vec<Underline>,
+}
+
+/// Horizontal underline segment, positioned relative to the text origin
+pub struct Underline {
+    pub position: [f32; 2],
+    pub width: f32,
+    pub color: [f32; 4],
+}
+
+/// Group consecutive characters with the same style into byte ranges
+fn styled_spans(text: &str, style_fn: &dyn Fn(usize, usize) -> Style) -> Vec<(Range<usize>, Style)> {
+    let mut spans: Vec<(Range<usize>, Style)> = Vec::new();
+    let mut line = 0;
+    let mut line_start = 0;
+
+    for (idx, ch) in text.char_indices() {
+        let style = style_fn(line, idx - line_start);
+        match spans.last_mut() {
+            Some((range, last)) if *last == style => range.end = idx + ch.len_utf8(),
+            _ => spans.push((idx..idx + ch.len_utf8(), style)),
+        }
+        if ch == '\n' {
+            line += 1;
+            line_start = idx + 1;
+        }
+    }
+
+    spans
+}
+
+fn style_attrs(style: &Style) -> Attrs<'static> {
+    let mut attrs = Attrs::new();
+    if style.bold {
+        attrs = attrs.weight(cosmic_text::Weight::BOLD);
+    }
+    if style.italic {
+        attrs = attrs.style(cosmic_text::Style::Italic);
+    }
+    attrs
+}
+
 pub struct TextRenderer {
     font_system: FontSystem,
     swash_cache: SwashCache,
@@ -32,20 +81,32 @@ impl TextRenderer {
         text: &str,
         font_size: f32,
         line_height: f32,
-        color_fn: Option<&dyn Fn(usize) -> [f32; 4]>,
-    ) -> Result<Vec<GlyphInstance>> {
+        style_fn: Option<&dyn Fn(usize, usize) -> Style>,
+    ) -> Result<RenderedText> {
         // Create cosmic-text buffer
         let metrics = Metrics::new(font_size, line_height);
         let mut buffer = TextBuffer::new(&mut self.font_system, metrics);
 
-        // Set text
-        buffer.set_text(&mut self.font_system, text, Attrs::new(), cosmic_text::Shaping::Advanced);
+        // Set text, split into spans of equal style so bold/italic get their own faces
+        match style_fn {
+            Some(style_fn) => {
+                let spans = styled_spans(text, style_fn);
+                buffer.set_rich_text(
+                    &mut self.font_system,
+                    spans.iter().map(|(range, style)| (&text[range.clone()], style_attrs(style))),
+                    cosmic_text::Shaping::Advanced,
+                );
+            }
+            None => {
+                buffer.set_text(&mut self.font_system, text, Attrs::new(), cosmic_text::Shaping::Advanced);
+            }
+        }
 
         // Layout text
         buffer.set_size(&mut self.font_system, 1280.0, 800.0);
         buffer.shape_until_scroll(&mut self.font_system);
 
-        let mut instances = Vec::new();
+        let mut rendered = RenderedText::default();
 
         // Process each line
         for run in buffer.layout_runs() {
@@ -54,6 +115,20 @@ impl TextRenderer {
                 let physical_glyph = glyph.physical((0., 0.), 1.0);
                 let cache_key = physical_glyph.cache_key;
 
+                // Style from callback (line index, byte offset in line) or plain white
+                let style = match style_fn {
+                    Some(style_fn) => style_fn(run.line_i, glyph.start),
+                    None => Style::plain([1.0, 1.0, 1.0, 1.0]),
+                };
+
+                if style.underline {
+                    rendered.underlines.push(Underline {
+                        position: [glyph.x, run.line_top + line_height - 2.0],
+                        width: glyph.w,
+                        color: style.fg,
+                    });
+                }
+
                 // Rasterize glyph if not in cache
                 let image_opt = self
                     .swash_cache
@@ -63,26 +138,19 @@ impl TextRenderer {
                     // Add glyph to atlas
                     let atlas_pos = self.atlas.add_glyph(device, queue, &image)?;
 
-                    // Get color from callback or use white
-                    let color = if let Some(ref color_fn) = color_fn {
-                        color_fn(glyph.start)
-                    } else {
-                        [1.0, 1.0, 1.0, 1.0]
-                    };
-
                     // Create instance
-                    instances.push(GlyphInstance {
+                    rendered.glyphs.push(GlyphInstance {
                         position: [physical_glyph.x as f32, physical_glyph.y as f32],
                         size: [image.placement.width as f32, image.placement.height as f32],
                         uv_offset: atlas_pos.uv_offset,
                         uv_size: atlas_pos.uv_size,
-                        color,
+                        color: style.fg,
                     });
                 }
             }
         }
 
-        Ok(instances)
+        Ok(rendered)
     }
 
     pub fn render_text_at_position(
@@ -139,6 +207,36 @@ impl TextRenderer {
         Ok(instances)
     }
 
+    /// X of each char boundary of `text` laid out on one line, relative to
+    /// where `render_text_at_position` draws it: one more than the chars,
+    /// ending at the width of the text
+    pub fn char_offsets(&mut self, text: &str, font_size: f32) -> Vec<f32> {
+        let metrics = Metrics::new(font_size, font_size * 1.2);
+        let mut buffer = TextBuffer::new(&mut self.font_system, metrics);
+        buffer.set_text(&mut self.font_system, text, Attrs::new(), cosmic_text::Shaping::Advanced);
+        buffer.set_size(&mut self.font_system, f32::MAX, font_size * 2.0);
+        buffer.shape_until_scroll(&mut self.font_system);
+
+        // Where the glyph starting at each byte is; chars inside a cluster
+        // take the position of the cluster
+        let mut starts = vec![None; text.len() + 1];
+        let mut width = 0.0f32;
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs.iter() {
+                starts[glyph.start].get_or_insert(glyph.x);
+                width = width.max(glyph.x + glyph.w);
+            }
+        }
+        let mut offsets = Vec::new();
+        let mut last = 0.0;
+        for (byte, _) in text.char_indices() {
+            last = starts[byte].unwrap_or(last);
+            offsets.push(last);
+        }
+        offsets.push(width);
+        offsets
+    }
+
     pub fn atlas_texture(&self) -> &wgpu::Texture {
         &self.atlas.texture
     }
@@ -0,0 +1,462 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub mod vscode;
+
+/// Built-in theme sources, shipped with the binary
+const DARK_THEME: &str = include_str!("../../themes/dark.toml");
+const LIGHT_THEME: &str = include_str!("../../themes/light.toml");
+
+/// RGBA color with components in 0.0..=1.0
+pub type Color = [f32; 4];
+
+/// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` into a color
+pub fn parse_hex_color(hex: &str) -> Result<Color> {
+    let digits = hex.trim().trim_start_matches('#');
+    let channel = |s: &str| -> Result<f32> {
+        let value = u8::from_str_radix(s, 16)
+            .map_err(|_| anyhow::anyhow!("Invalid color: {}", hex))?;
+        Ok(value as f32 / 255.0)
+    };
+    // Checked up front so the slicing below stays on char boundaries
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("Invalid color: {}", hex));
+    }
+
+    match digits.len() {
+        3 | 4 => {
+            let mut color = [1.0; 4];
+            for (i, c) in digits.chars().enumerate() {
+                let pair = format!("{}{}", c, c);
+                color[i] = channel(&pair)?;
+            }
+            Ok(color)
+        }
+        6 | 8 => {
+            let mut color = [1.0; 4];
+            for i in 0..digits.len() / 2 {
+                color[i] = channel(&digits[i * 2..i * 2 + 2])?;
+            }
+            Ok(color)
+        }
+        _ => Err(anyhow::anyhow!("Invalid color: {}", hex)),
+    }
+}
+
+/// Text style applied to a highlighted span
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub fg: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    pub fn plain(fg: Color) -> Self {
+        Self {
+            fg,
+            bold: false,
+            italic: false,
+            underline: false,
+        }
+    }
+}
+
+/// Colors used for editor chrome (everything that isn't syntax)
+#[derive(Debug, Clone)]
+pub struct UiColors {
+    pub background: Color,
+    pub foreground: Color,
+    pub cursor: Color,
+    pub selection: Color,
+    pub line_number: Color,
+    pub tab_bar_background: Color,
+    pub tab_active_foreground: Color,
+    pub tab_inactive_foreground: Color,
+    pub sidebar_background: Color,
+    pub sidebar_foreground: Color,
+    pub separator: Color,
+    pub status_bar_background: Color,
+    pub status_bar_foreground: Color,
+    pub overlay_background: Color,
+    pub overlay_foreground: Color,
+    pub overlay_selection: Color,
+    /// Background behind the bracket matching the one at the cursor
+    pub bracket_match: Color,
+}
+
+impl UiColors {
+    /// Set a color by its theme-file key. Returns false for unknown keys.
+    pub fn set(&mut self, key: &str, color: Color) -> bool {
+        let slot = match key {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "cursor" => &mut self.cursor,
+            "selection" => &mut self.selection,
+            "line_number" => &mut self.line_number,
+            "tab_bar_background" => &mut self.tab_bar_background,
+            "tab_active_foreground" => &mut self.tab_active_foreground,
+            "tab_inactive_foreground" => &mut self.tab_inactive_foreground,
+            "sidebar_background" => &mut self.sidebar_background,
+            "sidebar_foreground" => &mut self.sidebar_foreground,
+            "separator" => &mut self.separator,
+            "status_bar_background" => &mut self.status_bar_background,
+            "status_bar_foreground" => &mut self.status_bar_foreground,
+            "overlay_background" => &mut self.overlay_background,
+            "overlay_foreground" => &mut self.overlay_foreground,
+            "overlay_selection" => &mut self.overlay_selection,
+            "bracket_match" => &mut self.bracket_match,
+            _ => return false,
+        };
+        *slot = color;
+        true
+    }
+}
+
+impl Default for UiColors {
+    fn default() -> Self {
+        Self {
+            background: [0.12, 0.12, 0.12, 1.0],
+            foreground: [1.0, 1.0, 1.0, 1.0],
+            cursor: [1.0, 1.0, 1.0, 1.0],
+            selection: [0.3, 0.5, 0.8, 0.3],
+            line_number: [0.5, 0.5, 0.5, 1.0],
+            tab_bar_background: [0.15, 0.15, 0.15, 1.0],
+            tab_active_foreground: [0.9, 0.9, 0.9, 1.0],
+            tab_inactive_foreground: [0.6, 0.6, 0.6, 1.0],
+            sidebar_background: [0.10, 0.10, 0.10, 1.0],
+            sidebar_foreground: [0.9, 0.9, 0.9, 1.0],
+            separator: [0.3, 0.3, 0.3, 1.0],
+            status_bar_background: [0.08, 0.08, 0.08, 1.0],
+            status_bar_foreground: [0.7, 0.7, 0.7, 1.0],
+            overlay_background: [0.2, 0.2, 0.2, 0.95],
+            overlay_foreground: [1.0, 0.9, 0.3, 1.0],
+            overlay_selection: [0.3, 0.5, 0.8, 0.5],
+            bracket_match: [0.5, 0.5, 0.5, 0.35],
+        }
+    }
+}
+
+/// On-disk theme format
+#[derive(Debug, Deserialize)]
+struct ThemeFile {
+    name: Option<String>,
+    variant: Option<String>,
+    #[serde(default)]
+    ui: HashMap<String, String>,
+    #[serde(default)]
+    syntax: HashMap<String, StyleDef>,
+}
+
+/// A syntax entry is either a bare color or a table with font styles
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StyleDef {
+    Color(String),
+    Full {
+        fg: Option<String>,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        italic: bool,
+        #[serde(default)]
+        underline: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeVariant {
+    Dark,
+    Light,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub variant: ThemeVariant,
+    pub ui: UiColors,
+    /// Styles keyed by highlight scope, e.g. `function.method`
+    pub scopes: HashMap<String, Style>,
+}
+
+impl Theme {
+    /// Built-in dark theme
+    pub fn dark() -> Self {
+        Self::parse_toml(DARK_THEME, Self::base).expect("built-in dark theme is valid")
+    }
+
+    /// Built-in light theme
+    pub fn light() -> Self {
+        Self::parse_toml(LIGHT_THEME, Self::base).expect("built-in light theme is valid")
+    }
+
+    /// An empty theme of the given variant, used as the base for imports
+    fn base(variant: ThemeVariant) -> Self {
+        match variant {
+            ThemeVariant::Dark => Self {
+                name: "Base Dark".to_string(),
+                variant,
+                ui: UiColors::default(),
+                scopes: HashMap::new(),
+            },
+            ThemeVariant::Light => {
+                let mut theme = Self::base(ThemeVariant::Dark);
+                theme.variant = variant;
+                theme.ui.background = [1.0, 1.0, 1.0, 1.0];
+                theme.ui.foreground = [0.0, 0.0, 0.0, 1.0];
+                theme.ui.cursor = [0.0, 0.0, 0.0, 1.0];
+                theme
+            }
+        }
+    }
+
+    /// Parse a theme from TOML source. Anything the file leaves out is
+    /// inherited from the built-in theme of the same variant.
+    pub fn from_toml(source: &str) -> Result<Self> {
+        Self::parse_toml(source, |variant| match variant {
+            ThemeVariant::Dark => Self::dark(),
+            ThemeVariant::Light => Self::light(),
+        })
+    }
+
+    fn parse_toml(source: &str, base: impl Fn(ThemeVariant) -> Self) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(source)?;
+
+        let variant = match file.variant.as_deref() {
+            Some("light") => ThemeVariant::Light,
+            _ => ThemeVariant::Dark,
+        };
+
+        let mut theme = base(variant);
+        theme.name = file.name.unwrap_or_else(|| "Untitled Theme".to_string());
+
+        for (key, value) in &file.ui {
+            let color = parse_hex_color(value)?;
+            if !theme.ui.set(key, color) {
+                log::warn!("Unknown UI color in theme '{}': {}", theme.name, key);
+            }
+        }
+
+        for (scope, def) in file.syntax {
+            let style = match def {
+                StyleDef::Color(hex) => Style::plain(parse_hex_color(&hex)?),
+                StyleDef::Full { fg, bold, italic, underline } => Style {
+                    fg: match fg {
+                        Some(hex) => parse_hex_color(&hex)?,
+                        None => theme.ui.foreground,
+                    },
+                    bold,
+                    italic,
+                    underline,
+                },
+            };
+            theme.scopes.insert(scope, style);
+        }
+
+        Ok(theme)
+    }
+
+    /// Load a theme file. `.json` files are imported as VS Code/TextMate themes.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut theme = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => vscode::import(&content)?,
+            _ => Self::from_toml(&content)?,
+        };
+
+        if theme.name == "Untitled Theme" {
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                theme.name = stem.to_string();
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Resolve the style for a scope, falling back through dotted prefixes:
+    /// `function.method.call` → `function.method` → `function` → default
+    pub fn style(&self, scope: &str) -> Style {
+        let mut scope = scope;
+        loop {
+            if let Some(style) = self.scopes.get(scope) {
+                return *style;
+            }
+            match scope.rfind('.') {
+                Some(idx) => scope = &scope[..idx],
+                None => return Style::plain(self.ui.foreground),
+            }
+        }
+    }
+
+    /// Style of a bracket nested `depth` deep: the theme's `rainbow.1`,
+    /// `rainbow.2`, ... styles in turn. `None` if the theme defines none.
+    pub fn rainbow(&self, depth: usize) -> Option<Style> {
+        let count = (1..).take_while(|n| self.scopes.contains_key(&format!("rainbow.{}", n))).count();
+        if count == 0 {
+            return None;
+        }
+        self.scopes.get(&format!("rainbow.{}", depth % count + 1)).copied()
+    }
+
+    /// Shorthand for the foreground color of a scope
+    pub fn color(&self, scope: &str) -> Color {
+        self.style(scope).fg
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Holds every available theme and tracks the active one
+pub struct ThemeManager {
+    themes: Vec<Theme>,
+    active_index: usize,
+}
+
+impl ThemeManager {
+    /// Load built-in themes plus any user themes, activating `active_name`
+    pub fn new(active_name: &str) -> Self {
+        let mut manager = Self {
+            themes: vec![Theme::dark(), Theme::light()],
+            active_index: 0,
+        };
+
+        if let Some(dir) = Self::themes_dir() {
+            manager.load_dir(&dir);
+        }
+
+        if !manager.set_active(active_name) {
+            log::warn!("Theme '{}' not found, using '{}'", active_name, manager.active().name);
+        }
+
+        manager
+    }
+
+    /// Directory user themes are loaded from (`<config>/nexus/themes`)
+    pub fn themes_dir() -> Option<PathBuf> {
+        crate::config::Config::config_dir().ok().map(|dir| dir.join("themes"))
+    }
+
+    /// Load all `.toml` and `.json` themes in a directory, replacing any
+    /// existing theme with the same name
+    pub fn load_dir(&mut self, dir: &Path) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("toml" | "json")))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match Theme::load(&path) {
+                Ok(theme) => {
+                    log::info!("🎨 Loaded theme '{}' from {}", theme.name, path.display());
+                    self.add(theme);
+                }
+                Err(e) => log::warn!("Failed to load theme {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Add a theme, replacing one with the same name
+    pub fn add(&mut self, theme: Theme) {
+        match self.position(&theme.name) {
+            Some(idx) => self.themes[idx] = theme,
+            None => self.themes.push(theme),
+        }
+    }
+
+    /// Re-read user themes from disk, keeping the active theme by name
+    pub fn reload(&mut self) {
+        let active_name = self.active().name.clone();
+        self.themes = vec![Theme::dark(), Theme::light()];
+        self.active_index = 0;
+        if let Some(dir) = Self::themes_dir() {
+            self.load_dir(&dir);
+        }
+        self.set_active(&active_name);
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        // `UiConfig::theme` may use the short built-in names
+        let builtin = match name {
+            "dark" => Some(0),
+            "light" => Some(1),
+            _ => None,
+        };
+        self.themes
+            .iter()
+            .position(|t| t.name.eq_ignore_ascii_case(name))
+            .or(builtin)
+    }
+
+    /// Switch the active theme by name. Returns false if no such theme exists.
+    pub fn set_active(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(idx) => {
+                self.active_index = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn active(&self) -> &Theme {
+        &self.themes[self.active_index]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|t| t.name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#ff0000").unwrap(), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(parse_hex_color("#fff").unwrap(), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(parse_hex_color("#00000000").unwrap(), [0.0, 0.0, 0.0, 0.0]);
+        assert!(parse_hex_color("#12345").is_err());
+        assert!(parse_hex_color("#gggggg").is_err());
+        assert!(parse_hex_color("#ééé").is_err());
+    }
+
+    #[test]
+    fn test_builtin_themes_load() {
+        assert_eq!(Theme::dark().variant, ThemeVariant::Dark);
+        assert_eq!(Theme::light().variant, ThemeVariant::Light);
+    }
+
+    #[test]
+    fn test_scope_fallback() {
+        let theme = Theme::from_toml(
+            r##"
+            name = "Test"
+            [syntax]
+            function = "#ff0000"
+            "function.method" = { fg = "#00ff00", italic = true }
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.color("function.method"), [0.0, 1.0, 0.0, 1.0]);
+        assert!(theme.style("function.method.call").italic);
+        assert_eq!(theme.color("function.call"), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(theme.color("keyword"), Theme::dark().color("keyword"));
+        assert_eq!(theme.color("markup.heading"), theme.ui.foreground);
+    }
+}
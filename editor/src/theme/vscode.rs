@@ -0,0 +1,333 @@
+// Import VS Code color themes and TextMate themes in JSON form.
+//
+// VS Code themes keep token rules in `tokenColors` and UI colors in `colors`;
+// TextMate JSON themes put everything in `settings`, with the global defaults
+// in the one entry that has no `scope`.
+
+use super::{parse_hex_color, Style, Theme, ThemeVariant};
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+struct VsCodeTheme {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    colors: HashMap<String, Value>,
+    #[serde(default, rename = "tokenColors")]
+    token_colors: Vec<TokenRule>,
+    /// TextMate JSON themes use `settings` instead of `tokenColors`
+    #[serde(default)]
+    settings: Vec<TokenRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRule {
+    scope: Option<Value>,
+    #[serde(default)]
+    settings: HashMap<String, Value>,
+}
+
+/// VS Code workbench color keys mapped onto Nexus UI keys
+const UI_KEYS: &[(&str, &str)] = &[
+    ("editor.background", "background"),
+    ("editor.foreground", "foreground"),
+    ("editorCursor.foreground", "cursor"),
+    ("editor.selectionBackground", "selection"),
+    ("editorLineNumber.foreground", "line_number"),
+    ("editorGroupHeader.tabsBackground", "tab_bar_background"),
+    ("tab.activeForeground", "tab_active_foreground"),
+    ("tab.inactiveForeground", "tab_inactive_foreground"),
+    ("sideBar.background", "sidebar_background"),
+    ("sideBar.foreground", "sidebar_foreground"),
+    ("editorGroup.border", "separator"),
+    ("statusBar.background", "status_bar_background"),
+    ("statusBar.foreground", "status_bar_foreground"),
+    ("editorWidget.background", "overlay_background"),
+    ("editorWidget.foreground", "overlay_foreground"),
+    ("list.activeSelectionBackground", "overlay_selection"),
+];
+
+/// TextMate global settings mapped onto Nexus UI keys
+const TEXTMATE_GLOBAL_KEYS: &[(&str, &str)] = &[
+    ("background", "background"),
+    ("foreground", "foreground"),
+    ("caret", "cursor"),
+    ("selection", "selection"),
+];
+
+/// TextMate scopes mapped onto tree-sitter highlight scopes
+const SCOPE_MAP: &[(&str, &str)] = &[
+    ("comment", "comment"),
+    ("string", "string"),
+    ("constant.character.escape", "string.escape"),
+    ("constant.numeric", "number"),
+    ("constant.language", "constant.builtin"),
+    ("constant", "constant"),
+    ("variable.parameter", "parameter"),
+    ("variable.other.property", "property"),
+    ("variable.other.member", "property"),
+    ("support.variable.property", "property"),
+    ("variable.language", "variable.builtin"),
+    ("variable", "variable"),
+    ("keyword.operator", "operator"),
+    ("keyword", "keyword"),
+    ("storage.modifier", "keyword"),
+    ("storage.type", "keyword"),
+    ("entity.name.function.macro", "function.macro"),
+    ("entity.name.function", "function"),
+    ("support.function", "function.builtin"),
+    ("meta.function-call", "function"),
+    ("entity.name.namespace", "namespace"),
+    ("entity.name.type", "type"),
+    ("entity.name.class", "type"),
+    ("support.type", "type.builtin"),
+    ("support.class", "type"),
+    ("entity.name.tag", "tag"),
+    ("entity.other.attribute-name", "attribute"),
+    ("meta.attribute", "attribute"),
+    ("entity.name.lifetime", "label"),
+    ("punctuation", "punctuation"),
+];
+
+/// Import a VS Code or TextMate JSON theme
+pub fn import(source: &str) -> Result<Theme> {
+    let json = strip_json_comments(source);
+    let parsed: VsCodeTheme = serde_json::from_str(&json)?;
+
+    let variant = match parsed.kind.as_deref() {
+        Some("light") | Some("hcLight") => ThemeVariant::Light,
+        _ => ThemeVariant::Dark,
+    };
+
+    let mut theme = Theme::base(variant);
+    theme.name = parsed.name.unwrap_or_else(|| "Untitled Theme".to_string());
+
+    for (vscode_key, ui_key) in UI_KEYS {
+        if let Some(color) = parsed.colors.get(*vscode_key).and_then(color_value) {
+            theme.ui.set(ui_key, color);
+        }
+    }
+
+    let mut rules = parsed.token_colors;
+    rules.extend(parsed.settings);
+
+    // Best matching rule per Nexus scope; later rules win ties
+    let mut best: HashMap<&str, (usize, Style)> = HashMap::new();
+
+    for rule in &rules {
+        let selectors = match &rule.scope {
+            Some(scope) => scope_selectors(scope),
+            None => {
+                // Global defaults of a TextMate theme
+                for (tm_key, ui_key) in TEXTMATE_GLOBAL_KEYS {
+                    if let Some(color) = rule.settings.get(*tm_key).and_then(color_value) {
+                        theme.ui.set(ui_key, color);
+                    }
+                }
+                continue;
+            }
+        };
+
+        let fg = match rule.settings.get("foreground").and_then(color_value) {
+            Some(fg) => Some(fg),
+            None if rule.settings.contains_key("fontStyle") => None,
+            None => continue,
+        };
+        let font_style = rule
+            .settings
+            .get("fontStyle")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+
+        for selector in &selectors {
+            // A selector more specific than every table entry only applies to
+            // the closest one: `keyword.operator.new` styles operators, not keywords
+            let closest = SCOPE_MAP
+                .iter()
+                .filter(|(tm_scope, _)| is_scope_prefix(tm_scope, selector))
+                .map(|(tm_scope, _)| tm_scope.len())
+                .max();
+
+            for (tm_scope, nexus_scope) in SCOPE_MAP {
+                if selector.len() > tm_scope.len() && Some(tm_scope.len()) != closest {
+                    continue;
+                }
+                let specificity = match match_specificity(selector, tm_scope) {
+                    Some(s) => s,
+                    None => continue,
+                };
+
+                let previous = best.get(nexus_scope).copied();
+                if let Some((prev_specificity, _)) = previous {
+                    if specificity < prev_specificity {
+                        continue;
+                    }
+                }
+
+                let base_fg = previous.map(|(_, s)| s.fg).unwrap_or(theme.ui.foreground);
+                let style = Style {
+                    fg: fg.unwrap_or(base_fg),
+                    bold: font_style.contains("bold"),
+                    italic: font_style.contains("italic"),
+                    underline: font_style.contains("underline"),
+                };
+                best.insert(nexus_scope, (specificity, style));
+            }
+        }
+    }
+
+    for (scope, (_, style)) in best {
+        theme.scopes.insert(scope.to_string(), style);
+    }
+
+    Ok(theme)
+}
+
+/// Split a rule's `scope` (string with commas, or array) into selectors,
+/// keeping only the innermost scope of descendant selectors
+fn scope_selectors(scope: &Value) -> Vec<String> {
+    let raw: Vec<&str> = match scope {
+        Value::String(s) => s.split(',').collect(),
+        Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+
+    raw.iter()
+        .filter_map(|s| s.split_whitespace().last())
+        .filter(|s| !s.starts_with('-'))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// True if `short` equals `long` or is one of its dotted parents
+fn is_scope_prefix(short: &str, long: &str) -> bool {
+    long == short || (long.starts_with(short) && long.as_bytes().get(short.len()) == Some(&b'.'))
+}
+
+/// How specifically `selector` targets `tm_scope`, measured in matching
+/// dot-separated segments. `None` if the selector doesn't apply.
+fn match_specificity(selector: &str, tm_scope: &str) -> Option<usize> {
+    if is_scope_prefix(selector, tm_scope) {
+        // Broad selector (`entity`) covering a more specific scope
+        Some(selector.split('.').count())
+    } else if is_scope_prefix(tm_scope, selector) {
+        // Language-specific selector (`entity.name.function.rust`)
+        Some(tm_scope.split('.').count())
+    } else {
+        None
+    }
+}
+
+fn color_value(value: &Value) -> Option<[f32; 4]> {
+    value.as_str().and_then(|s| parse_hex_color(s).ok())
+}
+
+/// VS Code theme files are JSONC: strip `//` and `/* */` comments and
+/// trailing commas so serde_json can parse them
+fn strip_json_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            ',' => {
+                // Drop trailing commas before a closing bracket
+                let rest: String = chars.clone().take_while(|c| c.is_whitespace()).collect();
+                let next = chars.clone().nth(rest.chars().count());
+                if !matches!(next, Some('}') | Some(']')) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_vscode_theme() {
+        let source = r##"{
+            // A comment
+            "name": "Test Theme",
+            "type": "light",
+            "colors": {
+                "editor.background": "#ffffff",
+                "editor.foreground": "#333333",
+            },
+            "tokenColors": [
+                { "scope": "keyword", "settings": { "foreground": "#0000ff" } },
+                { "scope": "keyword.operator", "settings": { "foreground": "#111111" } },
+                { "scope": ["comment", "string.quoted"], "settings": { "foreground": "#008000", "fontStyle": "italic" } },
+                { "scope": "entity.name.function.rust", "settings": { "foreground": "#795e26", "fontStyle": "bold" } }
+            ]
+        }"##;
+
+        let theme = import(source).unwrap();
+        assert_eq!(theme.name, "Test Theme");
+        assert_eq!(theme.variant, ThemeVariant::Light);
+        assert_eq!(theme.ui.background, [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(theme.color("keyword"), [0.0, 0.0, 1.0, 1.0]);
+        assert_ne!(theme.color("operator"), theme.color("keyword"));
+        assert!(theme.style("comment").italic);
+        assert!(theme.style("function.method").bold);
+    }
+
+    #[test]
+    fn test_import_textmate_theme() {
+        let source = r##"{
+            "name": "TM",
+            "settings": [
+                { "settings": { "background": "#000000", "foreground": "#eeeeee" } },
+                { "scope": "constant.numeric", "settings": { "foreground": "#ff0000" } }
+            ]
+        }"##;
+
+        let theme = import(source).unwrap();
+        assert_eq!(theme.ui.background, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(theme.color("number"), [1.0, 0.0, 0.0, 1.0]);
+    }
+}
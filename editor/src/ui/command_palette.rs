@@ -0,0 +1,309 @@
+use std::path::PathBuf;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::file_tree::SortMode;
+use crate::history::History;
+use crate::indent::IndentStyle;
+use crate::input::InputHandler;
+use crate::line_ops::LineCommand;
+use crate::search::SearchScope;
+use crate::ui::text_input::{InputResponse, TextInput};
+
+/// Commands that can be run from the command palette
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteCommand {
+    SetTheme(String),
+    ReloadThemes,
+    /// Open the language picker for the active tab
+    ChangeLanguage,
+    /// Set the active tab's language; `None` is plain text
+    SetLanguage(Option<String>),
+    /// Fold the innermost range around the cursor
+    Fold,
+    /// Unfold the innermost folded range around the cursor
+    Unfold,
+    FoldAll,
+    UnfoldAll,
+    /// Fold every range nested this deep; 1 is top level
+    FoldLevel(usize),
+    /// Grow the selection to the enclosing syntax node
+    ExpandSelection,
+    ShrinkSelection,
+    /// Select a text object by its `textobjects.scm` capture name
+    SelectTextObject(String),
+    ToggleOutline,
+    /// Open the symbol picker for the active tab
+    GoToSymbolInFile,
+    /// Move the cursor to a line and column of the active tab
+    GoToPosition(usize, usize),
+    /// Recompute the indentation of the selected lines, or the whole tab
+    Reindent,
+    /// Run a line editing command on the active tab
+    Line(LineCommand),
+    ToggleComment,
+    ToggleBlockComment,
+    SetIndentStyle(IndentStyle),
+    /// Move the cursor to the bracket matching the one next to it
+    GoToMatchingBracket,
+    ToggleRainbowBrackets,
+    /// Open the symbol picker for the whole workspace
+    GoToSymbolInWorkspace,
+    /// Open the file picker for every workspace root
+    GoToFile,
+    /// Show or hide dotfiles in the file tree
+    ToggleHiddenFiles,
+    /// Show the file tree filtered to the names typed
+    FilterFileTree,
+    /// Show the file tree's directories expanded this many levels deep
+    ExpandFileTree(usize),
+    SortFileTree(SortMode),
+    /// Show the file tree with the active tab's file selected
+    RevealActiveFile,
+    /// Copy the active tab's path, relative to the workspace with `true`
+    CopyActiveFilePath(bool),
+    /// Open find with the word under the cursor and go to its next occurrence
+    FindWordUnderCursor,
+    /// Open the project search panel, in replace mode with `true`
+    FindInFiles(bool),
+    /// Open a file, or switch to its tab, at a line and column
+    OpenLocation(PathBuf, usize, usize),
+    /// Open find limited to part of the active tab
+    SetSearchScope(SearchScope),
+}
+
+#[derive(Debug, Clone)]
+pub struct PaletteItem {
+    pub label: String,
+    pub command: PaletteCommand,
+}
+
+impl PaletteItem {
+    pub fn new(label: impl Into<String>, command: PaletteCommand) -> Self {
+        Self {
+            label: label.into(),
+            command,
+        }
+    }
+}
+
+pub struct CommandPalette {
+    pub is_active: bool,
+    pub query: TextInput,
+    pub selected_index: usize,
+    /// Labels of commands run from the command list, recalled with Up
+    pub history: History,
+    items: Vec<PaletteItem>,
+    filtered: Vec<usize>,
+    /// The items are the command list, whose choices go into `history`
+    remember_history: bool,
+    /// Opened with `open_loading`, waiting for `set_loaded`
+    loading: bool,
+}
+
+impl CommandPalette {
+    pub fn new() -> Self {
+        Self {
+            is_active: false,
+            query: TextInput::new(),
+            selected_index: 0,
+            history: History::default(),
+            items: Vec::new(),
+            filtered: Vec::new(),
+            remember_history: false,
+            loading: false,
+        }
+    }
+
+    /// Open the palette with the given items
+    pub fn open(&mut self, items: Vec<PaletteItem>) {
+        self.is_active = true;
+        self.query.clear();
+        self.items = items;
+        self.remember_history = false;
+        self.loading = false;
+        self.refilter();
+    }
+
+    /// Open the palette with items that are still being loaded, such as a
+    /// file list from the last time
+    pub fn open_loading(&mut self, items: Vec<PaletteItem>) {
+        self.open(items);
+        self.loading = true;
+    }
+
+    /// Replace the items of a palette opened with `open_loading`, keeping
+    /// the query. Ignored once the palette was closed or opened with others.
+    pub fn set_loaded(&mut self, items: Vec<PaletteItem>) {
+        if self.is_active && self.loading {
+            self.items = items;
+            self.loading = false;
+            self.refilter();
+        }
+    }
+
+    /// Open the palette with the command list, remembering what is run
+    pub fn open_commands(&mut self, items: Vec<PaletteItem>) {
+        self.open(items);
+        self.remember_history = true;
+    }
+
+    pub fn close(&mut self) {
+        self.is_active = false;
+        self.query.clear();
+        self.items.clear();
+        self.filtered.clear();
+        self.selected_index = 0;
+        self.loading = false;
+        self.history.reset();
+    }
+
+    /// Close the palette, returning the selected command
+    pub fn accept(&mut self) -> Option<PaletteCommand> {
+        let command = self.selected_command();
+        if let Some(&idx) = self.filtered.get(self.selected_index).filter(|_| self.remember_history) {
+            self.history.push(&self.items[idx].label);
+        }
+        self.close();
+        command
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        self.history.reset();
+        self.query.insert(text);
+        self.refilter();
+    }
+
+    /// Edit the query by a key; false if the query doesn't use the key
+    pub fn handle_key(&mut self, key_code: KeyCode, modifiers: ModifiersState, clipboard: &mut InputHandler) -> bool {
+        match self.query.handle_key(key_code, modifiers, clipboard) {
+            InputResponse::Edited => {
+                self.history.reset();
+                self.refilter();
+            }
+            InputResponse::Handled => {}
+            InputResponse::Ignored => return false,
+        }
+        true
+    }
+
+    /// Move up the list; from its top, or while recalling, show the
+    /// previous command run instead
+    pub fn move_selection_up(&mut self) {
+        if self.selected_index > 0 && !self.history.is_browsing() {
+            self.selected_index -= 1;
+        } else if self.remember_history && self.query.recall(&mut self.history, true) {
+            self.refilter();
+        }
+    }
+
+    pub fn move_selection_down(&mut self) {
+        if self.history.is_browsing() {
+            if self.query.recall(&mut self.history, false) {
+                self.refilter();
+            }
+        } else if self.selected_index + 1 < self.filtered.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    /// Items matching the current query, in display order
+    pub fn visible_items(&self) -> impl Iterator<Item = &PaletteItem> {
+        self.filtered.iter().map(|&idx| &self.items[idx])
+    }
+
+    pub fn selected_command(&self) -> Option<PaletteCommand> {
+        self.filtered
+            .get(self.selected_index)
+            .map(|&idx| self.items[idx].command.clone())
+    }
+
+    fn refilter(&mut self) {
+        let query = self.query.text().to_lowercase();
+        self.filtered = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| fuzzy_match(&item.label.to_lowercase(), &query))
+            .map(|(idx, _)| idx)
+            .collect();
+        self.selected_index = 0;
+    }
+}
+
+impl Default for CommandPalette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// True if every character of `query` appears in `text` in order
+fn fuzzy_match(text: &str, query: &str) -> bool {
+    let mut chars = text.chars();
+    query.chars().all(|q| chars.any(|c| c == q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filtering() {
+        let mut palette = CommandPalette::new();
+        palette.open(vec![
+            PaletteItem::new("Theme: Nexus Dark", PaletteCommand::SetTheme("Nexus Dark".into())),
+            PaletteItem::new("Theme: Nexus Light", PaletteCommand::SetTheme("Nexus Light".into())),
+            PaletteItem::new("Reload Themes", PaletteCommand::ReloadThemes),
+        ]);
+        assert_eq!(palette.visible_items().count(), 3);
+
+        palette.insert("thlight");
+        assert_eq!(palette.visible_items().count(), 1);
+        assert_eq!(
+            palette.selected_command(),
+            Some(PaletteCommand::SetTheme("Nexus Light".into()))
+        );
+
+        let mut clipboard = InputHandler::new();
+        palette.handle_key(KeyCode::ArrowLeft, ModifiersState::empty(), &mut clipboard);
+        palette.handle_key(KeyCode::Backspace, ModifiersState::CONTROL, &mut clipboard);
+        assert_eq!(palette.query.text(), "t");
+        assert_eq!(palette.visible_items().count(), 3);
+        palette.handle_key(KeyCode::End, ModifiersState::empty(), &mut clipboard);
+        palette.handle_key(KeyCode::Backspace, ModifiersState::empty(), &mut clipboard);
+        palette.insert("th");
+        assert_eq!(palette.query.text(), "th");
+        assert_eq!(palette.visible_items().count(), 3);
+
+        // Commands run from the command list can be recalled from the top
+        palette.close();
+        palette.open_commands(vec![
+            PaletteItem::new("Fold All", PaletteCommand::FoldAll),
+            PaletteItem::new("Unfold All", PaletteCommand::UnfoldAll),
+        ]);
+        palette.move_selection_down();
+        assert_eq!(palette.accept(), Some(PaletteCommand::UnfoldAll));
+        palette.open_commands(vec![
+            PaletteItem::new("Fold All", PaletteCommand::FoldAll),
+            PaletteItem::new("Unfold All", PaletteCommand::UnfoldAll),
+        ]);
+        palette.move_selection_up();
+        assert_eq!(palette.query.text(), "Unfold All");
+        assert_eq!(palette.selected_command(), Some(PaletteCommand::UnfoldAll));
+        palette.move_selection_down();
+        assert_eq!(palette.query.text(), "");
+
+        // Items loaded after opening replace the old ones under the same query
+        palette.close();
+        palette.open_loading(vec![PaletteItem::new("Fold All", PaletteCommand::FoldAll)]);
+        palette.insert("un");
+        assert_eq!(palette.visible_items().count(), 0);
+        palette.set_loaded(vec![
+            PaletteItem::new("Fold All", PaletteCommand::FoldAll),
+            PaletteItem::new("Unfold All", PaletteCommand::UnfoldAll),
+        ]);
+        assert_eq!(palette.selected_command(), Some(PaletteCommand::UnfoldAll));
+        palette.close();
+        palette.set_loaded(vec![PaletteItem::new("Fold All", PaletteCommand::FoldAll)]);
+        assert!(!palette.is_active && palette.visible_items().count() == 0);
+    }
+}
@@ -6,6 +6,11 @@
 // - Tabs
 // - Settings panel
 
+pub mod command_palette;
+pub mod outline;
+pub mod project_search;
+pub mod text_input;
+
 pub struct UiState {
     pub file_explorer_open: bool,
     pub command_palette_open: bool,
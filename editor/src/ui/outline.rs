@@ -0,0 +1,30 @@
+use crate::symbols::{self, Symbol};
+
+/// Side panel listing the active tab's symbols
+pub struct OutlinePanel {
+    pub is_visible: bool,
+}
+
+impl OutlinePanel {
+    pub fn new() -> Self {
+        Self { is_visible: false }
+    }
+
+    pub fn toggle_visibility(&mut self) {
+        self.is_visible = !self.is_visible;
+    }
+
+    /// Rows of the panel: each symbol indented by its nesting depth
+    pub fn rows(symbols: &[Symbol]) -> Vec<String> {
+        symbols::flatten(symbols)
+            .into_iter()
+            .map(|(depth, symbol)| format!("{}{}", "  ".repeat(depth), symbol.label()))
+            .collect()
+    }
+}
+
+impl Default for OutlinePanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,417 @@
+use regex::Regex;
+use ropey::Rope;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::history::History;
+use crate::input::InputHandler;
+use crate::project_search::{self, FileResults, ProjectQuery, ProjectSearch};
+use crate::search::{self, Pattern};
+use crate::ui::text_input::{InputResponse, TextInput};
+use crate::workspace::{Workspace, WorkspaceRoot};
+
+/// Text fields of the panel, in Tab order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Query,
+    Replace,
+    Include,
+    Exclude,
+}
+
+/// A selectable row of the results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Row {
+    File(usize),
+    /// A match, by file and match index
+    Match(usize, usize),
+}
+
+/// Bottom panel searching the whole workspace, with the results grouped by
+/// file and, in replace mode, a preview of each replacement
+pub struct ProjectSearchPanel {
+    pub is_visible: bool,
+    /// Keys go to the panel rather than the editor
+    pub is_focused: bool,
+    pub is_replace_mode: bool,
+    pub query: TextInput,
+    pub replace_text: TextInput,
+    /// Comma-separated globs of files to search, e.g. `*.rs, docs/**`
+    pub include: TextInput,
+    /// Comma-separated globs of files to leave out
+    pub exclude: TextInput,
+    /// Field typing goes to while no result is selected
+    pub field: Field,
+    pub case_sensitive: bool,
+    pub use_regex: bool,
+    pub whole_word: bool,
+    pub preserve_case: bool,
+    /// Files with matches, sorted by path
+    pub results: Vec<FileResults>,
+    /// Index into `rows()`; `None` while editing a field
+    pub selected: Option<usize>,
+    /// Why the query or a glob is invalid, if one is
+    pub error: Option<String>,
+    /// The search stopped at `project_search::MAX_MATCHES`
+    pub truncated: bool,
+    /// The query compiled by the last `start`, for previews and replacing
+    regex: Option<Regex>,
+    job: Option<ProjectSearch>,
+}
+
+impl ProjectSearchPanel {
+    pub fn new() -> Self {
+        Self {
+            is_visible: false,
+            is_focused: false,
+            is_replace_mode: false,
+            query: TextInput::new(),
+            replace_text: TextInput::new(),
+            include: TextInput::new(),
+            exclude: TextInput::new(),
+            field: Field::Query,
+            case_sensitive: false,
+            use_regex: false,
+            whole_word: false,
+            preserve_case: false,
+            results: Vec::new(),
+            selected: None,
+            error: None,
+            truncated: false,
+            regex: None,
+            job: None,
+        }
+    }
+
+    /// Show and focus the panel with the query field selected
+    pub fn open(&mut self, replace: bool) {
+        self.is_visible = true;
+        self.is_focused = true;
+        self.is_replace_mode = replace;
+        self.field = Field::Query;
+        self.selected = None;
+    }
+
+    pub fn close(&mut self) {
+        self.is_visible = false;
+        self.is_focused = false;
+        self.job = None;
+    }
+
+    pub fn field_input(&self, field: Field) -> &TextInput {
+        match field {
+            Field::Query => &self.query,
+            Field::Replace => &self.replace_text,
+            Field::Include => &self.include,
+            Field::Exclude => &self.exclude,
+        }
+    }
+
+    fn field_mut(&mut self) -> &mut TextInput {
+        match self.field {
+            Field::Query => &mut self.query,
+            Field::Replace => &mut self.replace_text,
+            Field::Include => &mut self.include,
+            Field::Exclude => &mut self.exclude,
+        }
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        self.field_mut().insert(text);
+    }
+
+    /// Edit the field by a key; false if the field doesn't use the key
+    pub fn handle_key(&mut self, key_code: KeyCode, modifiers: ModifiersState, clipboard: &mut InputHandler) -> bool {
+        self.field_mut().handle_key(key_code, modifiers, clipboard) != InputResponse::Ignored
+    }
+
+    /// Show an older or newer entry of the query or replacement history in
+    /// its field; false if the field has no history or it has no such entry
+    pub fn recall(&mut self, queries: &mut History, replacements: &mut History, older: bool) -> bool {
+        let history = match self.field {
+            Field::Query => queries,
+            Field::Replace => replacements,
+            Field::Include | Field::Exclude => return false,
+        };
+        if !older && !history.is_browsing() {
+            return false;
+        }
+        self.field_mut().recall(history, older)
+    }
+
+    /// Move to the next field, skipping the replacement outside replace mode
+    pub fn next_field(&mut self) {
+        self.selected = None;
+        self.field = match self.field {
+            Field::Query if self.is_replace_mode => Field::Replace,
+            Field::Query | Field::Replace => Field::Include,
+            Field::Include => Field::Exclude,
+            Field::Exclude => Field::Query,
+        };
+    }
+
+    /// Search the workspace's roots again. `open` has the text of files
+    /// open in tabs, which may not be saved.
+    pub fn start(&mut self, roots: &[WorkspaceRoot], open: HashMap<PathBuf, Rope>) {
+        self.job = None;
+        self.results.clear();
+        self.selected = None;
+        self.error = None;
+        self.truncated = false;
+        self.regex = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        let regex = match search::build_regex(self.query.text(), self.case_sensitive, self.use_regex) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.error = Some(search::regex_error(&e));
+                return;
+            }
+        };
+        let globs = |text: &str| -> Vec<String> {
+            text.split(',').map(str::trim).filter(|glob| !glob.is_empty()).map(String::from).collect()
+        };
+        let query = ProjectQuery {
+            roots: roots.to_vec(),
+            pattern: Pattern { regex: regex.clone(), whole_word: self.whole_word },
+            include: globs(self.include.text()),
+            exclude: globs(self.exclude.text()),
+            open,
+        };
+        match ProjectSearch::spawn(query) {
+            Ok(job) => {
+                self.job = Some(job);
+                self.regex = Some(regex);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Take in files searched since the last frame; true if there were any
+    pub fn poll(&mut self) -> bool {
+        let Some(job) = &self.job else {
+            return false;
+        };
+        let (files, done) = job.poll();
+        let changed = !files.is_empty() || done;
+        for file in files {
+            let at = self.results.partition_point(|f| f.path < file.path);
+            self.results.insert(at, file);
+        }
+        if done {
+            self.truncated = job.truncated();
+            self.job = None;
+        }
+        changed
+    }
+
+    /// True while files are still being searched
+    pub fn is_searching(&self) -> bool {
+        self.job.is_some()
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.results.iter().map(|file| file.matches.len()).sum()
+    }
+
+    /// Files and, unless the file is collapsed, their matches
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (i, file) in self.results.iter().enumerate() {
+            rows.push(Row::File(i));
+            if !file.collapsed {
+                rows.extend((0..file.matches.len()).map(|j| Row::Match(i, j)));
+            }
+        }
+        rows
+    }
+
+    pub fn selected_row(&self) -> Option<Row> {
+        self.rows().get(self.selected?).copied()
+    }
+
+    /// Move the selection by `delta` rows; going up from the first row
+    /// returns to the fields
+    pub fn move_selection(&mut self, delta: isize) {
+        let count = self.rows().len();
+        self.selected = match self.selected {
+            None if delta > 0 && count > 0 => Some(0),
+            None => None,
+            Some(row) if delta < 0 && row < delta.unsigned_abs() => None,
+            Some(row) => Some(row.saturating_add_signed(delta).min(count.saturating_sub(1))),
+        };
+    }
+
+    /// Include or leave out the selected match in replacing, or all matches
+    /// of the selected file
+    pub fn toggle_selected(&mut self) {
+        match self.selected_row() {
+            Some(Row::File(i)) => {
+                let file = &mut self.results[i];
+                let included = !file.matches.iter().all(|m| m.included);
+                file.matches.iter_mut().for_each(|m| m.included = included);
+            }
+            Some(Row::Match(i, j)) => {
+                let found = &mut self.results[i].matches[j];
+                found.included = !found.included;
+            }
+            None => {}
+        }
+    }
+
+    /// Collapse or expand the file of the selected row
+    pub fn set_collapsed(&mut self, collapsed: bool) {
+        let file = match self.selected_row() {
+            Some(Row::File(i) | Row::Match(i, _)) => i,
+            None => return,
+        };
+        self.results[file].collapsed = collapsed;
+        self.selected = self.rows().iter().position(|row| *row == Row::File(file));
+    }
+
+    /// File, line and column of the selected row
+    pub fn selected_location(&self) -> Option<(PathBuf, usize, usize)> {
+        match self.selected_row()? {
+            Row::File(i) => Some((self.results[i].path.clone(), 0, 0)),
+            Row::Match(i, j) => {
+                let found = &self.results[i].matches[j];
+                Some((self.results[i].path.clone(), found.line, found.column))
+            }
+        }
+    }
+
+    /// Replacements of the included matches of `file` in `text`; `None` if
+    /// the file changed since it was searched
+    pub fn replacements(&self, file: &FileResults, text: &Rope) -> Option<Vec<(Range<usize>, String)>> {
+        let regex = self.regex.as_ref()?;
+        project_search::file_replacements(file, text, regex, self.replace_text.text(), self.use_regex, self.preserve_case)
+    }
+
+    /// Lines of the results as shown, each with the row it belongs to.
+    /// Matches come with their context lines, which are shown once when
+    /// matches are close together.
+    pub fn lines(&self, workspace: &Workspace) -> Vec<(String, Option<usize>)> {
+        let mut lines = Vec::new();
+        let number = |line: usize| format!("{:>5}", line + 1);
+        let mut shown_until = None;
+        for (row, entry) in self.rows().into_iter().enumerate() {
+            match entry {
+                Row::File(i) => {
+                    let file = &self.results[i];
+                    let path = workspace.relative(&file.path);
+                    let marker = if file.collapsed { "▸" } else { "▾" };
+                    lines.push((format!("{} {} ({})", marker, path.display(), file.matches.len()), Some(row)));
+                    shown_until = None;
+                }
+                Row::Match(i, j) => {
+                    let found = &self.results[i].matches[j];
+                    let fresh = |line: &usize| shown_until.is_none_or(|until| *line > until);
+                    for (line, text) in found.before.iter().filter(|(line, _)| fresh(line)) {
+                        lines.push((format!("       {}  {}", number(*line), text), None));
+                    }
+
+                    let checkbox = if found.included { "[x]" } else { "[ ]" };
+                    let first_line = |text: &str| text.lines().next().unwrap_or_default().to_string();
+                    let mut label = format!("{} {}: {}", checkbox, number(found.line), first_line(&found.text));
+                    if self.is_replace_mode {
+                        let preview = self
+                            .regex
+                            .as_ref()
+                            .and_then(|regex| found.preview(regex, self.replace_text.text(), self.use_regex, self.preserve_case));
+                        if let Some(preview) = preview {
+                            label.push_str(&format!("  →  {}", first_line(&preview).trim_start()));
+                        }
+                    }
+                    lines.push((label, Some(row)));
+
+                    let next_start = self.results[i].matches.get(j + 1).map(|next| next.line);
+                    let mut last = found.end_line;
+                    for (line, text) in &found.after {
+                        // The next match shows its own line
+                        if next_start.is_some_and(|next| *line >= next) {
+                            break;
+                        }
+                        lines.push((format!("       {}  {}", number(*line), text), None));
+                        last = *line;
+                    }
+                    shown_until = Some(last);
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl Default for ProjectSearchPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_search::FileMatch;
+
+    fn file(path: &str, lines: &[usize]) -> FileResults {
+        FileResults {
+            path: PathBuf::from(path),
+            matches: lines
+                .iter()
+                .map(|&line| FileMatch {
+                    line,
+                    column: 0,
+                    end_line: line,
+                    end_column: 3,
+                    range: line * 4..line * 4 + 3,
+                    text: "foo\n".to_string(),
+                    before: (line.saturating_sub(1)..line).map(|l| (l, format!("line {}", l))).collect(),
+                    after: vec![(line + 1, format!("line {}", line + 1))],
+                    included: true,
+                })
+                .collect(),
+            collapsed: false,
+        }
+    }
+
+    #[test]
+    fn test_results_rows() {
+        let mut panel = ProjectSearchPanel::new();
+        panel.results = vec![file("/w/a.rs", &[0, 1]), file("/w/b.rs", &[5])];
+        assert_eq!(panel.rows().len(), 5);
+
+        // Context between adjacent matches is shown once
+        let lines = panel.lines(&Workspace::folder(PathBuf::from("/w")));
+        let text: Vec<&str> = lines.iter().map(|(line, _)| line.as_str()).collect();
+        assert_eq!(
+            text,
+            [
+                "▾ a.rs (2)",
+                "[x]     1: foo",
+                "[x]     2: foo",
+                "           3  line 2",
+                "▾ b.rs (1)",
+                "           5  line 4",
+                "[x]     6: foo",
+                "           7  line 6",
+            ]
+        );
+
+        panel.move_selection(1);
+        panel.toggle_selected();
+        assert!(panel.results[0].matches.iter().all(|m| !m.included));
+        panel.move_selection(2);
+        panel.toggle_selected();
+        assert!(panel.results[0].matches[1].included);
+        assert_eq!(panel.selected_location(), Some((PathBuf::from("/w/a.rs"), 1, 0)));
+
+        panel.set_collapsed(true);
+        assert_eq!((panel.selected, panel.rows().len()), (Some(0), 3));
+        panel.move_selection(-1);
+        assert_eq!(panel.selected, None);
+    }
+}
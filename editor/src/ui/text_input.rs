@@ -0,0 +1,249 @@
+use std::ops::Range;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::history::History;
+use crate::input::InputHandler;
+use crate::motion::WordRules;
+
+/// What a key did to a text input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputResponse {
+    /// The text changed
+    Edited,
+    /// The caret or selection moved, or the selection was copied
+    Handled,
+    /// The key is not one the input uses
+    Ignored,
+}
+
+/// A single-line text field with a caret, a selection and clipboard keys,
+/// used by the find bar, the project search panel and the command palette.
+/// Positions are char indices into the text.
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+    /// The other end of the selection, if there is one
+    anchor: Option<usize>,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replace the text, with the caret at its end
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.len();
+        self.anchor = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text(String::new());
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Selected chars, if any
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor.filter(|&anchor| anchor != self.cursor)?;
+        Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        let selection = self.selection()?;
+        Some(&self.text[self.byte(selection.start)..self.byte(selection.end)])
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.len();
+    }
+
+    /// Move the caret to a char index, extending the selection with `extend`
+    pub fn move_to(&mut self, position: usize, extend: bool) {
+        if !extend {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some(self.cursor);
+        }
+        self.cursor = position.min(self.len());
+    }
+
+    /// Type `text` over the selection. Only its first line is kept.
+    pub fn insert(&mut self, text: &str) {
+        let text = text.lines().next().unwrap_or_default();
+        let range = self.selection().unwrap_or(self.cursor..self.cursor);
+        self.replace(range, text);
+    }
+
+    /// Delete the selection, or the char (or word with `word`) before the caret
+    pub fn backspace(&mut self, word: bool) -> bool {
+        let start = match self.selection() {
+            Some(selection) => return self.replace(selection, ""),
+            None if word => WordRules::default().previous_stop(&self.chars(), self.cursor).unwrap_or(0),
+            None => self.cursor.saturating_sub(1),
+        };
+        self.replace(start..self.cursor, "")
+    }
+
+    /// Delete the selection, or the char (or word with `word`) after the caret
+    pub fn delete(&mut self, word: bool) -> bool {
+        let end = match self.selection() {
+            Some(selection) => return self.replace(selection, ""),
+            None if word => WordRules::default().next_stop(&self.chars(), self.cursor).unwrap_or(self.cursor),
+            None => (self.cursor + 1).min(self.len()),
+        };
+        self.replace(self.cursor..end, "")
+    }
+
+    /// Show an older or newer entry of `history` in place of the text
+    pub fn recall(&mut self, history: &mut History, older: bool) -> bool {
+        let entry = if older { history.previous(&self.text) } else { history.next() };
+        match entry {
+            Some(entry) => {
+                self.set_text(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Edit or move by a key: arrows and Home / End, with Shift to select
+    /// and Ctrl to go by words; Backspace / Delete; Ctrl+A/C/X/V
+    pub fn handle_key(&mut self, key_code: KeyCode, modifiers: ModifiersState, clipboard: &mut InputHandler) -> InputResponse {
+        let (ctrl, shift) = (modifiers.control_key(), modifiers.shift_key());
+        let rules = WordRules::default();
+        let edited = match key_code {
+            KeyCode::ArrowLeft => {
+                let target = match self.selection() {
+                    Some(selection) if !shift && !ctrl => selection.start,
+                    _ if ctrl => rules.previous_stop(&self.chars(), self.cursor).unwrap_or(0),
+                    _ => self.cursor.saturating_sub(1),
+                };
+                self.move_to(target, shift);
+                false
+            }
+            KeyCode::ArrowRight => {
+                let target = match self.selection() {
+                    Some(selection) if !shift && !ctrl => selection.end,
+                    _ if ctrl => rules.next_stop(&self.chars(), self.cursor).unwrap_or(self.cursor),
+                    _ => self.cursor + 1,
+                };
+                self.move_to(target, shift);
+                false
+            }
+            KeyCode::Home => {
+                self.move_to(0, shift);
+                false
+            }
+            KeyCode::End => {
+                self.move_to(self.len(), shift);
+                false
+            }
+            KeyCode::Backspace => self.backspace(ctrl),
+            KeyCode::Delete => self.delete(ctrl),
+            KeyCode::KeyA if ctrl => {
+                self.select_all();
+                false
+            }
+            KeyCode::KeyC if ctrl => {
+                if let Some(text) = self.selected_text() {
+                    clipboard.set_clipboard_text(text);
+                }
+                false
+            }
+            KeyCode::KeyX if ctrl => {
+                if let Some(text) = self.selected_text() {
+                    clipboard.set_clipboard_text(text);
+                }
+                self.selection().is_some_and(|selection| self.replace(selection, ""))
+            }
+            KeyCode::KeyV if ctrl => match clipboard.clipboard_text() {
+                Some(text) => {
+                    self.insert(&text);
+                    true
+                }
+                None => false,
+            },
+            _ => return InputResponse::Ignored,
+        };
+        if edited {
+            InputResponse::Edited
+        } else {
+            InputResponse::Handled
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn chars(&self) -> Vec<char> {
+        self.text.chars().collect()
+    }
+
+    /// Byte offset of a char index
+    fn byte(&self, position: usize) -> usize {
+        self.text.char_indices().nth(position).map_or(self.text.len(), |(byte, _)| byte)
+    }
+
+    /// Replace chars with `text`, leaving the caret after it; true if the
+    /// text changed
+    fn replace(&mut self, range: Range<usize>, text: &str) -> bool {
+        let bytes = self.byte(range.start)..self.byte(range.end);
+        self.text.replace_range(bytes, text);
+        self.cursor = range.start + text.chars().count();
+        self.anchor = None;
+        !range.is_empty() || !text.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_input() {
+        let mut input = TextInput::new();
+        input.insert("héllo wörld\nsecond line");
+        assert_eq!((input.text(), input.cursor()), ("héllo wörld", 11));
+
+        // Typing replaces the selection
+        input.move_to(6, false);
+        input.move_to(11, true);
+        assert_eq!(input.selected_text(), Some("wörld"));
+        input.insert("there");
+        assert_eq!(input.text(), "héllo there");
+
+        assert!(input.backspace(true));
+        assert_eq!(input.text(), "héllo ");
+        input.move_to(0, false);
+        assert!(input.delete(false));
+        assert!(!input.backspace(false));
+        assert_eq!((input.text(), input.cursor()), ("éllo ", 0));
+
+        input.select_all();
+        assert!(input.backspace(false));
+        assert!(input.is_empty());
+
+        let mut history = History::default();
+        history.push("older");
+        input.insert("draft");
+        assert!(input.recall(&mut history, true));
+        assert_eq!((input.text(), input.cursor()), ("older", 5));
+        assert!(input.recall(&mut history, false));
+        assert_eq!(input.text(), "draft");
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::{bail, Context, Result};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+/// Extension of workspace files
+pub const WORKSPACE_EXTENSION: &str = "nexus-workspace";
+
+/// A workspace file: the folders of the workspace, e.g.
+///
+/// ```toml
+/// [[folders]]
+/// path = "editor"
+///
+/// [[folders]]
+/// path = "../docs"
+/// name = "Docs"
+/// show_hidden = true
+/// exclude = ["*.pdf", "build/**"]
+/// ```
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    folders: Vec<FolderConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FolderConfig {
+    /// Relative to the workspace file's directory, or absolute
+    path: PathBuf,
+    name: Option<String>,
+    #[serde(default)]
+    show_hidden: bool,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// A root folder of the workspace and its settings
+#[derive(Debug, Clone)]
+pub struct WorkspaceRoot {
+    pub path: PathBuf,
+    /// Shown in the file tree and before paths; the folder's name by default
+    pub name: String,
+    /// List dotfiles in the file tree
+    pub show_hidden: bool,
+    /// Globs of files left out of the tree, quick open, search and the
+    /// symbol index, on top of ignore files
+    pub exclude: Vec<String>,
+    overrides: Override,
+}
+
+impl WorkspaceRoot {
+    pub fn new(path: PathBuf) -> Self {
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+        Self { path, name, show_hidden: false, exclude: Vec::new(), overrides: Override::empty() }
+    }
+
+    fn with_exclude(mut self, exclude: Vec<String>) -> Result<Self> {
+        let mut overrides = OverrideBuilder::new(&self.path);
+        for glob in &exclude {
+            overrides.add(&format!("!{}", glob)).with_context(|| format!("Invalid exclude glob {:?}", glob))?;
+        }
+        self.overrides = overrides.build()?;
+        self.exclude = exclude;
+        Ok(self)
+    }
+
+    /// The exclude globs, to leave their files out of a walk
+    pub fn overrides(&self) -> Override {
+        self.overrides.clone()
+    }
+
+    /// A walk of `dir`, a folder of the root, leaving out `.git`, what ignore
+    /// files and the exclude globs leave out, and dotfiles unless shown
+    pub fn walker(&self, dir: &Path) -> WalkBuilder {
+        let mut walker = WalkBuilder::new(dir);
+        walker
+            .hidden(!self.show_hidden)
+            .require_git(false)
+            .overrides(self.overrides())
+            .filter_entry(|entry| entry.file_name() != ".git");
+        walker
+    }
+
+    /// True if an exclude glob leaves `path` out, or a directory holding it
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        path.ancestors()
+            .take_while(|ancestor| ancestor.starts_with(&self.path) && *ancestor != self.path)
+            .enumerate()
+            .any(|(i, ancestor)| self.overrides.matched(ancestor, is_dir || i > 0).is_ignore())
+    }
+}
+
+/// The folders being worked on: the current directory, or those listed in
+/// a workspace file
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// The workspace file the roots come from, if any
+    pub file: Option<PathBuf>,
+    pub roots: Vec<WorkspaceRoot>,
+}
+
+impl Workspace {
+    /// A workspace of one folder
+    pub fn folder(path: PathBuf) -> Self {
+        Self::folders(vec![path])
+    }
+
+    /// A workspace of these folders, as given on the command line
+    pub fn folders(paths: Vec<PathBuf>) -> Self {
+        Self { file: None, roots: paths.into_iter().map(WorkspaceRoot::new).collect() }
+    }
+
+    /// Load a workspace file. Folders that don't exist are left out.
+    pub fn load(file: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let parsed: WorkspaceFile = toml::from_str(&text).with_context(|| format!("Invalid workspace file {}", file.display()))?;
+        let base = file.parent().unwrap_or(Path::new("."));
+
+        let mut roots = Vec::new();
+        for folder in parsed.folders {
+            let path = match base.join(&folder.path).canonicalize() {
+                Ok(path) if path.is_dir() => path,
+                _ => {
+                    log::warn!("Workspace folder {} not found, skipped", folder.path.display());
+                    continue;
+                }
+            };
+            let mut root = WorkspaceRoot::new(path).with_exclude(folder.exclude)?;
+            root.show_hidden = folder.show_hidden;
+            if let Some(name) = folder.name {
+                root.name = name;
+            }
+            roots.push(root);
+        }
+        if roots.is_empty() {
+            bail!("{} lists no folders that exist", file.display());
+        }
+        Ok(Self { file: Some(file.to_path_buf()), roots })
+    }
+
+    /// Open a workspace file, or a folder as a workspace of its own. A
+    /// folder holding exactly one workspace file opens that file.
+    pub fn open(path: &Path) -> Result<Self> {
+        if is_workspace_file(path) {
+            return Self::load(path);
+        }
+        let workspace_files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_workspace_file(path) && path.is_file())
+            .collect();
+        match workspace_files.as_slice() {
+            [file] => Self::load(file),
+            _ => Ok(Self::folder(path.to_path_buf())),
+        }
+    }
+
+    /// Name for the window title: the workspace file's stem, or the folder's name
+    pub fn name(&self) -> String {
+        match &self.file {
+            Some(file) => file.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+            None => self.roots[0].name.clone(),
+        }
+    }
+
+    /// The root holding `path`; the innermost one if roots are nested
+    pub fn root_of(&self, path: &Path) -> Option<&WorkspaceRoot> {
+        self.roots.iter().filter(|root| path.starts_with(&root.path)).max_by_key(|root| root.path.components().count())
+    }
+
+    /// `path` as shown to the user: relative to its root, after the root's
+    /// name when there are several roots
+    pub fn relative(&self, path: &Path) -> PathBuf {
+        let Some(root) = self.root_of(path) else {
+            return path.to_path_buf();
+        };
+        let relative = path.strip_prefix(&root.path).unwrap_or(path);
+        if self.roots.len() > 1 {
+            Path::new(&root.name).join(relative)
+        } else {
+            relative.to_path_buf()
+        }
+    }
+
+    /// Every file of every root, skipping ignored and excluded files and
+    /// dotfiles unless the root shows them
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for root in &self.roots {
+            files.extend(
+                root.walker(&root.path)
+                    .build()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+                    .map(|entry| entry.into_path()),
+            );
+        }
+        files
+    }
+}
+
+/// The files of a workspace for Go to File, sorted as shown. Listed on a
+/// background thread; the last list is kept while a new one is made.
+#[derive(Default)]
+pub struct FileList {
+    files: Vec<PathBuf>,
+    listing: Option<Receiver<Vec<PathBuf>>>,
+}
+
+impl FileList {
+    /// List the files again in the background, unless a listing is running
+    pub fn refresh(&mut self, workspace: &Workspace) {
+        if self.listing.is_some() {
+            return;
+        }
+        let workspace = workspace.clone();
+        let (sender, receiver) = mpsc::channel();
+        let spawned = std::thread::Builder::new().name("file-list".to_string()).spawn(move || {
+            let mut files = workspace.files();
+            files.sort_by_cached_key(|path| workspace.relative(path));
+            let _ = sender.send(files);
+        });
+        match spawned {
+            Ok(_) => self.listing = Some(receiver),
+            Err(e) => log::error!("Failed to list the workspace files: {}", e),
+        }
+    }
+
+    /// Take a finished listing; true if it replaced the files
+    pub fn poll(&mut self) -> bool {
+        let Some(listing) = &self.listing else {
+            return false;
+        };
+        match listing.try_recv() {
+            Ok(files) => {
+                self.files = files;
+                self.listing = None;
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                self.listing = None;
+                false
+            }
+        }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+pub fn is_workspace_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == WORKSPACE_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_file() {
+        let dir = std::env::temp_dir().join("nexus_test_workspace");
+        let _ = std::fs::remove_dir_all(&dir);
+        for path in ["app/src/main.rs", "app/.env", "app/out.log", "app/generated/x.rs", "docs/guide.md", "docs/.env"] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let file = dir.join("project.nexus-workspace");
+        std::fs::write(
+            &file,
+            "[[folders]]\npath = \"app\"\nexclude = [\"*.log\", \"generated\"]\n\n\
+             [[folders]]\npath = \"docs\"\nname = \"Guides\"\nshow_hidden = true\n\n\
+             [[folders]]\npath = \"missing\"\n",
+        )
+        .unwrap();
+
+        // A folder holding a workspace file opens it
+        let workspace = Workspace::open(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        assert_eq!(workspace.name(), "project");
+        let names: Vec<&str> = workspace.roots.iter().map(|root| root.name.as_str()).collect();
+        assert_eq!(names, ["app", "Guides"]);
+        assert!(workspace.roots[0].is_excluded(&dir.join("app/out.log"), false));
+        assert!(workspace.roots[0].is_excluded(&dir.join("app/generated/x.rs"), false));
+        assert!(!workspace.roots[0].is_excluded(&dir.join("app/src/main.rs"), false));
+
+        assert_eq!(workspace.relative(&dir.join("docs/guide.md")), Path::new("Guides/guide.md"));
+        let mut list = FileList::default();
+        list.refresh(&workspace);
+        while !list.poll() {
+            std::thread::yield_now();
+        }
+        let files: Vec<PathBuf> = list.files().iter().map(|path| workspace.relative(path)).collect();
+        assert_eq!(files, [Path::new("Guides/.env"), Path::new("Guides/guide.md"), Path::new("app/src/main.rs")]);
+
+        let single = Workspace::open(&dir.join("app")).unwrap();
+        assert_eq!(single.relative(&dir.join("app/src/main.rs")), Path::new("src/main.rs"));
+        assert!(Workspace::load(&dir.join("app/src/main.rs")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}